@@ -0,0 +1,138 @@
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::io::{Read, Write};
+
+use crate::nbt;
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const ZLIB_CMF_DEFLATE: u8 = 0x78;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
+pub enum Compression {
+    #[default]
+    Gzip,
+    Zlib,
+    Uncompressed,
+}
+
+impl Compression {
+    pub fn new() -> Compression {
+        Compression::default()
+    }
+
+    /// Sniff the compression of a NBT buffer from its first bytes.
+    ///
+    /// Gzip streams start with the magic number `1F 8B`, zlib streams with a `78 xx` header whose
+    /// 16 bit value is a multiple of 31. Everything else is assumed to be raw NBT, which starts with a tag id.
+    pub fn detect(buffer: &[u8]) -> Option<Compression> {
+        match buffer {
+            [a, b, ..] if [*a, *b] == GZIP_MAGIC => Some(Compression::Gzip),
+            [ZLIB_CMF_DEFLATE, flg, ..]
+                if (u16::from(ZLIB_CMF_DEFLATE) << 8 | u16::from(*flg)) % 31 == 0 =>
+            {
+                Some(Compression::Zlib)
+            }
+            [id, ..] if nbt::NbtTagId::from_u8(*id).is_some() => Some(Compression::Uncompressed),
+            _ => None,
+        }
+    }
+
+    pub fn decompress(&self, buffer: &[u8]) -> io::Result<Vec<u8>> {
+        let mut decompressed_data = Vec::new();
+
+        match self {
+            Compression::Gzip => {
+                GzDecoder::new(buffer).read_to_end(&mut decompressed_data)?;
+            }
+            Compression::Zlib => {
+                ZlibDecoder::new(buffer).read_to_end(&mut decompressed_data)?;
+            }
+            Compression::Uncompressed => decompressed_data.extend_from_slice(buffer),
+        }
+
+        Ok(decompressed_data)
+    }
+
+    pub fn compress(&self, buffer: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(buffer)?;
+                encoder.finish()
+            }
+            Compression::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(buffer)?;
+                encoder.finish()
+            }
+            Compression::Uncompressed => Ok(buffer.to_vec()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_nbt() -> Vec<u8> {
+        // empty root compound named "a"
+        vec![0x0A, 0x00, 0x01, b'a', 0x00]
+    }
+
+    #[test]
+    fn test_detect_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&raw_nbt()).unwrap();
+        let buffer = encoder.finish().unwrap();
+
+        assert_eq!(Compression::detect(&buffer), Some(Compression::Gzip));
+        assert_eq!(Compression::Gzip.decompress(&buffer).unwrap(), raw_nbt());
+    }
+
+    #[test]
+    fn test_detect_zlib() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&raw_nbt()).unwrap();
+        let buffer = encoder.finish().unwrap();
+
+        assert_eq!(Compression::detect(&buffer), Some(Compression::Zlib));
+        assert_eq!(Compression::Zlib.decompress(&buffer).unwrap(), raw_nbt());
+    }
+
+    #[test]
+    fn test_detect_uncompressed() {
+        let buffer = raw_nbt();
+
+        assert_eq!(
+            Compression::detect(&buffer),
+            Some(Compression::Uncompressed)
+        );
+        assert_eq!(
+            Compression::Uncompressed.decompress(&buffer).unwrap(),
+            raw_nbt()
+        );
+    }
+
+    #[test]
+    fn test_compress_round_trip() {
+        for compression in [
+            Compression::Gzip,
+            Compression::Zlib,
+            Compression::Uncompressed,
+        ] {
+            let buffer = compression.compress(&raw_nbt()).unwrap();
+            assert_eq!(Compression::detect(&buffer), Some(compression));
+            assert_eq!(compression.decompress(&buffer).unwrap(), raw_nbt());
+        }
+    }
+
+    #[test]
+    fn test_detect_unknown() {
+        assert_eq!(Compression::detect(&[]), None);
+        assert_eq!(Compression::detect(&[0xFF, 0x00]), None);
+        // 0x78 0x00 is not a valid zlib header and not a valid tag id
+        assert_eq!(Compression::detect(&[0x78, 0x00]), None);
+    }
+}
@@ -1,18 +1,52 @@
 use super::NbtFile;
 use crate::nbt;
+use std::borrow::Cow;
 use std::fmt::Debug;
 
 impl Debug for NbtFile {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // bytes changed by NbtData::patch_value are marked with a * in the hex dumps
+        let patched_ranges = self.nbtdata.patched_ranges();
+
+        if let Some(header) = self.nbtdata.bedrock_header() {
+            writeln!(
+                f,
+                "Bedrock Header - StorageVersion: {}, Length: {}",
+                header.storage_version(),
+                header.length()
+            )?;
+            writeln!(
+                f,
+                "Raw Bytes: StorageVersion[{}:{}] Length[{}:{}]",
+                header.byte_start(),
+                header.byte_start() + 3,
+                header.byte_start() + 4,
+                header.byte_end()
+            )?;
+            writeln!(f, "Hex Dump[{}:{}]", header.byte_start(), header.byte_end())?;
+            formatted_raw_bytes(
+                f,
+                &self.as_raw_bytes()[header.byte_start()..=header.byte_end()],
+                header.byte_start(),
+                0,
+                patched_ranges,
+            )?;
+            writeln!(f)?;
+        }
+
         for nbttag in self.nbtdata.nbt_tags() {
             for _ in 0..nbttag.position().depth() {
                 write!(f, "\t")?;
             }
 
-            let nbttag_value = nbttag.value();
+            // values skipped by a lazy parse are decoded for display only
+            let nbttag_value = self
+                .nbtdata
+                .tag_value(nbttag.position().index())
+                .unwrap_or(Cow::Borrowed(nbttag.value()));
             let tag_name = nbttag.name();
 
-            match nbttag_value {
+            match nbttag_value.as_ref() {
                 nbt::NbtTagType::End(_) => write!(f, "End - {}", tag_name)?,
                 nbt::NbtTagType::Byte(x) => write!(f, "{}[Byte]: {}", tag_name, x)?,
                 nbt::NbtTagType::Short(x) => write!(f, "{}[Short]: {}", tag_name, x)?,
@@ -32,14 +66,55 @@ impl Debug for NbtFile {
                 nbt::NbtTagType::LongArray(_) => {
                     write!(f, "{}[LongArray]: [Values... see dump]", tag_name)?
                 }
+                nbt::NbtTagType::Lazy(x) => {
+                    write!(f, "{}[{:?}]: [Not decoded... see dump]", tag_name, x)?
+                }
+            }
+            if nbttag.has_invalid_name() || nbttag.has_invalid_value() {
+                write!(f, " (invalid MUTF-8, raw bytes kept)")?;
             }
             writeln!(f)?;
 
-            formatted_raw_values(f, nbttag, self.as_raw_bytes())?;
+            formatted_raw_values(f, nbttag, self.as_raw_bytes(), patched_ranges)?;
+
+            writeln!(f)?;
+        }
 
+        if let Some(trailer) = self.nbtdata.trailer() {
+            writeln!(
+                f,
+                "Unparsed Trailer - {} bytes",
+                trailer.byte_end() - trailer.byte_start() + 1
+            )?;
+            writeln!(
+                f,
+                "Hex Dump[{}:{}]",
+                trailer.byte_start(),
+                trailer.byte_end()
+            )?;
+            formatted_raw_bytes(
+                f,
+                &self.as_raw_bytes()[trailer.byte_start()..=trailer.byte_end()],
+                trailer.byte_start(),
+                0,
+                patched_ranges,
+            )?;
             writeln!(f)?;
         }
 
+        for diagnostic in self.nbtdata.diagnostics() {
+            writeln!(f, "Parse Error - {}", diagnostic)?;
+        }
+
+        for range in patched_ranges {
+            writeln!(
+                f,
+                "Patched Bytes[{}:{}]",
+                range.byte_start(),
+                range.byte_end()
+            )?;
+        }
+
         Ok(())
     }
 }
@@ -47,7 +122,8 @@ impl Debug for NbtFile {
 fn formatted_raw_values(
     f: &mut std::fmt::Formatter,
     nbttag: &nbt::NbtTag,
-    rawbytes: &Vec<u8>,
+    rawbytes: &[u8],
+    patched_ranges: &[nbt::NbtByteRange],
 ) -> std::fmt::Result {
     for _ in 0..nbttag.position().depth() {
         write!(f, "\t")?;
@@ -108,7 +184,7 @@ fn formatted_raw_values(
             byte_start_dump = nbttag.position().byte_start_all();
             byte_end_dump = nbttag.position().byte_end_all();
 
-            write!(f, "Value[{}]", "N/A")?;
+            write!(f, "Value[N/A]")?;
         }
         _ => {
             if let Some(x) = nbttag.position().byte_start_value() {
@@ -126,7 +202,11 @@ fn formatted_raw_values(
         }
     }
 
-    let dump_hex = &rawbytes[byte_start_dump..byte_end_dump];
+    // byte ranges are inclusive; tags without bytes of their own (e.g. compounds in a list) have an empty range
+    let dump_hex = rawbytes
+        .get(byte_start_dump..=byte_end_dump)
+        .filter(|_| byte_end_dump >= byte_start_dump)
+        .unwrap_or(&[]);
 
     writeln!(f)?;
     for _ in 0..nbttag.position().depth() {
@@ -134,7 +214,13 @@ fn formatted_raw_values(
     }
     write!(f, "Hex Dump[{}:{}]", byte_start_dump, byte_end_dump)?;
     writeln!(f)?;
-    formatted_raw_bytes(f, dump_hex, nbttag.position().depth())?;
+    formatted_raw_bytes(
+        f,
+        dump_hex,
+        byte_start_dump,
+        nbttag.position().depth(),
+        patched_ranges,
+    )?;
 
     Ok(())
 }
@@ -142,14 +228,15 @@ fn formatted_raw_values(
 fn formatted_raw_bytes(
     f: &mut std::fmt::Formatter,
     rawbytes: &[u8],
+    byte_start: usize,
     depth: i64,
+    patched_ranges: &[nbt::NbtByteRange],
 ) -> std::fmt::Result {
     for _ in 0..depth {
         write!(f, "\t")?;
     }
 
-    for i in 0..rawbytes.len() {
-        let byte = rawbytes[i];
+    for (i, byte) in rawbytes.iter().enumerate() {
         // Print a space every 4 bytes for grouping
         if i % 4 == 0 && i % 32 != 0 {
             write!(f, " ")?;
@@ -161,11 +248,16 @@ fn formatted_raw_bytes(
                 write!(f, "\t")?;
             }
         }
-        // Print the byte as hex
-        write!(f, "{:02X} ", byte)?;
+        // Print the byte as hex, marked if it was patched
+        let offset = byte_start + i;
+        if patched_ranges.iter().any(|range| range.contains(offset)) {
+            write!(f, "{:02X}*", byte)?;
+        } else {
+            write!(f, "{:02X} ", byte)?;
+        }
     }
     // Print a final new line
-    write!(f, "\n")?;
+    writeln!(f)?;
 
     Ok(())
 }
@@ -1,21 +1,33 @@
-use flate2::read::GzDecoder;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs;
-use std::io::BufReader;
 use std::io::Read;
+use std::path::Path;
 use thiserror::Error;
 
+mod compression;
 mod fmt;
 pub mod nbt;
+pub mod region;
 
+pub use compression::Compression;
+pub use nbt::{from_bytes, to_bytes};
 
 #[derive(Error, Debug)]
 pub enum NbtFileError {
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error), // Automatically convert `io::Error` to `NbtReadError`
 
+    #[error("NBT read error: {0}")]
+    NbtRead(#[from] nbt::NbtReadError), // Automatically convert `NbtReadError` to `NbtFileError`
+
+    #[error("NBT write error: {0}")]
+    NbtWrite(#[from] nbt::NbtWriteError), // Automatically convert `NbtWriteError` to `NbtFileError`
+
+    #[error("NBT edit error: {0}")]
+    NbtEdit(#[from] nbt::NbtEditError), // Automatically convert `NbtEditError` to `NbtFileError`
+
     #[error("Json could not be created")]
     JsonWriteFailure, // Custom error for content validation
 }
@@ -23,6 +35,7 @@ pub enum NbtFileError {
 #[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct NbtFile {
     file_path: String,
+    compression: Compression,
     nbtdata: nbt::NbtData,
 }
 
@@ -31,42 +44,197 @@ impl NbtFile {
         NbtFile::default()
     }
 
+    /// Read a NBT file, auto-detecting its compression.
+    ///
+    /// Panics if the file cannot be read or is not valid NBT, use [`NbtFile::try_read`] to handle the error.
     pub fn read(file_path: String) -> Self {
-        let buffer = Self::read_file(&file_path).unwrap();
-        let nbtdata = nbt::NbtData::from_buf(buffer).unwrap();
-        NbtFile { file_path, nbtdata }
+        Self::try_read(file_path).unwrap()
+    }
+
+    /// Read a NBT file with a forced compression.
+    ///
+    /// Panics if the file cannot be read or is not valid NBT, use [`NbtFile::try_read_with_compression`] to handle the error.
+    pub fn read_with_compression(file_path: String, compression: Compression) -> Self {
+        Self::try_read_with_compression(file_path, compression).unwrap()
+    }
+
+    /// Read a NBT file from any path, auto-detecting its compression.
+    ///
+    /// The file is read from the path itself, which does not need to be valid UTF-8;
+    /// only [`NbtFile::file_path`] keeps a lossy conversion of it
+    pub fn open<P: AsRef<Path>>(file_path: P) -> Result<Self, NbtFileError> {
+        let file_path = file_path.as_ref();
+        let (buffer, compression) = Self::read_file(file_path, None)?;
+        let nbtdata = nbt::NbtData::from_buf(buffer)?;
+        Ok(NbtFile {
+            file_path: file_path.to_string_lossy().into_owned(),
+            compression,
+            nbtdata,
+        })
+    }
+
+    pub fn try_read(file_path: String) -> Result<Self, NbtFileError> {
+        let (buffer, compression) = Self::read_file(&file_path, None)?;
+        let nbtdata = nbt::NbtData::from_buf(buffer)?;
+        Ok(NbtFile {
+            file_path,
+            compression,
+            nbtdata,
+        })
+    }
+
+    pub fn try_read_with_compression(
+        file_path: String,
+        compression: Compression,
+    ) -> Result<Self, NbtFileError> {
+        let (buffer, compression) = Self::read_file(&file_path, Some(compression))?;
+        let nbtdata = nbt::NbtData::from_buf(buffer)?;
+        Ok(NbtFile {
+            file_path,
+            compression,
+            nbtdata,
+        })
+    }
+
+    /// Read a NBT file, auto-detecting its compression, with custom parse options
+    /// (e.g. [`nbt::ParseOptions::bedrock`] for little endian files)
+    pub fn try_read_with_options(
+        file_path: String,
+        options: &nbt::ParseOptions,
+    ) -> Result<Self, NbtFileError> {
+        let (buffer, compression) = Self::read_file(&file_path, None)?;
+        let nbtdata = nbt::NbtData::from_buf_with_options(buffer, options)?;
+        Ok(NbtFile {
+            file_path,
+            compression,
+            nbtdata,
+        })
+    }
+
+    /// Read NBT from any reader until its end, e.g. stdin or a network capture, auto-detecting its compression.
+    /// The file path of the returned NbtFile is empty
+    pub fn from_reader<R: Read>(
+        mut reader: R,
+        options: &nbt::ParseOptions,
+    ) -> Result<Self, NbtFileError> {
+        let mut file_buffer = Vec::new();
+        reader.read_to_end(&mut file_buffer)?;
+
+        let (buffer, compression) = Self::decompress(file_buffer, None)?;
+        let nbtdata = nbt::NbtData::from_buf_with_options(buffer, options)?;
+        Ok(NbtFile {
+            file_path: String::new(),
+            compression,
+            nbtdata,
+        })
+    }
+
+    pub(crate) fn from_nbtdata(
+        file_path: String,
+        compression: Compression,
+        nbtdata: nbt::NbtData,
+    ) -> Self {
+        NbtFile {
+            file_path,
+            compression,
+            nbtdata,
+        }
     }
 
     pub fn file_path(&self) -> &str {
         &self.file_path
     }
 
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    pub fn nbt_data(&self) -> &nbt::NbtData {
+        &self.nbtdata
+    }
+
+    /// The parsed NBT, to edit the NbtTag tree before writing it back with [`NbtFile::write`]
+    pub fn nbt_data_as_mut(&mut self) -> &mut nbt::NbtData {
+        &mut self.nbtdata
+    }
+
     pub fn nbt_tags(&self) -> &Vec<nbt::NbtTag> {
-        &self.nbtdata.nbt_tags()
+        self.nbtdata.nbt_tags()
     }
 
     pub fn as_raw_bytes(&self) -> &Vec<u8> {
-        &self.nbtdata.raw_bytes()
+        self.nbtdata.raw_bytes()
     }
 
     pub fn nbt_hashmap(&self) -> &HashMap<String, usize> {
-        &self.nbtdata.tags_map()
+        self.nbtdata.tags_map()
+    }
+
+    pub fn bedrock_header(&self) -> Option<&nbt::BedrockHeader> {
+        self.nbtdata.bedrock_header()
+    }
+
+    /// Bytes left after the root tag, not part of the NBT
+    pub fn trailer(&self) -> Option<&nbt::NbtTrailer> {
+        self.nbtdata.trailer()
     }
 
-    fn read_file(file_path: &str) -> std::io::Result<Vec<u8>> {
-        // Open the file and create a buffered reader for efficient reading
-        let file = fs::File::open(file_path)?;
+    /// Errors skipped while reading with [`nbt::ParseOptions::recover_errors`]
+    pub fn diagnostics(&self) -> &Vec<nbt::NbtParseDiagnostic> {
+        self.nbtdata.diagnostics()
+    }
+
+    fn read_file<P: AsRef<Path>>(
+        file_path: P,
+        compression: Option<Compression>,
+    ) -> std::io::Result<(Vec<u8>, Compression)> {
+        let file_buffer = fs::read(file_path)?;
+        Self::decompress(file_buffer, compression)
+    }
 
-        let buf_reader = BufReader::new(file);
-        let mut decoder = GzDecoder::new(buf_reader);
-        let mut decompressed_data = Vec::new();
+    fn decompress(
+        file_buffer: Vec<u8>,
+        compression: Option<Compression>,
+    ) -> std::io::Result<(Vec<u8>, Compression)> {
+        // if no compression is forced, sniff it from the magic bytes
+        // unknown headers are passed through as they are and will be rejected by the NBT parser
+        let compression = compression
+            .or_else(|| Compression::detect(&file_buffer))
+            .unwrap_or(Compression::Uncompressed);
 
-        decoder.read_to_end(&mut decompressed_data)?;
-        Ok(decompressed_data)
+        // uncompressed buffers are used as they are, without a copy
+        if compression == Compression::Uncompressed {
+            return Ok((file_buffer, compression));
+        }
+
+        let decompressed_data = compression.decompress(&file_buffer)?;
+        Ok((decompressed_data, compression))
+    }
+
+    /// Serialize the NbtTag tree to binary NBT and write it to `output_path` with the given compression
+    pub fn write(&self, output_path: &str, compression: Compression) -> Result<(), NbtFileError> {
+        let buffer = self.nbtdata.to_bytes()?;
+        fs::write(output_path, compression.compress(&buffer)?)?;
+        Ok(())
     }
 
     pub fn to_json(&self, output_path: &str) -> Result<(), NbtFileError> {
         let file = fs::File::create(output_path)?;
+
+        // the JSON needs every value, including the ones skipped by a lazy parse
+        if self
+            .nbtdata
+            .nbt_tags()
+            .iter()
+            .any(|nbt_tag| nbt_tag.has_lazy_value())
+        {
+            let mut nbtdata = self.nbtdata.clone();
+            nbtdata.decode_values()?;
+            serde_json::to_writer_pretty(file, nbtdata.nbt_tags())
+                .map_err(|_| NbtFileError::JsonWriteFailure)?;
+            return Ok(());
+        }
+
         serde_json::to_writer_pretty(file, self.nbtdata.nbt_tags())
             .map_err(|_| NbtFileError::JsonWriteFailure)?;
         Ok(())
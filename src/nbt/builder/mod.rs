@@ -0,0 +1,463 @@
+use crate::nbt::{NbtCompound, NbtData, NbtEditError, NbtList, NbtValue, NbtWriteError};
+
+/// Fluent builder of a [`NbtCompound`].
+///
+/// The first invalid child, a duplicated name or a list element of the wrong type,
+/// is reported by [`NbtCompoundBuilder::build`]
+#[derive(Debug, Default)]
+pub struct NbtCompoundBuilder {
+    compound: NbtCompound,
+    error: Option<NbtEditError>,
+}
+
+impl NbtCompoundBuilder {
+    pub fn new() -> NbtCompoundBuilder {
+        NbtCompoundBuilder::default()
+    }
+
+    pub fn byte(self, name: &str, value: i8) -> NbtCompoundBuilder {
+        self.value(name, value)
+    }
+
+    pub fn short(self, name: &str, value: i16) -> NbtCompoundBuilder {
+        self.value(name, value)
+    }
+
+    pub fn int(self, name: &str, value: i32) -> NbtCompoundBuilder {
+        self.value(name, value)
+    }
+
+    pub fn long(self, name: &str, value: i64) -> NbtCompoundBuilder {
+        self.value(name, value)
+    }
+
+    pub fn float(self, name: &str, value: f32) -> NbtCompoundBuilder {
+        self.value(name, value)
+    }
+
+    pub fn double(self, name: &str, value: f64) -> NbtCompoundBuilder {
+        self.value(name, value)
+    }
+
+    pub fn string(self, name: &str, value: &str) -> NbtCompoundBuilder {
+        self.value(name, value)
+    }
+
+    pub fn byte_array(self, name: &str, values: Vec<i8>) -> NbtCompoundBuilder {
+        self.value(name, values)
+    }
+
+    pub fn int_array(self, name: &str, values: Vec<i32>) -> NbtCompoundBuilder {
+        self.value(name, values)
+    }
+
+    pub fn long_array(self, name: &str, values: Vec<i64>) -> NbtCompoundBuilder {
+        self.value(name, values)
+    }
+
+    /// Add a child compound, filled by `build`
+    pub fn compound<F>(self, name: &str, build: F) -> NbtCompoundBuilder
+    where
+        F: FnOnce(NbtCompoundBuilder) -> NbtCompoundBuilder,
+    {
+        match build(NbtCompoundBuilder::new()).build() {
+            Ok(compound) => self.value(name, compound),
+            Err(error) => self.fail(error),
+        }
+    }
+
+    /// Add a child list, filled by `build`
+    pub fn list<F>(self, name: &str, build: F) -> NbtCompoundBuilder
+    where
+        F: FnOnce(NbtListBuilder) -> NbtListBuilder,
+    {
+        match build(NbtListBuilder::new()).build() {
+            Ok(list) => self.value(name, list),
+            Err(error) => self.fail(error),
+        }
+    }
+
+    /// Add a child of any type
+    pub fn value<V: Into<NbtValue>>(mut self, name: &str, value: V) -> NbtCompoundBuilder {
+        if self.error.is_some() {
+            return self;
+        }
+        if self.compound.contains_key(name) {
+            return self.fail(NbtEditError::DuplicateName(name.to_string()));
+        }
+
+        self.compound.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn build(self) -> Result<NbtCompound, NbtEditError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.compound),
+        }
+    }
+
+    /// Build a NbtData with the compound as root tag named `root_name`
+    pub fn build_nbt_data(self, root_name: &str) -> Result<NbtData, NbtWriteError> {
+        let compound = self
+            .build()
+            .map_err(|e| NbtWriteError::InvalidNbtTree(e.to_string()))?;
+
+        let mut roots = NbtCompound::new();
+        roots.insert(root_name.to_string(), NbtValue::Compound(compound));
+        NbtData::from_value(&roots)
+    }
+
+    // only the first error is kept, the next children are ignored
+    fn fail(mut self, error: NbtEditError) -> NbtCompoundBuilder {
+        self.error.get_or_insert(error);
+        self
+    }
+}
+
+/// Fluent builder of a [`NbtList`]: the first element sets the type of the list,
+/// [`NbtListBuilder::build`] reports an element of another type
+#[derive(Debug, Default)]
+pub struct NbtListBuilder {
+    list: NbtList,
+    error: Option<NbtEditError>,
+}
+
+impl NbtListBuilder {
+    pub fn new() -> NbtListBuilder {
+        NbtListBuilder::default()
+    }
+
+    /// Append an element of any type
+    pub fn element<V: Into<NbtValue>>(mut self, value: V) -> NbtListBuilder {
+        if self.error.is_some() {
+            return self;
+        }
+        if let Err(error) = self.list.push(value.into()) {
+            self.error = Some(error);
+        }
+        self
+    }
+
+    /// Append a compound, filled by `build`
+    pub fn compound<F>(mut self, build: F) -> NbtListBuilder
+    where
+        F: FnOnce(NbtCompoundBuilder) -> NbtCompoundBuilder,
+    {
+        match build(NbtCompoundBuilder::new()).build() {
+            Ok(compound) => self.element(compound),
+            Err(error) => {
+                self.error.get_or_insert(error);
+                self
+            }
+        }
+    }
+
+    /// Append a list, filled by `build`
+    pub fn list<F>(mut self, build: F) -> NbtListBuilder
+    where
+        F: FnOnce(NbtListBuilder) -> NbtListBuilder,
+    {
+        match build(NbtListBuilder::new()).build() {
+            Ok(list) => self.element(list),
+            Err(error) => {
+                self.error.get_or_insert(error);
+                self
+            }
+        }
+    }
+
+    pub fn build(self) -> Result<NbtList, NbtEditError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.list),
+        }
+    }
+}
+
+/// Build a [`NbtCompound`](crate::nbt::NbtCompound) with a syntax close to SNBT.
+///
+/// Values are converted with `NbtValue::from`, `{ ... }` is a compound, `[ ... ]` a list and
+/// `[B; ...]`, `[I; ...]`, `[L; ...]` are byte, int and long arrays. The elements of a list are
+/// collected in a `Vec`, so elements of different types do not compile.
+/// The top level compound holds the roots, ready for [`NbtData::from_value`](crate::nbt::NbtData::from_value).
+/// Like [`NbtCompoundBuilder`], a name used twice in a compound is an error:
+///
+/// ```
+/// use nbtsniper::nbt;
+///
+/// let roots = nbtsniper::nbt! {
+///     "Level": {
+///         "x": 1i32,
+///         "name": "Steve",
+///         "items": [{ "id": "minecraft:stone", "Count": 64i8 }],
+///         "heights": [L; 1, 2, 3],
+///     }
+/// }
+/// .unwrap();
+/// let nbt_data = nbt::NbtData::from_value(&roots).unwrap();
+/// assert_eq!(nbt_data.nbt_tags()[0].name(), "Level");
+///
+/// assert!(nbtsniper::nbt! { "x": 1i32, "x": 2i32 }.is_err());
+/// ```
+///
+/// Entries and elements whose value is a single token tree (a literal, a variable, `{ ... }`, `[ ... ]`
+/// or an expression in parentheses) are all expanded at once. Values made of several tokens, e.g. `-1i8`,
+/// are split from the next entries one token at a time, so a compound made of many of them can reach the
+/// recursion limit: write them in parentheses, e.g. `(-1i8)`, to keep the expansion flat.
+///
+/// ```compile_fail
+/// let roots = nbtsniper::nbt! { "mixed": [1i32, "a"] };
+/// ```
+#[macro_export]
+macro_rules! nbt {
+    (@compound $($tt:tt)*) => {{
+        #[allow(clippy::redundant_closure_call)]
+        let compound = (|| -> ::std::result::Result<$crate::nbt::NbtCompound, $crate::nbt::NbtEditError> {
+            #[allow(unused_mut)]
+            let mut compound = $crate::nbt::NbtCompoundBuilder::new();
+            $crate::nbt!(@entries compound $($tt)*);
+            compound.build()
+        })();
+        compound
+    }};
+
+    // "name": value, ... entries with single token values are all expanded by the first rule,
+    // the others are split one by one: the tokens of the value are collected until the next comma
+    (@entries $compound:ident $($name:tt : $value:tt),* $(,)?) => {
+        $($crate::nbt!(@insert $compound $name ($value));)*
+    };
+    (@entries $compound:ident $name:tt : $value:tt , $($rest:tt)*) => {
+        $crate::nbt!(@insert $compound $name ($value));
+        $crate::nbt!(@entries $compound $($rest)*);
+    };
+    (@entries $compound:ident $name:tt : $($rest:tt)*) => {
+        $crate::nbt!(@entry $compound $name () $($rest)*);
+    };
+    (@entry $compound:ident $name:tt ($($value:tt)+) , $($rest:tt)*) => {
+        $crate::nbt!(@insert $compound $name ($($value)+));
+        $crate::nbt!(@entries $compound $($rest)*);
+    };
+    (@entry $compound:ident $name:tt ($($value:tt)+)) => {
+        $crate::nbt!(@insert $compound $name ($($value)+));
+    };
+    (@entry $compound:ident $name:tt ($($value:tt)*) $next:tt $($rest:tt)*) => {
+        $crate::nbt!(@entry $compound $name ($($value)* $next) $($rest)*);
+    };
+    (@insert $compound:ident $name:tt ($($value:tt)+)) => {
+        $compound = $compound.value(&($name).to_string(), $crate::nbt!(@value $($value)+));
+    };
+
+    (@value { $($tt:tt)* }) => {
+        $crate::nbt::NbtValue::Compound($crate::nbt!(@compound $($tt)*)?)
+    };
+    (@value [ $($tt:tt)* ]) => {
+        $crate::nbt::NbtValue::from($crate::nbt!(@element [ $($tt)* ]))
+    };
+    (@value $value:expr) => {
+        $crate::nbt::NbtValue::from($value)
+    };
+
+    // list elements keep their Rust type, so that the Vec of elements checks they are all the same
+    (@element { $($tt:tt)* }) => {
+        $crate::nbt!(@compound $($tt)*)?
+    };
+    (@element [B; $($value:expr),* $(,)?]) => {
+        ::std::vec::Vec::<i8>::from([$($value),*])
+    };
+    (@element [I; $($value:expr),* $(,)?]) => {
+        ::std::vec::Vec::<i32>::from([$($value),*])
+    };
+    (@element [L; $($value:expr),* $(,)?]) => {
+        ::std::vec::Vec::<i64>::from([$($value),*])
+    };
+    (@element []) => {
+        $crate::nbt::NbtList::Empty
+    };
+    (@element [ $($element:tt),+ $(,)? ]) => {
+        $crate::nbt::NbtList::from(::std::vec![$($crate::nbt!(@element $element)),+])
+    };
+    (@element [ $($tt:tt)+ ]) => {
+        $crate::nbt::NbtList::from($crate::nbt!(@elements [] () $($tt)+))
+    };
+    (@element $value:expr) => {
+        $value
+    };
+
+    (@elements [$($done:expr,)*] ()) => {
+        ::std::vec![$($done,)*]
+    };
+    (@elements [$($done:expr,)*] ($($element:tt)+) , $($rest:tt)*) => {
+        $crate::nbt!(@elements [$($done,)* $crate::nbt!(@element $($element)+),] () $($rest)*)
+    };
+    (@elements [$($done:expr,)*] ($($element:tt)+)) => {
+        ::std::vec![$($done,)* $crate::nbt!(@element $($element)+)]
+    };
+    (@elements [$($done:expr,)*] ($($element:tt)*) $next:tt $($rest:tt)*) => {
+        $crate::nbt!(@elements [$($done,)*] ($($element)* $next) $($rest)*)
+    };
+
+    // entry point: the content of a compound
+    ($($tt:tt)*) => {
+        $crate::nbt!(@compound $($tt)*)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::{NbtTagId, NbtTagType};
+
+    #[test]
+    fn test_nbt_macro() {
+        let count = 64i8;
+        let roots = crate::nbt! {
+            "Level": {
+                "x": 1i32,
+                "y": -2i16,
+                "name": "Steve",
+                "scale": 0.5f32 * 3.0,
+                "items": [{ "id": "minecraft:stone", "Count": count }, { "Count": 1i8 }],
+                "scores": [1, 2, 3],
+                "nested": [[1i8], [], ["a", "b"]],
+                "empty": [],
+                "bytes": [B; 1, -1],
+                "ints": [I; 1, 2],
+                "longs": [L; 3],
+                "arrays": [[I; 1], [I; 2, 3]],
+            }
+        }
+        .unwrap();
+
+        let level = NbtCompoundBuilder::new()
+            .int("x", 1)
+            .short("y", -2)
+            .string("name", "Steve")
+            .float("scale", 1.5)
+            .list("items", |items| {
+                items
+                    .compound(|item| item.string("id", "minecraft:stone").byte("Count", 64))
+                    .compound(|item| item.byte("Count", 1))
+            })
+            .list("scores", |scores| scores.element(1).element(2).element(3))
+            .list("nested", |nested| {
+                nested
+                    .list(|list| list.element(1i8))
+                    .list(|list| list)
+                    .list(|list| list.element("a").element("b"))
+            })
+            .list("empty", |list| list)
+            .byte_array("bytes", vec![1, -1])
+            .int_array("ints", vec![1, 2])
+            .long_array("longs", vec![3])
+            .list("arrays", |arrays| {
+                arrays.element(vec![1i32]).element(vec![2i32, 3])
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots.get("Level"), Some(&NbtValue::Compound(level)));
+    }
+
+    #[test]
+    fn test_nbt_macro_to_nbt_data() {
+        let roots = crate::nbt! {
+            "Root": { "Pos": { "X": 7i64 }, "Scores": [1, 2] }
+        }
+        .unwrap();
+        let nbt_data = NbtData::from_value(&roots).unwrap();
+
+        let tags = nbt_data.nbt_tags();
+        assert_eq!(tags[0].name(), "Root");
+        assert_eq!(tags[2].value(), &NbtTagType::Long(7));
+        assert_eq!(tags[4].value(), &NbtTagType::List((NbtTagId::Int, 2)));
+        assert_eq!(crate::nbt! {}.unwrap(), NbtCompound::new());
+    }
+
+    #[test]
+    fn test_nbt_macro_many_entries() {
+        // each entry is expanded at the same depth, whatever the number of entries
+        let roots = crate::nbt! { "": {
+            "e0": 0, "e1": 1, "e2": 2, "e3": 3, "e4": 4, "e5": 5, "e6": 6, "e7": 7, "e8": 8, "e9": 9,
+            "e10": 10, "e11": 11, "e12": 12, "e13": 13, "e14": 14, "e15": 15, "e16": 16, "e17": 17,
+            "e18": 18, "e19": 19, "e20": 20, "e21": 21, "e22": 22, "e23": 23, "e24": 24, "e25": 25,
+            "e26": 26, "e27": 27, "e28": 28, "e29": 29, "e30": 30, "e31": 31, "e32": 32, "e33": 33,
+            "e34": 34, "e35": 35, "e36": 36, "e37": 37, "e38": 38, "e39": 39, "e40": 40, "e41": 41,
+            "e42": 42, "e43": 43, "e44": 44, "e45": 45, "e46": 46, "e47": 47, "e48": 48, "e49": 49,
+            "e50": 50, "e51": 51, "e52": 52, "e53": 53, "e54": 54, "e55": 55, "e56": 56, "e57": 57,
+            "e58": 58, "e59": 59, "e60": 60, "e61": 61, "e62": 62, "e63": 63, "e64": 64, "e65": 65,
+            "e66": 66, "e67": 67, "e68": 68, "e69": 69, "e70": 70, "e71": 71, "e72": 72, "e73": 73,
+            "e74": 74, "e75": 75, "e76": 76, "e77": 77, "e78": 78, "e79": 79, "e80": 80, "e81": 81,
+            "e82": 82, "e83": 83, "e84": 84, "e85": 85, "e86": 86, "e87": 87, "e88": 88, "e89": 89,
+            "e90": 90, "e91": 91, "e92": 92, "e93": 93, "e94": 94, "e95": 95, "e96": 96, "e97": 97,
+            "e98": 98, "e99": 99, "e100": 100, "e101": 101, "e102": 102, "e103": 103, "e104": 104,
+            "e105": (-105), "e106": { "inner": [1, 2] }, "e107": [{ "a": 1 }, { "a": 2 }],
+        } }
+        .unwrap();
+        let root = roots.get("").unwrap().as_compound().unwrap();
+        assert_eq!(root.len(), 108);
+        assert_eq!(root.get("e99"), Some(&NbtValue::Int(99)));
+        assert_eq!(root.get("e105"), Some(&NbtValue::Int(-105)));
+
+        // values of several tokens before the others
+        let roots = crate::nbt! { "x": -1i8, "y": 2i8 * 3, "z": 7i8 }.unwrap();
+        assert_eq!(roots.get("y"), Some(&NbtValue::Byte(6)));
+        assert_eq!(roots.get("z"), Some(&NbtValue::Byte(7)));
+    }
+
+    #[test]
+    fn test_nbt_macro_duplicate_names() {
+        assert!(matches!(
+            crate::nbt! { "x": 1i32, "x": 2i32 },
+            Err(NbtEditError::DuplicateName(_))
+        ));
+        assert!(matches!(
+            crate::nbt! { "Root": { "list": [{ "a": -1, "a": 2 }] } },
+            Err(NbtEditError::DuplicateName(_))
+        ));
+        assert!(crate::nbt! { "a": { "x": 1 }, "b": { "x": 1 } }.is_ok());
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        assert!(matches!(
+            NbtCompoundBuilder::new().int("x", 1).byte("x", 2).build(),
+            Err(NbtEditError::DuplicateName(_))
+        ));
+        assert!(matches!(
+            NbtCompoundBuilder::new()
+                .list("mixed", |list| list.element(1i32).element("a"))
+                .int("after", 1)
+                .build(),
+            Err(NbtEditError::ListTypeMismatch {
+                expected: NbtTagId::Int,
+                found: NbtTagId::String
+            })
+        ));
+        // errors of nested builders are reported by the outer one
+        assert!(matches!(
+            NbtCompoundBuilder::new()
+                .compound("outer", |outer| outer.list("list", |list| list
+                    .compound(|inner| inner.int("a", 1).int("a", 2))))
+                .build_nbt_data("Root"),
+            Err(NbtWriteError::InvalidNbtTree(_))
+        ));
+    }
+
+    #[test]
+    fn test_builder_nbt_data() {
+        let nbt_data = NbtCompoundBuilder::new()
+            .string("Name", "Steve")
+            .list("Scores", |scores| scores.element(1).element(2))
+            .build_nbt_data("Root")
+            .unwrap();
+
+        assert_eq!(nbt_data.roots(), vec![0]);
+        assert_eq!(nbt_data.nbt_tags()[0].name(), "Root");
+        assert_eq!(
+            nbt_data.to_value().unwrap(),
+            crate::nbt! { "Root": { "Name": "Steve", "Scores": [1, 2] } }.unwrap()
+        );
+    }
+}
@@ -0,0 +1,654 @@
+use std::io::Cursor;
+
+use serde::de::value::{SeqDeserializer, StringDeserializer};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IgnoredAny, IntoDeserializer, MapAccess,
+    SeqAccess, VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use crate::nbt::{
+    fsm, BedrockHeader, NbtEncoding, NbtReadError, NbtTagId, NbtTagType, ParseOptions, RootMode,
+    BEDROCK_HEADER_SIZE,
+};
+
+/// Deserialize a `T` from binary Java NBT. The name of the root tag is ignored, and so are the bytes after it
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, NbtReadError> {
+    from_bytes_with_options(bytes, &ParseOptions::default())
+}
+
+/// Deserialize a `T` from NBT read with the encoding, root mode and limits of `options`.
+///
+/// Bedrock level.dat headers are skipped as in [`crate::nbt::NbtData::from_buf_with_options`]
+pub fn from_bytes_with_options<T: DeserializeOwned>(
+    bytes: &[u8],
+    options: &ParseOptions,
+) -> Result<T, NbtReadError> {
+    if bytes.is_empty() {
+        return Err(NbtReadError::EmptyFile);
+    }
+
+    let mut cursor = Cursor::new(bytes);
+    if matches!(options.encoding(), NbtEncoding::LittleEndian)
+        && BedrockHeader::detect(bytes).is_some()
+    {
+        cursor.set_position(BEDROCK_HEADER_SIZE as u64);
+    }
+
+    let mut reader = NbtReader {
+        cursor,
+        options,
+        depth: 0,
+    };
+    let tag_id = reader.read_tag_id()?;
+    if tag_id != NbtTagId::Compound && !options.any_root_tag() {
+        return Err(NbtReadError::InvalidNbtRootTagId);
+    }
+    if tag_id == NbtTagId::End {
+        return Err(NbtReadError::InvalidContent);
+    }
+    if options.root_mode() != RootMode::Nameless {
+        fsm::parse::nbt_tag_string(&mut reader.cursor, options)?;
+    }
+
+    T::deserialize(TagDeserializer {
+        reader: &mut reader,
+        tag_id,
+    })
+}
+
+impl de::Error for NbtReadError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        NbtReadError::Custom(msg.to_string())
+    }
+}
+
+// the raw bytes and how deep in the tree the deserializer is
+struct NbtReader<'a> {
+    cursor: Cursor<&'a [u8]>,
+    options: &'a ParseOptions,
+    depth: i64,
+}
+
+impl NbtReader<'_> {
+    fn read_tag_id(&mut self) -> Result<NbtTagId, NbtReadError> {
+        fsm::parse::nbt_tag_id(&mut self.cursor)?.ok_or(NbtReadError::InvalidContent)
+    }
+
+    fn read_name(&mut self) -> Result<StringDeserializer<NbtReadError>, NbtReadError> {
+        let name = fsm::parse::nbt_tag_string(&mut self.cursor, self.options)?;
+        Ok(name.into_deserializer())
+    }
+
+    fn enter_container(&mut self) -> Result<(), NbtReadError> {
+        self.depth += 1;
+        if self.depth > self.options.max_depth() {
+            return Err(NbtReadError::MaxDepthExceeded(self.options.max_depth()));
+        }
+        Ok(())
+    }
+
+    fn leave_container(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+// Deserializer of the payload of one tag, whose id and name were already read
+struct TagDeserializer<'r, 'a> {
+    reader: &'r mut NbtReader<'a>,
+    tag_id: NbtTagId,
+}
+
+impl TagDeserializer<'_, '_> {
+    fn read_payload(&mut self) -> Result<NbtTagType, NbtReadError> {
+        fsm::parse::nbt_tag(&mut self.reader.cursor, &self.tag_id, self.reader.options)
+    }
+}
+
+fn visit_array<'de, T, V>(values: Vec<T>, visitor: V) -> Result<V::Value, NbtReadError>
+where
+    T: IntoDeserializer<'de, NbtReadError>,
+    V: Visitor<'de>,
+{
+    let mut elements = SeqDeserializer::new(values.into_iter());
+    let value = visitor.visit_seq(&mut elements)?;
+    elements.end()?;
+    Ok(value)
+}
+
+impl<'de> de::Deserializer<'de> for TagDeserializer<'_, '_> {
+    type Error = NbtReadError;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.read_payload()? {
+            NbtTagType::End(_) => visitor.visit_unit(),
+            NbtTagType::Byte(value) => visitor.visit_i8(value),
+            NbtTagType::Short(value) => visitor.visit_i16(value),
+            NbtTagType::Int(value) => visitor.visit_i32(value),
+            NbtTagType::Long(value) => visitor.visit_i64(value),
+            NbtTagType::Float(value) => visitor.visit_f32(value),
+            NbtTagType::Double(value) => visitor.visit_f64(value),
+            NbtTagType::ByteArray(values) => visit_array(values, visitor),
+            NbtTagType::String(value) => visitor.visit_string(value),
+            NbtTagType::IntArray(values) => visit_array(values, visitor),
+            NbtTagType::LongArray(values) => visit_array(values, visitor),
+            NbtTagType::List((element_id, len)) => {
+                self.reader.enter_container()?;
+                let mut list = ListAccess {
+                    reader: self.reader,
+                    element_id,
+                    remaining: len.max(0) as usize,
+                };
+                let value = visitor.visit_seq(&mut list)?;
+                if list.remaining > 0 {
+                    return Err(de::Error::invalid_length(
+                        len as usize,
+                        &"fewer elements in the NBT List",
+                    ));
+                }
+                list.reader.leave_container();
+                Ok(value)
+            }
+            NbtTagType::Compound(_) => {
+                self.reader.enter_container()?;
+                let mut compound = CompoundAccess {
+                    reader: self.reader,
+                    tag_id: None,
+                    finished: false,
+                };
+                let value = visitor.visit_map(&mut compound)?;
+                compound.skip_remaining()?;
+                compound.reader.leave_container();
+                Ok(value)
+            }
+            // payloads are always decoded here, lazy_values only applies to NbtData
+            NbtTagType::Lazy(_) => Err(NbtReadError::InvalidContent),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.tag_id != NbtTagId::Byte {
+            return self.deserialize_any(visitor);
+        }
+        match self.read_payload()? {
+            NbtTagType::Byte(value) => visitor.visit_bool(value != 0),
+            _ => Err(NbtReadError::InvalidContent),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(
+        mut self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if self.tag_id != NbtTagId::ByteArray {
+            return self.deserialize_any(visitor);
+        }
+        match self.read_payload()? {
+            NbtTagType::ByteArray(values) => {
+                visitor.visit_byte_buf(values.into_iter().map(|value| value as u8).collect())
+            }
+            _ => Err(NbtReadError::InvalidContent),
+        }
+    }
+
+    // units are written as empty compounds
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.tag_id != NbtTagId::Compound {
+            return self.deserialize_any(visitor);
+        }
+        self.deserialize_any(IgnoredAny)?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    // a missing tag is None, so every tag which is present is Some
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // unit variants are Strings, the others a Compound with a single child named as the variant
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.tag_id {
+            NbtTagId::String => match self.read_payload()? {
+                NbtTagType::String(variant) => {
+                    let variant: StringDeserializer<NbtReadError> = variant.into_deserializer();
+                    visitor.visit_enum(variant)
+                }
+                _ => Err(NbtReadError::InvalidContent),
+            },
+            NbtTagId::Compound => {
+                self.reader.enter_container()?;
+                visitor.visit_enum(VariantCompoundAccess {
+                    reader: self.reader,
+                    tag_id: NbtTagId::End,
+                })
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if !fsm::parse::has_skippable_payload(&self.tag_id) {
+            return self.deserialize_any(visitor);
+        }
+        fsm::parse::skip_nbt_tag_payload(
+            &mut self.reader.cursor,
+            &self.tag_id,
+            self.reader.options,
+        )?;
+        visitor.visit_unit()
+    }
+
+    // unsigned integers are read from the signed type of the same width, negative values are rejected by their visitor
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string seq tuple
+        tuple_struct map struct identifier
+    }
+}
+
+struct ListAccess<'r, 'a> {
+    reader: &'r mut NbtReader<'a>,
+    element_id: NbtTagId,
+    remaining: usize,
+}
+
+impl<'de> SeqAccess<'de> for ListAccess<'_, '_> {
+    type Error = NbtReadError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+
+        // the elements have neither id nor name, only their payload
+        seed.deserialize(TagDeserializer {
+            reader: &mut *self.reader,
+            tag_id: self.element_id,
+        })
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct CompoundAccess<'r, 'a> {
+    reader: &'r mut NbtReader<'a>,
+    tag_id: Option<NbtTagId>,
+    finished: bool,
+}
+
+impl CompoundAccess<'_, '_> {
+    // the children not read by the visitor, up to and including the End tag
+    fn skip_remaining(&mut self) -> Result<(), NbtReadError> {
+        if self.tag_id.is_some() {
+            self.next_value::<IgnoredAny>()?;
+        }
+        while self.next_key::<IgnoredAny>()?.is_some() {
+            self.next_value::<IgnoredAny>()?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de> MapAccess<'de> for CompoundAccess<'_, '_> {
+    type Error = NbtReadError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let tag_id = self.reader.read_tag_id()?;
+        if tag_id == NbtTagId::End {
+            self.finished = true;
+            return Ok(None);
+        }
+        self.tag_id = Some(tag_id);
+        seed.deserialize(self.reader.read_name()?).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let tag_id = self.tag_id.take().ok_or(NbtReadError::InvalidContent)?;
+        seed.deserialize(TagDeserializer {
+            reader: &mut *self.reader,
+            tag_id,
+        })
+    }
+}
+
+// the Compound of an enum variant with data, its single child is the variant
+struct VariantCompoundAccess<'r, 'a> {
+    reader: &'r mut NbtReader<'a>,
+    tag_id: NbtTagId,
+}
+
+impl<'a> VariantCompoundAccess<'_, 'a> {
+    fn tag_deserializer(&mut self) -> TagDeserializer<'_, 'a> {
+        TagDeserializer {
+            reader: &mut *self.reader,
+            tag_id: self.tag_id,
+        }
+    }
+
+    fn finish(self) -> Result<(), NbtReadError> {
+        if self.reader.read_tag_id()? != NbtTagId::End {
+            return Err(de::Error::custom(
+                "an enum variant Compound must have a single child",
+            ));
+        }
+        self.reader.leave_container();
+        Ok(())
+    }
+}
+
+impl<'de> EnumAccess<'de> for VariantCompoundAccess<'_, '_> {
+    type Error = NbtReadError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        mut self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        self.tag_id = self.reader.read_tag_id()?;
+        if self.tag_id == NbtTagId::End {
+            return Err(de::Error::custom(
+                "an enum variant Compound must have a single child",
+            ));
+        }
+        let variant = seed.deserialize(self.reader.read_name()?)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for VariantCompoundAccess<'_, '_> {
+    type Error = NbtReadError;
+
+    fn unit_variant(mut self) -> Result<(), Self::Error> {
+        de::Deserialize::deserialize(self.tag_deserializer()).map(|IgnoredAny| ())?;
+        self.finish()
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        mut self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        let value = seed.deserialize(self.tag_deserializer())?;
+        self.finish()?;
+        Ok(value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        mut self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = de::Deserializer::deserialize_seq(self.tag_deserializer(), visitor)?;
+        self.finish()?;
+        Ok(value)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        mut self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = de::Deserializer::deserialize_map(self.tag_deserializer(), visitor)?;
+        self.finish()?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::{to_bytes, to_bytes_with_options, NbtData, NbtIntArray, NbtLongArray};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Point,
+        Circle(f32),
+        Segment(i32, i32),
+        Rect { w: i32, h: i32 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Everything {
+        flag: bool,
+        letter: char,
+        unsigned_byte: u8,
+        unsigned_int: u32,
+        unsigned_long: u64,
+        missing: Option<i16>,
+        present: Option<String>,
+        matrix: Vec<Vec<i32>>,
+        scores: BTreeMap<String, i32>,
+        shapes: Vec<Shape>,
+        unit_shape: Shape,
+        #[serde(with = "crate::nbt::byte_array")]
+        bytes: Vec<i8>,
+        ints: NbtIntArray,
+        longs: NbtLongArray,
+    }
+
+    fn everything() -> Everything {
+        Everything {
+            flag: true,
+            letter: 'é',
+            unsigned_byte: i8::MAX as u8,
+            unsigned_int: i32::MAX as u32,
+            unsigned_long: i64::MAX as u64,
+            missing: None,
+            present: Some("here".to_string()),
+            matrix: vec![vec![1, 2], Vec::new()],
+            scores: [("a".to_string(), 1), ("b".to_string(), 2)].into(),
+            shapes: vec![
+                Shape::Circle(1.5),
+                Shape::Segment(3, 4),
+                Shape::Rect { w: 2, h: 3 },
+            ],
+            unit_shape: Shape::Point,
+            bytes: vec![-1, 0, 1],
+            ints: NbtIntArray(vec![i32::MIN, i32::MAX]),
+            longs: NbtLongArray(Vec::new()),
+        }
+    }
+
+    #[test]
+    fn test_from_bytes_round_trip() {
+        let bytes = to_bytes(&everything()).unwrap();
+        assert_eq!(from_bytes::<Everything>(&bytes).unwrap(), everything());
+
+        for options in [
+            ParseOptions::bedrock(),
+            ParseOptions::bedrock_network(),
+            ParseOptions::java_network(),
+        ] {
+            let bytes = to_bytes_with_options(&everything(), &options).unwrap();
+            assert_eq!(
+                from_bytes_with_options::<Everything>(&bytes, &options).unwrap(),
+                everything()
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_bytes_skips_unknown_children() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Pos {
+            x: i64,
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Player {
+            #[serde(rename = "Name")]
+            name: String,
+            #[serde(rename = "Pos")]
+            pos: Pos,
+        }
+
+        let roots = crate::nbt! {
+            "Root": {
+                "Scores": [[1i32, 2i32], [3i32]],
+                "Name": "Steve",
+                "Inventory": [{ "id": "minecraft:stone" }],
+                "Pos": { "y": [L; 1, 2], "x": 7i64, "z": { "deep": 1i8 } },
+                "Motion": [0.5f64],
+            }
+        }
+        .unwrap();
+        let nbt_data = NbtData::from_value(&roots).unwrap();
+
+        let player: Player = from_bytes(nbt_data.raw_bytes()).unwrap();
+        assert_eq!(
+            player,
+            Player {
+                name: "Steve".to_string(),
+                pos: Pos { x: 7 },
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_bytes_invalid_data() {
+        #[derive(Debug, Deserialize)]
+        struct Counter {
+            #[allow(dead_code)]
+            count: i32,
+        }
+
+        assert!(matches!(
+            from_bytes::<Counter>(&[]),
+            Err(NbtReadError::EmptyFile)
+        ));
+
+        // a String where an Int is expected
+        let roots = crate::nbt! { "": { "count": "one" } }.unwrap();
+        let nbt_data = NbtData::from_value(&roots).unwrap();
+        assert!(matches!(
+            from_bytes::<Counter>(nbt_data.raw_bytes()),
+            Err(NbtReadError::Custom(_))
+        ));
+
+        // the root is not a compound
+        let mut options = ParseOptions::default();
+        options.set_any_root_tag(true);
+        let bytes = to_bytes_with_options(&vec![1i32, 2, 3], &options).unwrap();
+        assert!(matches!(
+            from_bytes::<Vec<i32>>(&bytes),
+            Err(NbtReadError::InvalidNbtRootTagId)
+        ));
+        assert_eq!(
+            from_bytes_with_options::<Vec<i32>>(&bytes, &options).unwrap(),
+            vec![1, 2, 3]
+        );
+
+        // a List with more elements than the tuple
+        assert!(from_bytes_with_options::<(i32, i32)>(&bytes, &options).is_err());
+
+        // cut in the middle of the root
+        assert!(from_bytes::<Counter>(&bytes[..bytes.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_units_round_trip() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Marker;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Units {
+            unit: (),
+            marker: Marker,
+            markers: Vec<Marker>,
+            optional: Option<()>,
+        }
+
+        let units = Units {
+            unit: (),
+            marker: Marker,
+            markers: vec![Marker, Marker],
+            optional: Some(()),
+        };
+        let bytes = to_bytes(&units).unwrap();
+        assert_eq!(from_bytes::<Units>(&bytes).unwrap(), units);
+
+        // a unit is an empty compound
+        let roots = NbtData::from_buf(bytes).unwrap().to_value().unwrap();
+        let expected = crate::nbt! {
+            "": { "unit": {}, "marker": {}, "markers": [{}, {}], "optional": {} }
+        }
+        .unwrap();
+        assert_eq!(roots, expected);
+    }
+
+    #[test]
+    fn test_from_bytes_unsigned_integers() {
+        let mut options = ParseOptions::default();
+        options.set_any_root_tag(true);
+
+        let bytes = to_bytes_with_options(&100i8, &options).unwrap();
+        assert_eq!(
+            from_bytes_with_options::<u8>(&bytes, &options).unwrap(),
+            100
+        );
+
+        // negative values are not reinterpreted
+        let bytes = to_bytes_with_options(&-56i8, &options).unwrap();
+        assert!(from_bytes_with_options::<u8>(&bytes, &options).is_err());
+        let bytes = to_bytes_with_options(&-1i64, &options).unwrap();
+        assert!(from_bytes_with_options::<u64>(&bytes, &options).is_err());
+        let bytes = to_bytes_with_options(&NbtLongArray(vec![1, -1]), &options).unwrap();
+        assert!(from_bytes_with_options::<Vec<u64>>(&bytes, &options).is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_max_depth() {
+        let roots = crate::nbt! { "": { "a": { "b": { "c": [[1i8]] } } } }.unwrap();
+        let bytes = NbtData::from_value(&roots).unwrap().raw_bytes().clone();
+
+        let mut options = ParseOptions::default();
+        options.set_max_depth(4);
+        let error = from_bytes_with_options::<IgnoredAny>(&bytes, &options).unwrap_err();
+        assert!(matches!(error, NbtReadError::MaxDepthExceeded(4)));
+
+        options.set_max_depth(5);
+        assert!(from_bytes_with_options::<IgnoredAny>(&bytes, &options).is_ok());
+    }
+}
@@ -0,0 +1,749 @@
+use crate::nbt::write;
+use crate::nbt::{NbtByteRange, NbtData, NbtEditError, NbtTag, NbtTagPosition, NbtTagType};
+
+/// Editing of the NbtTag tree.
+///
+/// Every edit keeps the tags in the order they are written, with consistent indexes, parents, children,
+/// depths, tags map and list headers, so the tree can be written back with [`NbtData::to_bytes`].
+/// The byte ranges of the tags still refer to [`NbtData::raw_bytes`], inserted tags have empty ones:
+/// parse the written bytes again to get the new ranges
+impl NbtData {
+    /// Insert `nbt_tag` as the child number `position` of the compound or list at `parent`, and return its index.
+    ///
+    /// The tag is inserted without children: a compound gets its End tag, a list starts empty.
+    /// In a list the tag loses its name and must have the type of the other elements,
+    /// the first element of an empty list sets the type of the list
+    pub fn insert_child(
+        &mut self,
+        parent: usize,
+        position: usize,
+        mut nbt_tag: NbtTag,
+    ) -> Result<usize, NbtEditError> {
+        self.check_index(parent)?;
+        Self::check_new_value(nbt_tag.value())?;
+        self.check_new_child(parent, &nbt_tag, None)?;
+        self.check_child_position(parent, position, None)?;
+
+        let roots = self.roots();
+        let index = self.tags.len();
+        let mut tag_position = NbtTagPosition::new();
+        tag_position.set_index(index);
+        tag_position.set_parent(parent);
+        nbt_tag.set_position(tag_position);
+        if let NbtTagType::List((tag_id, _)) = nbt_tag.value() {
+            nbt_tag.value = NbtTagType::List((*tag_id, 0));
+        }
+        self.tags.push(nbt_tag);
+        self.add_end_tag(index);
+
+        self.link_child(parent, position, index);
+        let new_indexes = self.reindex(&roots);
+
+        Ok(new_indexes[index].unwrap_or_default())
+    }
+
+    /// Remove the tag at `index` with all its children. A root can be removed too
+    pub fn remove_subtree(&mut self, index: usize) -> Result<(), NbtEditError> {
+        self.check_editable(index)?;
+
+        let mut roots = self.roots();
+        if roots.contains(&index) {
+            roots.retain(|&root| root != index);
+        } else {
+            self.unlink_child(index);
+        }
+        self.reindex(&roots);
+
+        Ok(())
+    }
+
+    /// Rename the tag at `index`. Elements of a list have no name
+    pub fn rename(&mut self, index: usize, name: String) -> Result<(), NbtEditError> {
+        self.check_editable(index)?;
+
+        let parent = self.tags[index].position().parent();
+        if parent != index {
+            match self.tags[parent].value() {
+                NbtTagType::List(_) => return Err(NbtEditError::UnnamedListElement(index)),
+                _ => self.check_free_name(parent, &name, Some(index))?,
+            }
+        }
+
+        self.tags[index].set_name(name);
+        self.update_tags_map();
+
+        Ok(())
+    }
+
+    /// Replace the value of the tag at `index`, possibly with a value of another type.
+    ///
+    /// The children of a compound or a list are removed: a new compound only has its End tag,
+    /// a new list is empty. A list element can only change type if it is the only element of its list.
+    /// A root stays a compound unless [`ParseOptions::any_root_tag`](crate::nbt::ParseOptions::any_root_tag) is set
+    pub fn set_value(&mut self, index: usize, value: NbtTagType) -> Result<(), NbtEditError> {
+        self.check_editable(index)?;
+        Self::check_new_value(&value)?;
+
+        let parent = self.tags[index].position().parent();
+        if parent == index
+            && !self.options.any_root_tag()
+            && !matches!(value, NbtTagType::Compound(_))
+        {
+            return Err(NbtEditError::NonCompoundRoot(index));
+        }
+        if let (true, NbtTagType::List((list_tag_id, len))) =
+            (parent != index, self.tags[parent].value().clone())
+        {
+            if list_tag_id != value.into_id() {
+                if self.own_children(parent) != [index] {
+                    return Err(NbtEditError::ListTypeMismatch {
+                        expected: list_tag_id,
+                        found: value.into_id(),
+                    });
+                }
+                self.tags[parent].value = NbtTagType::List((value.into_id(), len));
+            }
+        }
+
+        let roots = self.roots();
+        let nbt_tag = &mut self.tags[index];
+        nbt_tag.position.children.retain(|&child| child == index);
+        nbt_tag.set_value(match value {
+            NbtTagType::List((tag_id, _)) => NbtTagType::List((tag_id, 0)),
+            value => value,
+        });
+        self.add_end_tag(index);
+        self.reindex(&roots);
+
+        Ok(())
+    }
+
+    /// Move the tag at `index` with all its children to the child number `position` of `new_parent`,
+    /// and return its new index. The same rules as [`NbtData::insert_child`] apply
+    pub fn move_subtree(
+        &mut self,
+        index: usize,
+        new_parent: usize,
+        position: usize,
+    ) -> Result<usize, NbtEditError> {
+        self.check_editable(index)?;
+        self.check_index(new_parent)?;
+
+        // the new parent cannot be the tag itself or one of its descendants
+        let mut ancestor = new_parent;
+        loop {
+            if ancestor == index {
+                return Err(NbtEditError::MoveIntoItself(index));
+            }
+            let parent = self.tags[ancestor].position().parent();
+            if parent == ancestor {
+                break;
+            }
+            ancestor = parent;
+        }
+
+        self.check_new_child(new_parent, &self.tags[index], Some(index))?;
+        self.check_child_position(new_parent, position, Some(index))?;
+
+        let mut roots = self.roots();
+        if roots.contains(&index) {
+            roots.retain(|&root| root != index);
+            self.tags[index]
+                .position
+                .children
+                .retain(|&child| child != index);
+        } else {
+            self.unlink_child(index);
+        }
+        self.link_child(new_parent, position, index);
+        let new_indexes = self.reindex(&roots);
+
+        Ok(new_indexes[index].unwrap_or_default())
+    }
+
+    /// Replace the value of the tag at `index` directly in [`NbtData::raw_bytes`], without writing the rest again.
+    ///
+    /// The new value must have the same type and take as many bytes as the parsed one, e.g. any Int,
+    /// or a String with the same encoded length. Return the ranges of bytes which actually changed,
+    /// they are also kept in [`NbtData::patched_ranges`]
+    pub fn patch_value(
+        &mut self,
+        index: usize,
+        value: NbtTagType,
+    ) -> Result<Vec<NbtByteRange>, NbtEditError> {
+        self.check_editable(index)?;
+        if let NbtTagType::Lazy(_) = value {
+            return Err(NbtEditError::LazyValue);
+        }
+        let nbt_tag = &self.tags[index];
+        if let NbtTagType::Compound(_) | NbtTagType::List(_) = nbt_tag.value() {
+            return Err(NbtEditError::UnpatchableValue(index));
+        }
+        if nbt_tag.value().into_id() != value.into_id() {
+            return Err(NbtEditError::ValueTypeMismatch {
+                expected: nbt_tag.value().into_id(),
+                found: value.into_id(),
+            });
+        }
+
+        // inserted tags have no bytes yet
+        let position = nbt_tag.position();
+        let (byte_start, byte_end) = match (position.byte_start_value(), position.byte_end_value())
+        {
+            (Some(byte_start), Some(byte_end))
+                if byte_start <= byte_end && byte_end < self.raw_bytes.len() =>
+            {
+                (byte_start, byte_end)
+            }
+            _ => return Err(NbtEditError::UnpatchableValue(index)),
+        };
+
+        let mut encoded = Vec::new();
+        write::nbt_tag(&mut encoded, &value, self.options.encoding())?;
+        let width = byte_end - byte_start + 1;
+        if encoded.len() != width {
+            return Err(NbtEditError::ValueWidthMismatch {
+                expected: width,
+                found: encoded.len(),
+            });
+        }
+
+        let mut changed_ranges: Vec<NbtByteRange> = Vec::new();
+        for (offset, (old_byte, new_byte)) in self.raw_bytes[byte_start..=byte_end]
+            .iter_mut()
+            .zip(&encoded)
+            .enumerate()
+        {
+            if old_byte == new_byte {
+                continue;
+            }
+            *old_byte = *new_byte;
+
+            let offset = byte_start + offset;
+            match changed_ranges.last_mut() {
+                Some(range) if range.byte_end + 1 == offset => range.byte_end = offset,
+                _ => changed_ranges.push(NbtByteRange {
+                    byte_start: offset,
+                    byte_end: offset,
+                }),
+            }
+        }
+
+        self.tags[index].set_value(value);
+        self.patched_ranges.extend(&changed_ranges);
+
+        Ok(changed_ranges)
+    }
+
+    fn check_index(&self, index: usize) -> Result<(), NbtEditError> {
+        if index >= self.tags.len() {
+            return Err(NbtEditError::InvalidIndex(index));
+        }
+        Ok(())
+    }
+
+    // End tags close their compound, they are added and removed with it
+    fn check_editable(&self, index: usize) -> Result<(), NbtEditError> {
+        self.check_index(index)?;
+        if let NbtTagType::End(_) = self.tags[index].value() {
+            return Err(NbtEditError::EndTag);
+        }
+        Ok(())
+    }
+
+    // End tags come with their compound, Lazy values only come from parsed bytes
+    fn check_new_value(value: &NbtTagType) -> Result<(), NbtEditError> {
+        match value {
+            NbtTagType::End(_) => Err(NbtEditError::EndTag),
+            NbtTagType::Lazy(_) => Err(NbtEditError::LazyValue),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_new_child(
+        &self,
+        parent: usize,
+        nbt_tag: &NbtTag,
+        moved: Option<usize>,
+    ) -> Result<(), NbtEditError> {
+        match self.tags[parent].value() {
+            NbtTagType::Compound(_) => self.check_free_name(parent, nbt_tag.name(), moved),
+            NbtTagType::List((list_tag_id, _)) => {
+                let has_other_elements = self
+                    .own_children(parent)
+                    .iter()
+                    .any(|&child| Some(child) != moved);
+                let tag_id = nbt_tag.value().into_id();
+                if has_other_elements && *list_tag_id != tag_id {
+                    return Err(NbtEditError::ListTypeMismatch {
+                        expected: *list_tag_id,
+                        found: tag_id,
+                    });
+                }
+                Ok(())
+            }
+            _ => Err(NbtEditError::NotAContainer(parent)),
+        }
+    }
+
+    fn check_free_name(
+        &self,
+        compound: usize,
+        name: &str,
+        except: Option<usize>,
+    ) -> Result<(), NbtEditError> {
+        let is_taken = self.own_children(compound).iter().any(|&child| {
+            Some(child) != except
+                && self.tags[child].name() == name
+                && !matches!(self.tags[child].value(), NbtTagType::End(_))
+        });
+        if is_taken {
+            return Err(NbtEditError::DuplicateName(name.to_string()));
+        }
+        Ok(())
+    }
+
+    fn check_child_position(
+        &self,
+        parent: usize,
+        position: usize,
+        moved: Option<usize>,
+    ) -> Result<(), NbtEditError> {
+        // the End tag stays the last child of a compound
+        let len = self
+            .own_children(parent)
+            .iter()
+            .filter(|&&child| Some(child) != moved)
+            .filter(|&&child| !matches!(self.tags[child].value(), NbtTagType::End(_)))
+            .count();
+        if position > len {
+            return Err(NbtEditError::InvalidPosition(position, len));
+        }
+        Ok(())
+    }
+
+    // children of a tag, without the root itself which is listed in its own children
+    fn own_children(&self, index: usize) -> Vec<usize> {
+        self.tags[index]
+            .position()
+            .children
+            .iter()
+            .copied()
+            .filter(|&child| child != index)
+            .collect()
+    }
+
+    fn add_end_tag(&mut self, compound: usize) {
+        if !matches!(self.tags[compound].value(), NbtTagType::Compound(_)) {
+            return;
+        }
+
+        let end_index = self.tags.len();
+        let mut end_tag = NbtTag::new();
+        end_tag.position.set_index(end_index);
+        end_tag.position.set_parent(compound);
+        self.tags.push(end_tag);
+        self.tags[compound].position.children.push(end_index);
+    }
+
+    fn link_child(&mut self, parent: usize, position: usize, child: usize) {
+        // a root is listed first in its own children, and the End tag of a compound stays last
+        let offset = usize::from(self.tags[parent].position().children.first() == Some(&parent));
+        self.tags[parent]
+            .position
+            .children
+            .insert(offset + position, child);
+
+        self.tags[child].position.set_parent(parent);
+        if let NbtTagType::List(_) = self.tags[parent].value() {
+            let tag_id = self.tags[child].value().into_id();
+            self.tags[parent].value = NbtTagType::List((tag_id, 0));
+            self.tags[child].set_name(String::new());
+        }
+        self.update_list_len(parent);
+    }
+
+    fn unlink_child(&mut self, child: usize) {
+        let parent = self.tags[child].position().parent();
+        self.tags[parent]
+            .position
+            .children
+            .retain(|&sibling| sibling != child);
+        self.update_list_len(parent);
+    }
+
+    fn update_list_len(&mut self, list: usize) {
+        let len = self.own_children(list).len() as i32;
+        if let NbtTagType::List((tag_id, _)) = self.tags[list].value() {
+            self.tags[list].value = NbtTagType::List((*tag_id, len));
+        }
+    }
+
+    /// Rebuild the tree from the children links, in the order the tags are written.
+    /// Tags which cannot be reached from `roots` are dropped.
+    /// Return the new index of every tag, None for dropped tags
+    fn reindex(&mut self, roots: &[usize]) -> Vec<Option<usize>> {
+        let old_tags = std::mem::take(&mut self.tags);
+        let mut new_indexes = vec![None; old_tags.len()];
+
+        // (old index, new parent index, depth), the root of each subtree is its own parent
+        let mut order: Vec<(usize, Option<usize>, i64)> = Vec::with_capacity(old_tags.len());
+        let mut stack: Vec<(usize, Option<usize>, i64)> =
+            roots.iter().rev().map(|&root| (root, None, 0)).collect();
+        while let Some((old_index, parent, depth)) = stack.pop() {
+            let new_index = order.len();
+            new_indexes[old_index] = Some(new_index);
+            order.push((old_index, parent, depth));
+
+            let children = &old_tags[old_index].position().children;
+            for &child in children.iter().rev().filter(|&&child| child != old_index) {
+                stack.push((child, Some(new_index), depth + 1));
+            }
+        }
+
+        let mut old_tags: Vec<Option<NbtTag>> = old_tags.into_iter().map(Some).collect();
+        for (new_index, (old_index, parent, depth)) in order.into_iter().enumerate() {
+            let mut nbt_tag = old_tags[old_index].take().unwrap_or_default();
+            let parent = parent.unwrap_or(new_index);
+            nbt_tag.position.set_index(new_index);
+            nbt_tag.position.set_parent(parent);
+            nbt_tag.position.set_depth(depth);
+            nbt_tag.position.children.clear();
+            self.tags.push(nbt_tag);
+            self.tags[parent].position.children.push(new_index);
+        }
+
+        self.update_tags_map();
+
+        new_indexes
+    }
+
+    fn update_tags_map(&mut self) {
+        self.tags_map = self
+            .tags
+            .iter()
+            .enumerate()
+            .map(|(index, nbt_tag)| (nbt_tag.name().to_string(), index))
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::test_utils;
+    use crate::nbt::{NbtTagId, ParseOptions};
+
+    fn sample() -> NbtData {
+        NbtData::from_buf(test_utils::sample()).unwrap()
+    }
+
+    fn tag(name: &str, value: NbtTagType) -> NbtTag {
+        let mut nbt_tag = NbtTag::new();
+        nbt_tag.set_name(name.to_string());
+        nbt_tag.set_value(value);
+        nbt_tag
+    }
+
+    // the edited tree has the same structure as the tree parsed from its bytes
+    fn assert_same_as_reparsed(nbt_data: &NbtData) -> NbtData {
+        let reparsed = NbtData::from_buf(nbt_data.to_bytes().unwrap()).unwrap();
+        assert_eq!(nbt_data.nbt_tags().len(), reparsed.nbt_tags().len());
+        for (edited, parsed) in nbt_data.nbt_tags().iter().zip(reparsed.nbt_tags()) {
+            assert_eq!(edited.name(), parsed.name());
+            assert_eq!(edited.value(), parsed.value());
+            assert_eq!(edited.position().index(), parsed.position().index());
+            assert_eq!(edited.position().parent(), parsed.position().parent());
+            assert_eq!(edited.position().depth(), parsed.position().depth());
+            assert_eq!(edited.position().children, parsed.position().children);
+        }
+        assert_eq!(nbt_data.tags_map(), reparsed.tags_map());
+        reparsed
+    }
+
+    fn index_of(nbt_data: &NbtData, name: &str) -> usize {
+        nbt_data.tags_map()[name]
+    }
+
+    #[test]
+    fn test_insert_child() {
+        let mut nbt_data = sample();
+
+        let index = nbt_data
+            .insert_child(0, 1, tag("Level", NbtTagType::Int(3)))
+            .unwrap();
+        assert_eq!(index, 2);
+        assert_eq!(nbt_data.nbt_tags()[2].name(), "Level");
+
+        // a compound gets its End tag
+        let pos = index_of(&nbt_data, "Pos");
+        nbt_data
+            .insert_child(pos, 1, tag("Motion", NbtTagType::Compound(String::new())))
+            .unwrap();
+        let reparsed = assert_same_as_reparsed(&nbt_data);
+        assert_eq!(
+            reparsed.nbt_tags()[index_of(&reparsed, "Motion")]
+                .position()
+                .children
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_insert_child_in_list() {
+        let mut nbt_data = sample();
+        let scores = index_of(&nbt_data, "Scores");
+
+        let index = nbt_data
+            .insert_child(scores, 0, tag("Ignored", NbtTagType::Int(0)))
+            .unwrap();
+        assert_eq!(nbt_data.nbt_tags()[index].name(), "");
+        assert_eq!(
+            nbt_data.nbt_tags()[scores].value(),
+            &NbtTagType::List((NbtTagId::Int, 3))
+        );
+
+        assert!(matches!(
+            nbt_data.insert_child(scores, 0, tag("", NbtTagType::Byte(0))),
+            Err(NbtEditError::ListTypeMismatch { .. })
+        ));
+        assert!(matches!(
+            nbt_data.insert_child(scores, 4, tag("", NbtTagType::Int(0))),
+            Err(NbtEditError::InvalidPosition(4, 3))
+        ));
+        assert_same_as_reparsed(&nbt_data);
+    }
+
+    #[test]
+    fn test_insert_child_invalid() {
+        let mut nbt_data = sample();
+
+        assert!(matches!(
+            nbt_data.insert_child(0, 0, tag("Name", NbtTagType::Int(0))),
+            Err(NbtEditError::DuplicateName(_))
+        ));
+        assert!(matches!(
+            nbt_data.insert_child(1, 0, tag("Child", NbtTagType::Int(0))),
+            Err(NbtEditError::NotAContainer(1))
+        ));
+        assert!(matches!(
+            nbt_data.insert_child(0, 0, tag("", NbtTagType::End(None))),
+            Err(NbtEditError::EndTag)
+        ));
+        assert!(matches!(
+            nbt_data.insert_child(100, 0, tag("Child", NbtTagType::Int(0))),
+            Err(NbtEditError::InvalidIndex(100))
+        ));
+        assert_eq!(nbt_data, sample());
+    }
+
+    #[test]
+    fn test_remove_subtree() {
+        let mut nbt_data = sample();
+
+        nbt_data.remove_subtree(index_of(&nbt_data, "Pos")).unwrap();
+        nbt_data.remove_subtree(3).unwrap();
+        assert!(!nbt_data.tags_map().contains_key("Pos"));
+        assert_eq!(
+            nbt_data.nbt_tags()[index_of(&nbt_data, "Scores")].value(),
+            &NbtTagType::List((NbtTagId::Int, 1))
+        );
+        assert_same_as_reparsed(&nbt_data);
+
+        let end = nbt_data.nbt_tags().len() - 1;
+        assert!(matches!(
+            nbt_data.remove_subtree(end),
+            Err(NbtEditError::EndTag)
+        ));
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut nbt_data = sample();
+
+        nbt_data.rename(1, "Nickname".to_string()).unwrap();
+        assert_eq!(index_of(&nbt_data, "Nickname"), 1);
+        assert!(!nbt_data.tags_map().contains_key("Name"));
+        assert!(matches!(
+            nbt_data.rename(1, "Scores".to_string()),
+            Err(NbtEditError::DuplicateName(_))
+        ));
+        assert!(matches!(
+            nbt_data.rename(3, "First".to_string()),
+            Err(NbtEditError::UnnamedListElement(3))
+        ));
+        assert_same_as_reparsed(&nbt_data);
+    }
+
+    #[test]
+    fn test_set_value() {
+        let mut nbt_data = sample();
+
+        // retype a scalar
+        nbt_data.set_value(1, NbtTagType::Double(1.5)).unwrap();
+        // a list element of another type only if it is alone in its list
+        assert!(matches!(
+            nbt_data.set_value(3, NbtTagType::Byte(1)),
+            Err(NbtEditError::ListTypeMismatch { .. })
+        ));
+        nbt_data.set_value(3, NbtTagType::Int(10)).unwrap();
+        // a container replaced by a scalar loses its children
+        let pos = index_of(&nbt_data, "Pos");
+        nbt_data.set_value(pos, NbtTagType::Int(0)).unwrap();
+        assert!(!nbt_data.tags_map().contains_key("X"));
+
+        let reparsed = assert_same_as_reparsed(&nbt_data);
+        assert_eq!(reparsed.nbt_tags()[1].value(), &NbtTagType::Double(1.5));
+        assert_eq!(reparsed.nbt_tags()[3].value(), &NbtTagType::Int(10));
+    }
+
+    #[test]
+    fn test_set_value_of_root() {
+        let mut nbt_data = sample();
+
+        // the file could not be read back with the same options
+        assert!(matches!(
+            nbt_data.set_value(0, NbtTagType::Int(1)),
+            Err(NbtEditError::NonCompoundRoot(0))
+        ));
+        nbt_data
+            .set_value(0, NbtTagType::Compound(String::new()))
+            .unwrap();
+        assert_eq!(nbt_data.nbt_tags().len(), 2);
+
+        let mut options = ParseOptions::default();
+        options.set_any_root_tag(true);
+        let mut nbt_data = NbtData::from_buf_with_options(test_utils::sample(), &options).unwrap();
+        nbt_data.set_value(0, NbtTagType::Int(1)).unwrap();
+        let reparsed =
+            NbtData::from_buf_with_options(nbt_data.to_bytes().unwrap(), &options).unwrap();
+        assert_eq!(reparsed.nbt_tags()[0].value(), &NbtTagType::Int(1));
+    }
+
+    #[test]
+    fn test_move_subtree() {
+        let mut nbt_data = sample();
+        let pos = index_of(&nbt_data, "Pos");
+
+        // move Name into Pos, before X
+        let index = nbt_data.move_subtree(1, pos, 0).unwrap();
+        assert_eq!(nbt_data.nbt_tags()[index].name(), "Name");
+        assert_eq!(nbt_data.nbt_tags()[index].position().depth(), 2);
+        let pos = index_of(&nbt_data, "Pos");
+        assert_eq!(nbt_data.nbt_tags()[index].position().parent(), pos);
+
+        // Pos cannot be moved into its own children
+        let x = index_of(&nbt_data, "X");
+        assert!(matches!(
+            nbt_data.move_subtree(pos, pos, 0),
+            Err(NbtEditError::MoveIntoItself(_))
+        ));
+        assert!(matches!(
+            nbt_data.move_subtree(index, x, 0),
+            Err(NbtEditError::NotAContainer(_))
+        ));
+
+        // move the last score to the front of its list
+        let scores = index_of(&nbt_data, "Scores");
+        let last_score = nbt_data.nbt_tags()[scores].position().children[1];
+        nbt_data.move_subtree(last_score, scores, 0).unwrap();
+
+        let reparsed = assert_same_as_reparsed(&nbt_data);
+        let first_score = reparsed.nbt_tags()[scores].position().children[0];
+        assert_eq!(
+            reparsed.nbt_tags()[first_score].value(),
+            &NbtTagType::Int(2)
+        );
+    }
+
+    #[test]
+    fn test_patch_value() {
+        let mut nbt_data = sample();
+        let raw_bytes = nbt_data.raw_bytes().clone();
+
+        // X: 7L -> 264L changes 2 bytes of the 8 of the long
+        let x = index_of(&nbt_data, "X");
+        let byte_end = nbt_data.nbt_tags()[x].position().byte_end_value().unwrap();
+        let changed = nbt_data.patch_value(x, NbtTagType::Long(264)).unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].byte_start(), byte_end - 1);
+        assert_eq!(changed[0].byte_end(), byte_end);
+
+        // Steve -> Stove changes the third letter only
+        let changed = nbt_data
+            .patch_value(1, NbtTagType::String("Stove".to_string()))
+            .unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].byte_start(), changed[0].byte_end());
+        assert_eq!(nbt_data.raw_bytes()[changed[0].byte_start()], b'o');
+
+        assert_eq!(nbt_data.patched_ranges().len(), 2);
+        assert_eq!(nbt_data.raw_bytes().len(), raw_bytes.len());
+        assert_eq!(&nbt_data.to_bytes().unwrap(), nbt_data.raw_bytes());
+        let reparsed = NbtData::from_buf(nbt_data.raw_bytes().clone()).unwrap();
+        assert_eq!(reparsed.nbt_tags()[x].value(), &NbtTagType::Long(264));
+        assert_eq!(
+            reparsed.nbt_tags()[1].value(),
+            &NbtTagType::String("Stove".to_string())
+        );
+
+        // the same value changes nothing
+        assert!(nbt_data
+            .patch_value(x, NbtTagType::Long(264))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_patch_value_invalid() {
+        let mut nbt_data = sample();
+
+        assert!(matches!(
+            nbt_data.patch_value(1, NbtTagType::String("Alex".to_string())),
+            Err(NbtEditError::ValueWidthMismatch {
+                expected: 7,
+                found: 6
+            })
+        ));
+        assert!(matches!(
+            nbt_data.patch_value(1, NbtTagType::Int(0)),
+            Err(NbtEditError::ValueTypeMismatch { .. })
+        ));
+        assert!(matches!(
+            nbt_data.patch_value(0, NbtTagType::Compound(String::new())),
+            Err(NbtEditError::UnpatchableValue(0))
+        ));
+
+        // an inserted tag is only in the NbtTag tree
+        let index = nbt_data
+            .insert_child(0, 0, tag("Level", NbtTagType::Int(3)))
+            .unwrap();
+        assert!(matches!(
+            nbt_data.patch_value(index, NbtTagType::Int(4)),
+            Err(NbtEditError::UnpatchableValue(_))
+        ));
+        assert!(nbt_data.patched_ranges().is_empty());
+    }
+
+    #[test]
+    fn test_edit_multiple_roots() {
+        let mut buffer = sample().to_bytes().unwrap();
+        buffer.extend(sample().to_bytes().unwrap());
+        let mut options = crate::nbt::ParseOptions::default();
+        options.set_multiple_roots(true);
+        let mut nbt_data = NbtData::from_buf_with_options(buffer, &options).unwrap();
+        let second_root = nbt_data.roots()[1];
+
+        // the first root becomes a child of the second one
+        nbt_data.rename(0, "First".to_string()).unwrap();
+        let index = nbt_data.move_subtree(0, second_root, 0).unwrap();
+        assert_eq!(nbt_data.roots(), vec![0]);
+        assert_eq!(nbt_data.nbt_tags()[index].position().parent(), 0);
+        assert_eq!(nbt_data.nbt_tags()[0].position().children[0], 0);
+        assert_same_as_reparsed(&nbt_data);
+
+        nbt_data.remove_subtree(0).unwrap();
+        assert!(nbt_data.nbt_tags().is_empty());
+    }
+}
@@ -0,0 +1,533 @@
+use std::io::Cursor;
+use std::ops::RangeInclusive;
+
+mod push;
+
+pub use push::{NbtPushParser, NbtPushStatus};
+
+use crate::nbt;
+use crate::nbt::fsm;
+use crate::nbt::{
+    BedrockHeader, NbtReadError, NbtReadErrorContext, NbtTag, NbtTagId, NbtTagPosition, NbtTagType,
+    ParseOptions, RootMode,
+};
+
+/// Event read by [`NbtEventReader`], in the order the tags appear in the binary NBT.
+///
+/// Events only carry what is needed to follow the NBT, not whole NbtTags: the tree positions
+/// (index, depth, parent, children) are computed by [`NbtData`](crate::nbt::NbtData) when it builds
+/// the NbtTag tree. Children of a list have an empty name. Offsets are absolute byte offsets
+#[derive(Debug, Clone, PartialEq)]
+pub enum NbtEvent {
+    /// A compound starts at `offset`, its children follow until the matching End
+    CompoundStart { name: String, offset: usize },
+    /// A list of `len` elements of type `elem_id` starts at `offset`,
+    /// its elements follow until the matching ListEnd
+    ListStart {
+        name: String,
+        elem_id: NbtTagId,
+        len: i32,
+        offset: usize,
+    },
+    /// A tag which cannot have children: numbers, strings and arrays.
+    /// `span` is the inclusive byte range of the whole tag, id and name included
+    Scalar {
+        name: String,
+        value: NbtTagType,
+        span: RangeInclusive<usize>,
+    },
+    /// The End tag closing a compound
+    End,
+    /// The last element of a list was read. Lists have no closing tag
+    ListEnd,
+}
+
+// what the reader hands to the NbtData tree builder: the whole NbtTag with its tree position,
+// NbtEvent is built from it for the public iterator.
+// The NbtTag is moved into the tree right away, boxing it would only add an allocation per tag
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum TagEvent {
+    Tag(NbtTag),
+    ListEnd,
+}
+
+impl From<TagEvent> for NbtEvent {
+    fn from(event: TagEvent) -> NbtEvent {
+        let nbt_tag = match event {
+            TagEvent::Tag(nbt_tag) => nbt_tag,
+            TagEvent::ListEnd => return NbtEvent::ListEnd,
+        };
+
+        let byte_start = nbt_tag.position.byte_start_all();
+        let byte_end = nbt_tag.position.byte_end_all();
+        let is_root = nbt_tag.position.parent() == nbt_tag.position.index();
+        match nbt_tag.value {
+            NbtTagType::Compound(_) => NbtEvent::CompoundStart {
+                name: nbt_tag.name,
+                offset: byte_start,
+            },
+            NbtTagType::List((elem_id, len)) => NbtEvent::ListStart {
+                name: nbt_tag.name,
+                elem_id,
+                len,
+                offset: byte_start,
+            },
+            // a root End tag is the whole (empty) NBT, it closes nothing
+            NbtTagType::End(_) if !is_root => NbtEvent::End,
+            value => NbtEvent::Scalar {
+                name: nbt_tag.name,
+                value,
+                span: byte_start..=byte_end,
+            },
+        }
+    }
+}
+
+/// Pull parser iterating over the tags of a NBT buffer without building the NbtTag tree.
+///
+/// The iteration ends when the root is closed, or after the first error. Both can be resumed:
+/// with [`NbtEventReader::start_next_root`] and [`NbtEventReader::resync`] respectively
+#[derive(Debug, Clone)]
+pub struct NbtEventReader<B> {
+    cursor: Cursor<B>,
+    // offset of the first byte of the buffer, when the bytes already read were discarded
+    base_offset: usize,
+    nbt_parser: fsm::NbtParser,
+    options: ParseOptions,
+    bedrock_header: Option<BedrockHeader>,
+    // names of the open compounds and lists from the root, one per open container
+    path: Vec<String>,
+    pending_list_ends: usize,
+    failed: bool,
+}
+
+impl<B: AsRef<[u8]>> NbtEventReader<B> {
+    pub fn new(buffer: B, options: &ParseOptions) -> NbtEventReader<B> {
+        // Bedrock level.dat files start with a header before the root tag
+        let mut bedrock_header = None;
+        if let nbt::NbtEncoding::LittleEndian = options.encoding() {
+            bedrock_header = BedrockHeader::detect(buffer.as_ref());
+        }
+
+        let mut cursor = Cursor::new(buffer);
+        if bedrock_header.is_some() {
+            cursor.set_position(nbt::BEDROCK_HEADER_SIZE as u64);
+        }
+
+        NbtEventReader {
+            cursor,
+            base_offset: 0,
+            nbt_parser: fsm::NbtParser::new(),
+            options: options.clone(),
+            bedrock_header,
+            path: Vec::new(),
+            pending_list_ends: 0,
+            failed: false,
+        }
+    }
+
+    pub fn bedrock_header(&self) -> Option<&BedrockHeader> {
+        self.bedrock_header.as_ref()
+    }
+
+    /// Offset in the buffer of the next byte to read
+    pub fn offset(&self) -> usize {
+        self.base_offset + self.cursor.position() as usize
+    }
+
+    pub fn state(&self) -> fsm::ParseNbtFsmState {
+        *self.nbt_parser.state()
+    }
+
+    /// Depth in the NbtTag tree of the next tag
+    pub fn depth(&self) -> i64 {
+        self.nbt_parser.tree_depth()
+    }
+
+    /// Read one more root tag from the current position, once the previous root is closed.
+    /// The new root is its own parent, like the first one
+    pub fn start_next_root(&mut self) {
+        if *self.nbt_parser.state() == fsm::ParseNbtFsmState::EndOfFile {
+            self.nbt_parser.start_next_root();
+        }
+    }
+
+    /// Prepare to resume reading after `error`, returns the offset of the tag header where reading resumes.
+    ///
+    /// The next plausible header is looked for after the start of the failed tag: a tag id other than End,
+    /// followed by a non empty printable name and by a value which can be read. Only named children of the
+    /// innermost open compound can be found this way, so the lists opened inside it are dropped.
+    /// Returns None if there is nowhere to resume: the reader then stays finished
+    pub fn resync(&mut self, error: &NbtReadError) -> Option<usize> {
+        // exceeding a limit is not corruption, resuming would only exceed it again
+        if let NbtReadError::MaxDepthExceeded(_) | NbtReadError::AllocationBudgetExceeded(_) =
+            error.kind()
+        {
+            return None;
+        }
+
+        let tag_start = error.context()?.tag_start().checked_sub(self.base_offset)?;
+        if !self.nbt_parser.resync_in_compound() {
+            return None;
+        }
+        self.path.truncate(self.nbt_parser.tree_depth() as usize);
+
+        let offset = (tag_start + 1..self.cursor.get_ref().as_ref().len())
+            .find(|&offset| self.is_plausible_tag_header(offset))?;
+
+        self.cursor.set_position(offset as u64);
+        self.pending_list_ends = 0;
+        self.failed = false;
+        Some(self.base_offset + offset)
+    }
+
+    // `offset` is relative to the start of the buffer
+    fn is_plausible_tag_header(&mut self, offset: usize) -> bool {
+        let tag_id = match NbtTagId::from_u8(self.cursor.get_ref().as_ref()[offset]) {
+            Some(NbtTagId::End) | None => return false,
+            Some(tag_id) => tag_id,
+        };
+
+        self.cursor.set_position(offset as u64 + 1);
+        let is_plausible_name =
+            match fsm::parse::nbt_tag_string_with_validity(&mut self.cursor, &self.options) {
+                Ok((name, is_valid)) => {
+                    is_valid && !name.is_empty() && !name.chars().any(char::is_control)
+                }
+                Err(_) => false,
+            };
+
+        is_plausible_name
+            && self
+                .parse_nbt_tag_value(&mut NbtTag::new(), &tag_id)
+                .is_ok()
+    }
+
+    fn read_tag(&mut self) -> Result<NbtTag, NbtReadError> {
+        // #1 Initialize the new NbtTag
+        let mut new_nbt_tag = NbtTag::new();
+        let mut new_tag_position = NbtTagPosition::new();
+        new_tag_position.set_byte_start_all(self.offset());
+        new_tag_position.set_byte_end_all(self.offset());
+
+        if self.nbt_parser.tree_depth() > self.options.max_depth() {
+            return Err(self.error_with_context(
+                NbtReadError::MaxDepthExceeded(self.options.max_depth()),
+                &new_tag_position,
+            ));
+        }
+
+        // #2 Parse the NbtTag according to the FSM state
+        let state = *self.nbt_parser.state();
+        let result = match state {
+            // children of lists have no id and no name, the list defines their type
+            fsm::ParseNbtFsmState::List => {
+                self.parse_list_state(&mut new_nbt_tag, &mut new_tag_position)
+            }
+            // the first NbtTag must be a root compound, unless any root tag is allowed by the ParseOptions
+            _ => self.parse_normal_state(
+                &mut new_nbt_tag,
+                &mut new_tag_position,
+                state == fsm::ParseNbtFsmState::NbtRoot,
+            ),
+        };
+        result.map_err(|e| self.error_with_context(e, &new_tag_position))?;
+
+        // #3 Place the NbtTag in the tree
+        // byte_end is inclusive, so a tag that did not consume any byte ends 1 byte before its start
+        // this happens for compounds inside a list: list children do not have tag ids, since tag id is
+        // defined by the list itself, and compounds have no value bytes of their own.
+        // Keeping the empty range makes the byte ranges of consecutive NbtTags always continuous
+        new_tag_position.set_byte_end_all(self.offset().saturating_sub(1));
+        new_tag_position.set_index(*self.nbt_parser.index());
+        new_tag_position.set_depth(self.nbt_parser.tree_depth());
+        new_tag_position.set_parent(self.nbt_parser.parent_index());
+        // byte_end_with_children of compounds and lists is moved forward by the tree builder when a child
+        // is appended. Until then (or forever for empty lists) it is the end of the tag itself
+        new_tag_position.set_byte_end_all_with_children(new_tag_position.byte_end_all());
+        new_nbt_tag.set_position(new_tag_position);
+
+        // #4 Open or close compounds and lists, this also selects the state of the next NbtTag
+        self.update_open_containers(state, &new_nbt_tag);
+        Ok(new_nbt_tag)
+    }
+
+    fn update_open_containers(&mut self, state: fsm::ParseNbtFsmState, new_nbt_tag: &NbtTag) {
+        let index = new_nbt_tag.position().index();
+        let depth = self.nbt_parser.tree_depth();
+        // children of a list are named after their position in the list in the error context
+        let path_name = match self.nbt_parser.list_element_index() {
+            Some(element_index) => format!("[{}]", element_index),
+            None => new_nbt_tag.name().to_string(),
+        };
+        self.nbt_parser.increment_index();
+
+        // number of containers closed by the event itself, the other ones are lists closed by their last element
+        let mut closed_by_event = 0;
+        match new_nbt_tag.value() {
+            NbtTagType::Compound(_) => {
+                self.nbt_parser.open_compound(index);
+                self.path.push(path_name);
+                return;
+            }
+            // empty lists are complete at the header, there is no element to parse.
+            // vanilla reads negative lengths as empty lists as well
+            NbtTagType::List((_, len)) if *len <= 0 => {
+                self.nbt_parser.finish_element();
+                self.pending_list_ends += 1;
+            }
+            NbtTagType::List((list_tag_id, len)) => {
+                self.nbt_parser.open_list(index, *list_tag_id, *len);
+                self.path.push(path_name);
+                return;
+            }
+            // an End tag closes its compound, except as root, where it is the whole (empty) NBT
+            NbtTagType::End(_) if state != fsm::ParseNbtFsmState::NbtRoot => {
+                self.nbt_parser.close_compound();
+                closed_by_event = 1;
+            }
+            _ => self.nbt_parser.finish_element(),
+        }
+
+        // the last element of a list closes it, which can in turn finish the element of an outer list
+        let closed = (depth - self.nbt_parser.tree_depth()) as usize;
+        self.pending_list_ends += closed - closed_by_event;
+        self.path.truncate(self.nbt_parser.tree_depth() as usize);
+    }
+
+    fn error_with_context(
+        &self,
+        error: NbtReadError,
+        new_tag_position: &NbtTagPosition,
+    ) -> NbtReadError {
+        let context = NbtReadErrorContext {
+            offset: self.offset(),
+            tag_start: new_tag_position.byte_start_all(),
+            state: *self.nbt_parser.state(),
+            depth: self.nbt_parser.tree_depth(),
+            path: self.path.clone(),
+        };
+
+        NbtReadError::WithContext {
+            source: Box::new(error),
+            context: Box::new(context),
+        }
+    }
+
+    fn parse_list_state(
+        &mut self,
+        new_nbt_tag: &mut NbtTag,
+        new_tag_position: &mut NbtTagPosition,
+    ) -> Result<(), NbtReadError> {
+        let tag_id = match self.nbt_parser.list_tag_id() {
+            Some(tag_id) => tag_id,
+            None => return Err(NbtReadError::InvalidContent),
+        };
+
+        new_tag_position.set_byte_start_value(self.offset());
+        self.parse_nbt_tag_value(new_nbt_tag, &tag_id)?;
+        new_tag_position.set_byte_end_value(self.offset().saturating_sub(1));
+
+        Ok(())
+    }
+
+    fn parse_normal_state(
+        &mut self,
+        new_nbt_tag: &mut NbtTag,
+        new_tag_position: &mut NbtTagPosition,
+        is_root: bool,
+    ) -> Result<(), NbtReadError> {
+        new_tag_position.set_byte_start_id(self.offset());
+        let tag_id = match fsm::parse::nbt_tag_id(&mut self.cursor)? {
+            Some(id) => id,
+            None => return Err(NbtReadError::InvalidContent),
+        };
+        new_tag_position.set_byte_end_id(self.offset() - 1);
+
+        if is_root && tag_id != NbtTagId::Compound && !self.options.any_root_tag() {
+            return Err(NbtReadError::InvalidNbtRootTagId);
+        }
+        if tag_id == NbtTagId::End {
+            return Ok(());
+        }
+
+        // the root compound of Java network NBT has no name field,
+        // if absent the name positions stay unset
+        if !(is_root && self.options.root_mode() == RootMode::Nameless) {
+            new_tag_position.set_byte_start_name(self.offset());
+            let (name, is_valid) =
+                fsm::parse::nbt_tag_string_with_validity(&mut self.cursor, &self.options)?;
+            new_nbt_tag.set_name(name);
+            new_nbt_tag.invalid_name = !is_valid;
+            new_tag_position.set_byte_end_name(self.offset() - 1);
+        }
+
+        new_tag_position.set_byte_start_value(self.offset());
+        self.parse_nbt_tag_value(new_nbt_tag, &tag_id)?;
+        new_tag_position.set_byte_end_value(self.offset() - 1);
+
+        Ok(())
+    }
+
+    // in lazy mode the payloads of arrays and strings are skipped, NbtData decodes them on demand
+    fn parse_nbt_tag_value(
+        &mut self,
+        new_nbt_tag: &mut NbtTag,
+        tag_id: &NbtTagId,
+    ) -> Result<(), NbtReadError> {
+        if self.options.lazy_values() && fsm::parse::has_skippable_payload(tag_id) {
+            fsm::parse::skip_nbt_tag_payload(&mut self.cursor, tag_id, &self.options)?;
+            new_nbt_tag.set_value(NbtTagType::Lazy(*tag_id));
+            return Ok(());
+        }
+
+        let (value, is_valid) =
+            fsm::parse::nbt_tag_with_validity(&mut self.cursor, tag_id, &self.options)?;
+        new_nbt_tag.set_value(value);
+        new_nbt_tag.invalid_value = !is_valid;
+
+        Ok(())
+    }
+
+    /// Same as [`Iterator::next`], with the whole NbtTag instead of an NbtEvent
+    pub(crate) fn next_tag_event(&mut self) -> Option<Result<TagEvent, NbtReadError>> {
+        if self.pending_list_ends > 0 {
+            self.pending_list_ends -= 1;
+            return Some(Ok(TagEvent::ListEnd));
+        }
+        if self.failed || *self.nbt_parser.state() == fsm::ParseNbtFsmState::EndOfFile {
+            return None;
+        }
+
+        let result = self.read_tag();
+        self.failed = result.is_err();
+        Some(result.map(TagEvent::Tag))
+    }
+}
+
+impl<B: AsRef<[u8]>> Iterator for NbtEventReader<B> {
+    type Item = Result<NbtEvent, NbtReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_tag_event()
+            .map(|result| result.map(NbtEvent::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::test_utils::{list_header, named_tag_header};
+
+    // Root { Lists: [[1], []], Empty: [], Value: 7 }
+    fn nested_lists() -> Vec<u8> {
+        let mut buffer = Vec::new();
+        named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+        named_tag_header(&mut buffer, NbtTagId::List, "Lists");
+        list_header(&mut buffer, NbtTagId::List, 2);
+        list_header(&mut buffer, NbtTagId::Int, 1);
+        buffer.extend(&1i32.to_be_bytes());
+        list_header(&mut buffer, NbtTagId::Int, 0);
+        named_tag_header(&mut buffer, NbtTagId::List, "Empty");
+        list_header(&mut buffer, NbtTagId::End, 0);
+        named_tag_header(&mut buffer, NbtTagId::Int, "Value");
+        buffer.extend(&7i32.to_be_bytes());
+        buffer.push(NbtTagId::End.into_u8());
+        buffer
+    }
+
+    fn describe(event: &NbtEvent) -> String {
+        match event {
+            NbtEvent::CompoundStart { name, .. } => format!("CompoundStart {}", name),
+            NbtEvent::ListStart { name, len, .. } => format!("ListStart {} {}", name, len),
+            NbtEvent::Scalar { name, value, .. } => format!("Scalar {} {:?}", name, value),
+            NbtEvent::End => "End".to_string(),
+            NbtEvent::ListEnd => "ListEnd".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_events_of_nested_lists() {
+        let buffer = nested_lists();
+        let events: Vec<String> = NbtEventReader::new(&buffer, &ParseOptions::default())
+            .map(|event| describe(&event.unwrap()))
+            .collect();
+
+        assert_eq!(
+            events,
+            vec![
+                "CompoundStart Root",
+                "ListStart Lists 2",
+                "ListStart  1",
+                "Scalar  Int(1)",
+                "ListEnd",
+                "ListStart  0",
+                "ListEnd",
+                "ListEnd",
+                "ListStart Empty 0",
+                "ListEnd",
+                "Scalar Value Int(7)",
+                "End",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_events_carry_offsets() {
+        let buffer = nested_lists();
+        let mut reader = NbtEventReader::new(&buffer, &ParseOptions::default());
+        let events: Vec<NbtEvent> = reader.by_ref().map(|event| event.unwrap()).collect();
+
+        assert_eq!(
+            events[1],
+            NbtEvent::ListStart {
+                name: "Lists".to_string(),
+                elem_id: NbtTagId::List,
+                len: 2,
+                offset: 7,
+            }
+        );
+        // the Int inside the first inner list has no id and no name
+        assert_eq!(
+            events[3],
+            NbtEvent::Scalar {
+                name: String::new(),
+                value: NbtTagType::Int(1),
+                span: 25..=28,
+            }
+        );
+        assert_eq!(
+            events[10],
+            NbtEvent::Scalar {
+                name: "Value".to_string(),
+                value: NbtTagType::Int(7),
+                span: 47..=58,
+            }
+        );
+        assert_eq!(reader.offset(), buffer.len());
+        assert_eq!(reader.state(), fsm::ParseNbtFsmState::EndOfFile);
+    }
+
+    #[test]
+    fn test_events_stop_after_error() {
+        let mut buffer = nested_lists();
+        buffer.truncate(buffer.len() - 3);
+
+        let mut reader = NbtEventReader::new(&buffer, &ParseOptions::default());
+        let error = reader.by_ref().find_map(|event| event.err()).unwrap();
+        assert_eq!(error.context().unwrap().path(), &vec!["Root".to_string()]);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_events_scan_for_one_field() {
+        let buffer = nested_lists();
+        let value = NbtEventReader::new(&buffer, &ParseOptions::default())
+            .filter_map(|event| event.ok())
+            .find_map(|event| match event {
+                NbtEvent::Scalar { name, value, .. } if name == "Value" => Some(value),
+                _ => None,
+            });
+        assert_eq!(value, Some(NbtTagType::Int(7)));
+    }
+}
@@ -0,0 +1,283 @@
+use std::io;
+use std::io::Cursor;
+
+use crate::nbt::events::{NbtEvent, NbtEventReader};
+use crate::nbt::fsm;
+use crate::nbt::{NbtReadError, NbtTagId, ParseOptions, RootMode};
+
+/// What the push parser is waiting for after a call to [`NbtPushParser::feed`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum NbtPushStatus {
+    /// The bytes fed so far end in the middle of a tag, or before the root is closed
+    NeedMoreData,
+    /// The root is closed. With [`ParseOptions::multiple_roots`] the next bytes fed start a new root
+    Finished,
+}
+
+/// Resumable parser for NBT received in fragments, e.g. from a TCP stream.
+///
+/// Every call to [`NbtPushParser::feed`] parses all the tags completed by the new bytes. A tag cut in the
+/// middle is parsed again from its start once the size given by its headers is buffered, so the FSM state
+/// only moves forward on complete tags. The events carry absolute offsets, counted from the first byte ever fed.
+/// The bytes of complete tags are discarded, so NBT payloads fed with [`ParseOptions::lazy_values`] cannot
+/// be decoded afterwards; Bedrock level.dat headers are not detected either
+#[derive(Debug, Clone)]
+pub struct NbtPushParser {
+    reader: NbtEventReader<Vec<u8>>,
+    events: Vec<NbtEvent>,
+    multiple_roots: bool,
+    // number of buffered bytes needed before the cut tag at the start of the buffer is parsed again
+    needed_len: usize,
+    failed: bool,
+}
+
+impl NbtPushParser {
+    pub fn new(options: &ParseOptions) -> NbtPushParser {
+        NbtPushParser {
+            reader: NbtEventReader::new(Vec::new(), options),
+            events: Vec::new(),
+            multiple_roots: options.multiple_roots(),
+            needed_len: 0,
+            failed: false,
+        }
+    }
+
+    /// Parse the tags completed by `bytes`, their events are available with [`NbtPushParser::take_events`].
+    ///
+    /// An error other than a tag cut by the end of the bytes fed so far stops the parser:
+    /// the following calls ignore their bytes and return the same error again
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<NbtPushStatus, NbtReadError> {
+        if !self.failed {
+            self.reader.cursor.get_mut().extend_from_slice(bytes);
+        }
+        if self.reader.cursor.get_ref().len() < self.needed_len {
+            return Ok(NbtPushStatus::NeedMoreData);
+        }
+
+        loop {
+            match self.reader.next() {
+                Some(Ok(event)) => self.events.push(event),
+                Some(Err(error)) => {
+                    // the failed tag is parsed again from its start at the next feed:
+                    // once complete if it was cut, or to return the same error
+                    let tag_start = error.context().map_or(0, |context| context.tag_start());
+                    self.reader
+                        .cursor
+                        .set_position((tag_start - self.reader.base_offset) as u64);
+                    self.reader.failed = false;
+                    self.discard_parsed_bytes();
+
+                    if !Self::is_cut_tag(&error) {
+                        self.failed = true;
+                        return Err(error);
+                    }
+                    self.needed_len = self.min_cut_tag_size();
+                    return Ok(NbtPushStatus::NeedMoreData);
+                }
+                None => {
+                    if self.multiple_roots && !self.reader.failed && self.remaining_len() > 0 {
+                        self.reader.start_next_root();
+                        continue;
+                    }
+                    self.discard_parsed_bytes();
+                    return Ok(NbtPushStatus::Finished);
+                }
+            }
+        }
+    }
+
+    /// The events parsed since the last call, in order
+    pub fn take_events(&mut self) -> Vec<NbtEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn state(&self) -> fsm::ParseNbtFsmState {
+        self.reader.state()
+    }
+
+    /// Absolute offset of the first byte which is not part of a parsed tag
+    pub fn offset(&self) -> usize {
+        self.reader.offset()
+    }
+
+    /// Number of bytes fed but not parsed yet: the start of a cut tag, or the bytes after a finished root
+    pub fn remaining_len(&self) -> usize {
+        self.reader.cursor.get_ref().len() - self.reader.cursor.position() as usize
+    }
+
+    fn is_cut_tag(error: &NbtReadError) -> bool {
+        matches!(error.kind(), NbtReadError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof)
+    }
+
+    // the id, the name length and the payload length of the cut tag give its size once they are complete,
+    // the tag needs at least one more byte in any case
+    fn min_cut_tag_size(&self) -> usize {
+        let bytes = self.reader.cursor.get_ref().as_slice();
+        let mut cursor = Cursor::new(bytes);
+        let size = self.read_min_tag_size(&mut cursor).unwrap_or(0) as usize;
+        size.max(bytes.len() + 1)
+    }
+
+    fn read_min_tag_size(&self, cursor: &mut Cursor<&[u8]>) -> Result<u64, NbtReadError> {
+        let options = &self.reader.options;
+        let state = self.reader.state();
+
+        // children of lists have no id and no name
+        let tag_id = match state {
+            fsm::ParseNbtFsmState::List => self.reader.nbt_parser.list_tag_id(),
+            _ => fsm::parse::nbt_tag_id(cursor)?,
+        }
+        .ok_or(NbtReadError::InvalidContent)?;
+
+        let is_nameless_root =
+            state == fsm::ParseNbtFsmState::NbtRoot && options.root_mode() == RootMode::Nameless;
+        if state != fsm::ParseNbtFsmState::List && tag_id != NbtTagId::End && !is_nameless_root {
+            let name_size =
+                fsm::parse::min_nbt_tag_payload_size(cursor, &NbtTagId::String, options)?;
+            let name_end = cursor.position() + name_size;
+            // the length of the payload follows the name
+            if name_end >= cursor.get_ref().len() as u64 {
+                return Ok(name_end);
+            }
+            cursor.set_position(name_end);
+        }
+
+        let payload_size = fsm::parse::min_nbt_tag_payload_size(cursor, &tag_id, options)?;
+        Ok(cursor.position() + payload_size)
+    }
+
+    fn discard_parsed_bytes(&mut self) {
+        let parsed_len = self.reader.cursor.position() as usize;
+        self.reader.cursor.get_mut().drain(..parsed_len);
+        self.reader.cursor.set_position(0);
+        self.reader.base_offset += parsed_len;
+        self.needed_len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::test_utils::{named_tag_header, sample};
+    use crate::nbt::{NbtEventReader, NbtTagId, NbtTagType};
+
+    #[test]
+    fn test_push_parser_byte_by_byte() {
+        let buffer = sample();
+        let mut push_parser = NbtPushParser::new(&ParseOptions::default());
+        let mut events = Vec::new();
+
+        for (index, byte) in buffer.iter().enumerate() {
+            let status = push_parser.feed(&[*byte]).unwrap();
+            events.extend(push_parser.take_events());
+
+            if index + 1 < buffer.len() {
+                assert_eq!(status, NbtPushStatus::NeedMoreData);
+            } else {
+                assert_eq!(status, NbtPushStatus::Finished);
+            }
+        }
+
+        // same events and absolute offsets as a read of the whole buffer
+        let read_events: Vec<NbtEvent> = NbtEventReader::new(&buffer, &ParseOptions::default())
+            .map(|event| event.unwrap())
+            .collect();
+        assert_eq!(events, read_events);
+        assert_eq!(push_parser.offset(), buffer.len());
+        assert_eq!(push_parser.remaining_len(), 0);
+        assert_eq!(push_parser.state(), fsm::ParseNbtFsmState::EndOfFile);
+    }
+
+    #[test]
+    fn test_push_parser_emits_complete_tags_only() {
+        let buffer = sample();
+        let mut push_parser = NbtPushParser::new(&ParseOptions::default());
+
+        // the root header and half of the Name string
+        let status = push_parser.feed(&buffer[..16]).unwrap();
+        assert_eq!(status, NbtPushStatus::NeedMoreData);
+        let events = push_parser.take_events();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], NbtEvent::CompoundStart { .. }));
+        assert_eq!(push_parser.offset(), 7);
+        assert_eq!(push_parser.remaining_len(), 9);
+        assert_eq!(push_parser.state(), fsm::ParseNbtFsmState::Normal);
+
+        push_parser.feed(&buffer[16..]).unwrap();
+        let events = push_parser.take_events();
+        assert_eq!(
+            events[0],
+            NbtEvent::Scalar {
+                name: "Name".to_string(),
+                value: NbtTagType::String("Steve".to_string()),
+                span: 7..=20,
+            }
+        );
+    }
+
+    #[test]
+    fn test_push_parser_multiple_roots() {
+        let mut options = ParseOptions::default();
+        options.set_multiple_roots(true);
+        let mut push_parser = NbtPushParser::new(&options);
+
+        let buffer = sample();
+        assert_eq!(push_parser.feed(&buffer).unwrap(), NbtPushStatus::Finished);
+        let first_root = push_parser.take_events();
+
+        // the second root is split in two feeds
+        let (head, tail) = buffer.split_at(20);
+        assert_eq!(push_parser.feed(head).unwrap(), NbtPushStatus::NeedMoreData);
+        assert_eq!(push_parser.feed(tail).unwrap(), NbtPushStatus::Finished);
+
+        let second_root = push_parser.take_events();
+        assert_eq!(second_root.len(), first_root.len());
+        assert_eq!(
+            second_root[0],
+            NbtEvent::CompoundStart {
+                name: "Root".to_string(),
+                offset: buffer.len(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_push_parser_waits_for_the_size_of_cut_tags() {
+        let mut buffer = Vec::new();
+        named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+        named_tag_header(&mut buffer, NbtTagId::ByteArray, "Bytes");
+        buffer.extend(&1000i32.to_be_bytes());
+        buffer.extend(vec![1u8; 1000]);
+        buffer.push(NbtTagId::End.into_u8());
+
+        let mut push_parser = NbtPushParser::new(&ParseOptions::default());
+        assert_eq!(
+            push_parser.feed(&buffer[..20]).unwrap(),
+            NbtPushStatus::NeedMoreData
+        );
+        // the array is parsed again only once its 8 bytes of header and 1000 bytes of payload are buffered
+        assert_eq!(push_parser.offset(), 7);
+        assert_eq!(push_parser.needed_len, 8 + 4 + 1000);
+
+        for byte in &buffer[20..buffer.len() - 2] {
+            push_parser.feed(&[*byte]).unwrap();
+            assert_eq!(push_parser.offset(), 7);
+        }
+        push_parser.feed(&buffer[buffer.len() - 2..]).unwrap();
+        assert_eq!(push_parser.take_events().len(), 3);
+        assert_eq!(push_parser.offset(), buffer.len());
+    }
+
+    #[test]
+    fn test_push_parser_invalid_data() {
+        let mut push_parser = NbtPushParser::new(&ParseOptions::default());
+        assert!(push_parser.feed(&[42, 0, 0]).is_err());
+
+        // the parser stays failed, whatever is fed next
+        let error = push_parser.feed(&sample()).unwrap_err();
+        assert!(matches!(error.kind(), NbtReadError::InvalidContent));
+        assert!(push_parser.feed(&[]).is_err());
+        assert!(push_parser.take_events().is_empty());
+        assert_eq!(push_parser.offset(), 0);
+    }
+}
@@ -29,14 +29,6 @@ impl NbtListParser {
         }
     }
 
-    pub fn list_elem_count(&self) -> i32 {
-        self.list_elem_count
-    }
-
-    pub fn list_len(&self) -> i32 {
-        self.list_len
-    }
-
     pub fn set_id(&mut self, tag_id: nbt::NbtTagId) {
         self.list_tag_id = tag_id;
     }
@@ -49,47 +41,53 @@ impl NbtListParser {
         self.list_len = len;
     }
 
-    pub fn increment(&mut self) {
-        self.list_elem_count = self.list_elem_count + 1;
+    /// Position in the list of the element being parsed
+    pub fn elem_count(&self) -> i32 {
+        self.list_elem_count
     }
 
-    pub fn reset(&mut self) {
-        self.list_tag_id = nbt::NbtTagId::End;
-        self.list_len = 0;
-        self.list_elem_count = 0;
+    pub fn increment(&mut self) {
+        self.list_elem_count += 1;
     }
 
+    /// True if the element being parsed is the last one of the list.
+    /// Empty lists are never opened by the parser, so at least one element is always parsed
     pub fn is_end(&self) -> bool {
         self.list_elem_count >= self.list_len - 1
     }
 }
 
+/// A compound or a list whose children are being parsed
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+enum NbtContainer {
+    Compound,
+    List(NbtListParser),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+struct OpenContainer {
+    index: usize, // index of the compound or list in the NbtTag tree
+    container: NbtContainer,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
 pub struct NbtParser {
     state: ParseNbtFsmState,
-    list_parser: NbtListParser,
-    unfinished_lists: Vec<NbtListParser>,
-    //cursor: &'a mut Cursor<Vec<u8>>,
+    // every compound and list which is not finished yet, the innermost is the last one.
+    // Keeping lists and compounds in the same stack allows any nesting of the two
+    open_containers: Vec<OpenContainer>,
     index: usize,
-    tree_depth: i64,
 }
 
 impl NbtParser {
     pub fn new() -> NbtParser {
         NbtParser {
             state: ParseNbtFsmState::default(),
-            list_parser: NbtListParser::new(),
-            unfinished_lists: Vec::<NbtListParser>::new(),
-            //cursor: cursor,
+            open_containers: Vec::<OpenContainer>::new(),
             index: 0,
-            tree_depth: 0,
         }
     }
 
-    pub fn change_state_to(&mut self, state: ParseNbtFsmState) {
-        self.state = state;
-    }
-
     pub fn state(&self) -> &ParseNbtFsmState {
         &self.state
     }
@@ -98,70 +96,127 @@ impl NbtParser {
         &self.index
     }
 
-    pub fn tree_depth(&self) -> &i64 {
-        &self.tree_depth
-    }
-
-    pub fn set_tree_depth(&mut self, depth: i64) {
-        self.tree_depth = depth;
+    /// Depth in the NbtTag tree of the next parsed NbtTag
+    pub fn tree_depth(&self) -> i64 {
+        self.open_containers.len() as i64
     }
 
     pub fn increment_index(&mut self) {
-        self.index = self.index + 1;
+        self.index += 1;
     }
 
-    pub fn list_index(&self) -> i32 {
-        self.list_parser.list_elem_count()
-    }
-
-    pub fn list_len(&self) -> i32 {
-        self.list_parser.list_len()
-    }
-
-    pub fn switch_list_ctx(&mut self) {
-        let list_parser = self.list_parser.clone();
-        self.unfinished_lists.push(list_parser);
-        self.list_parser.reset();
+    /// Index of the parent of the next parsed NbtTag. The root is its own parent
+    pub fn parent_index(&self) -> usize {
+        match self.open_containers.last() {
+            Some(open_container) => open_container.index,
+            None => self.index,
+        }
     }
 
-    pub fn restore_list_ctx(&mut self) -> bool {
-        match self.unfinished_lists.pop() {
-            //the list of compounds was not yet finished, restore the ctx
-            Some(previous_list_parser) => {
-                self.list_parser = previous_list_parser;
-                true
+    /// Parse a new root after the previous one is closed
+    pub fn start_next_root(&mut self) {
+        self.state = ParseNbtFsmState::NbtRoot;
+    }
+
+    pub fn open_compound(&mut self, index: usize) {
+        self.open_containers.push(OpenContainer {
+            index,
+            container: NbtContainer::Compound,
+        });
+        self.sync_state();
+    }
+
+    pub fn open_list(&mut self, index: usize, tag_id: nbt::NbtTagId, len: i32) {
+        let mut list_parser = NbtListParser::new();
+        list_parser.set_id(tag_id);
+        list_parser.set_len(len);
+
+        self.open_containers.push(OpenContainer {
+            index,
+            container: NbtContainer::List(list_parser),
+        });
+        self.sync_state();
+    }
+
+    /// Close the innermost compound, when its End tag is found
+    pub fn close_compound(&mut self) {
+        self.open_containers.pop();
+        self.sync_state();
+        // the compound was an element of its parent
+        self.finish_element();
+    }
+
+    /// To be called when a child of the innermost container is completely parsed.
+    /// Lists are closed when their last element is finished, which in turn
+    /// can finish the element of an outer list
+    pub fn finish_element(&mut self) {
+        match self.open_containers.last_mut() {
+            Some(OpenContainer {
+                container: NbtContainer::List(list_parser),
+                ..
+            }) => {
+                if list_parser.is_end() {
+                    self.open_containers.pop();
+                    self.sync_state();
+                    self.finish_element();
+                } else {
+                    list_parser.increment();
+                }
             }
-            // the list of compounds was finished and we do not need to restore the ctx
-            // only in this case we will have a depth_delta of -2 because
-            // compound is finished (=-1) and the list as well (=-1)
-            None => false,
+            Some(_) => {}
+            // a root which is not a compound, or an empty list, is complete by itself
+            None => self.state = ParseNbtFsmState::EndOfFile,
         }
     }
 
-    // Delegating NbtListParser methods to NbtParser
-
-    pub fn set_list_tag_id(&mut self, tag_id: nbt::NbtTagId) {
-        self.list_parser.set_id(tag_id);
-    }
-
-    pub fn list_tag_id(&self) -> &nbt::NbtTagId {
-        &self.list_parser.tag_id()
-    }
-
-    pub fn set_list_len(&mut self, len: i32) {
-        self.list_parser.set_len(len);
+    /// Drop the lists opened inside the innermost compound, so that parsing can resume after
+    /// corrupted bytes with a named child of that compound. Returns false if no compound is open
+    pub fn resync_in_compound(&mut self) -> bool {
+        while let Some(open_container) = self.open_containers.last() {
+            if open_container.container == NbtContainer::Compound {
+                self.sync_state();
+                return true;
+            }
+            self.open_containers.pop();
+        }
+        self.sync_state();
+        false
     }
 
-    pub fn increment_list_index(&mut self) {
-        self.list_parser.increment();
+    pub fn list_tag_id(&self) -> Option<nbt::NbtTagId> {
+        match self.open_containers.last() {
+            Some(OpenContainer {
+                container: NbtContainer::List(list_parser),
+                ..
+            }) => Some(*list_parser.tag_id()),
+            _ => None,
+        }
     }
 
-    pub fn reset_list(&mut self) {
-        self.list_parser.reset();
+    /// Position of the next parsed NbtTag in its list, None if its parent is not a list
+    pub fn list_element_index(&self) -> Option<i32> {
+        match self.open_containers.last() {
+            Some(OpenContainer {
+                container: NbtContainer::List(list_parser),
+                ..
+            }) => Some(list_parser.elem_count()),
+            _ => None,
+        }
     }
 
-    pub fn is_list_end(&self) -> bool {
-        self.list_parser.is_end()
+    // the state follows the innermost container: children of lists have no id and no name
+    fn sync_state(&mut self) {
+        self.state = match self.open_containers.last() {
+            Some(OpenContainer {
+                container: NbtContainer::Compound,
+                ..
+            }) => ParseNbtFsmState::Normal,
+            Some(OpenContainer {
+                container: NbtContainer::List(_),
+                ..
+            }) => ParseNbtFsmState::List,
+            None => ParseNbtFsmState::EndOfFile,
+        };
     }
 }
 
@@ -221,22 +276,6 @@ mod tests {
         assert_eq!(parser.list_elem_count, 2);
     }
 
-    #[test]
-    fn test_nbt_list_parser_reset() {
-        let mut parser = NbtListParser::new();
-        parser.set_id(NbtTagId::Int);
-        parser.set_len(5);
-        parser.increment();
-        assert_eq!(parser.list_tag_id, NbtTagId::Int);
-        assert_eq!(parser.list_len, 5);
-        assert_eq!(parser.list_elem_count, 1);
-
-        parser.reset();
-        assert_eq!(parser.list_tag_id, NbtTagId::End);
-        assert_eq!(parser.list_len, 0);
-        assert_eq!(parser.list_elem_count, 0);
-    }
-
     #[test]
     fn test_nbt_list_parser_is_end() {
         let mut parser = NbtListParser::new();
@@ -293,26 +332,10 @@ mod tests {
             ParseNbtFsmState::NbtRoot => (),
             _ => panic!("Initial state should be NbtRoot"),
         }
-        assert_eq!(parser.list_parser.list_tag_id, NbtTagId::End);
-        assert!(parser.unfinished_lists.is_empty());
+        assert!(parser.open_containers.is_empty());
         assert_eq!(parser.index, 0);
-        assert_eq!(parser.tree_depth, 0);
-    }
-
-    #[test]
-    fn test_nbt_parser_change_state_to() {
-        let mut parser = NbtParser::new();
-        parser.change_state_to(ParseNbtFsmState::List);
-        match parser.state {
-            ParseNbtFsmState::List => (),
-            _ => panic!("State should be List"),
-        }
-
-        parser.change_state_to(ParseNbtFsmState::EndOfFile);
-        match parser.state {
-            ParseNbtFsmState::EndOfFile => (),
-            _ => panic!("State should be EndOfFile"),
-        }
+        assert_eq!(parser.tree_depth(), 0);
+        assert_eq!(parser.parent_index(), 0);
     }
 
     #[test]
@@ -328,88 +351,101 @@ mod tests {
     }
 
     #[test]
-    fn test_nbt_parser_tree_depth() {
-        let parser = NbtParser::new();
-        assert_eq!(*parser.tree_depth(), 0);
-
+    fn test_nbt_parser_open_and_close_compound() {
         let mut parser = NbtParser::new();
-        parser.set_tree_depth(3);
-        assert_eq!(*parser.tree_depth(), 3);
+        parser.open_compound(0);
+        assert_eq!(parser.state(), &ParseNbtFsmState::Normal);
+        assert_eq!(parser.tree_depth(), 1);
+        assert_eq!(parser.parent_index(), 0);
+
+        parser.open_compound(3);
+        assert_eq!(parser.tree_depth(), 2);
+        assert_eq!(parser.parent_index(), 3);
+
+        parser.close_compound();
+        assert_eq!(parser.tree_depth(), 1);
+        assert_eq!(parser.parent_index(), 0);
 
-        parser.set_tree_depth(-1);
-        assert_eq!(*parser.tree_depth(), -1);
+        // closing the root ends the parsing
+        parser.close_compound();
+        assert_eq!(parser.tree_depth(), 0);
+        assert_eq!(parser.state(), &ParseNbtFsmState::EndOfFile);
     }
 
     #[test]
-    fn test_nbt_parser_set_tree_depth() {
+    fn test_nbt_parser_list_elements() {
         let mut parser = NbtParser::new();
-        parser.set_tree_depth(10);
-        assert_eq!(parser.tree_depth, 10);
+        parser.open_compound(0);
+        parser.open_list(1, NbtTagId::Int, 2);
+        assert_eq!(parser.state(), &ParseNbtFsmState::List);
+        assert_eq!(parser.list_tag_id(), Some(NbtTagId::Int));
+        assert_eq!(parser.parent_index(), 1);
 
-        parser.set_tree_depth(0);
-        assert_eq!(parser.tree_depth, 0);
+        parser.finish_element();
+        assert_eq!(parser.state(), &ParseNbtFsmState::List);
+
+        // the last element closes the list
+        parser.finish_element();
+        assert_eq!(parser.state(), &ParseNbtFsmState::Normal);
+        assert_eq!(parser.list_tag_id(), None);
+        assert_eq!(parser.parent_index(), 0);
     }
 
     #[test]
-    fn test_nbt_parser_increment_index() {
+    fn test_nbt_parser_root_without_children() {
         let mut parser = NbtParser::new();
-        assert_eq!(parser.index, 0);
-        parser.increment_index();
-        assert_eq!(parser.index, 1);
-        parser.increment_index();
-        assert_eq!(parser.index, 2);
+        parser.finish_element();
+        assert_eq!(parser.state(), &ParseNbtFsmState::EndOfFile);
     }
 
     #[test]
-    fn test_nbt_parser_unfinished_lists() {
+    fn test_nbt_parser_list_of_lists_of_compounds() {
         let mut parser = NbtParser::new();
-        assert!(parser.unfinished_lists.is_empty());
+        parser.open_compound(0);
+        parser.open_list(1, NbtTagId::List, 1);
+        parser.open_list(2, NbtTagId::Compound, 1);
+        parser.open_compound(3);
+        assert_eq!(parser.tree_depth(), 4);
+        assert_eq!(parser.state(), &ParseNbtFsmState::Normal);
 
-        let list1 = NbtListParser::new();
-        parser.unfinished_lists.push(list1.clone());
-        assert_eq!(parser.unfinished_lists.len(), 1);
-        assert_eq!(parser.unfinished_lists[0], list1);
+        // closing the only compound finishes the inner list, which finishes the outer list
+        parser.close_compound();
+        assert_eq!(parser.tree_depth(), 1);
+        assert_eq!(parser.parent_index(), 0);
+        assert_eq!(parser.state(), &ParseNbtFsmState::Normal);
+    }
+
+    #[test]
+    fn test_nbt_parser_resync_in_compound() {
+        let mut parser = NbtParser::new();
+        parser.open_compound(0);
+        parser.open_compound(1);
+        parser.open_list(2, NbtTagId::List, 3);
+        parser.open_list(3, NbtTagId::Int, 3);
+        assert!(parser.resync_in_compound());
+        assert_eq!(parser.tree_depth(), 2);
+        assert_eq!(parser.parent_index(), 1);
+        assert_eq!(parser.state(), &ParseNbtFsmState::Normal);
 
-        let list2 = NbtListParser::new();
-        parser.unfinished_lists.push(list2.clone());
-        assert_eq!(parser.unfinished_lists.len(), 2);
-        assert_eq!(parser.unfinished_lists[1], list2);
+        let mut parser = NbtParser::new();
+        parser.open_list(0, NbtTagId::Int, 3);
+        assert!(!parser.resync_in_compound());
+        assert_eq!(parser.state(), &ParseNbtFsmState::EndOfFile);
     }
 
     #[test]
-    fn test_nbt_parser_list_parser_methods() {
+    fn test_nbt_parser_next_root() {
         let mut parser = NbtParser::new();
-        let mut list_parser = &mut parser.list_parser;
-
-        // Test default values
-        assert_eq!(list_parser.list_tag_id, NbtTagId::End);
-        assert_eq!(list_parser.list_len, 0);
-        assert_eq!(list_parser.list_elem_count, 0);
-
-        // Set ID
-        list_parser.set_id(NbtTagId::Float);
-        assert_eq!(list_parser.list_tag_id, NbtTagId::Float);
-
-        // Set Length
-        list_parser.set_len(20);
-        assert_eq!(list_parser.list_len, 20);
-
-        // Increment
-        list_parser.increment();
-        assert_eq!(list_parser.list_elem_count, 1);
-
-        // Check is_end
-        list_parser.set_len(3);
-        list_parser.set_id(NbtTagId::Double);
-        list_parser.list_elem_count = 1;
-        assert!(!list_parser.is_end());
-        list_parser.increment();
-        assert!(list_parser.is_end());
-
-        // Reset
-        list_parser.reset();
-        assert_eq!(list_parser.list_tag_id, NbtTagId::End);
-        assert_eq!(list_parser.list_len, 0);
-        assert_eq!(list_parser.list_elem_count, 0);
+        parser.open_compound(0);
+        parser.increment_index();
+        parser.close_compound();
+        parser.increment_index();
+        assert_eq!(parser.state(), &ParseNbtFsmState::EndOfFile);
+
+        // the new root is its own parent
+        parser.start_next_root();
+        assert_eq!(parser.state(), &ParseNbtFsmState::NbtRoot);
+        assert_eq!(parser.parent_index(), 2);
+        assert_eq!(parser.tree_depth(), 0);
     }
 }
@@ -1,44 +1,188 @@
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use std::io;
 use std::io::Cursor;
 
 use crate::nbt;
 
-pub fn nbt_tag_id(
-    cursor: &mut Cursor<Vec<u8>>,
-) -> Result<Option<nbt::NbtTagId>, nbt::NbtReadError> {
-    let id = cursor.read_u8()?;
+// Bedrock network NBT never uses more than 5 bytes for a 32 bit VarInt and 10 bytes for a 64 bit one
+const MAX_VARINT32_BYTES: u32 = 5;
+const MAX_VARINT64_BYTES: u32 = 10;
 
-    let tag_id = match nbt::NbtTagId::from_u8(id) {
-        None => None,
-        Some(id) => Some(id),
-    };
+fn read_varint<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    max_bytes: u32,
+) -> Result<u64, nbt::NbtReadError> {
+    let mut value: u64 = 0;
+
+    for i in 0..max_bytes {
+        let byte = cursor.read_u8()?;
+        value |= u64::from(byte & 0x7F) << (7 * i);
+
+        // the most significant bit is set on every byte except the last one
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(nbt::NbtReadError::InvalidVarInt)
+}
 
-    Ok(tag_id)
+fn read_zigzag_varint32<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> Result<i32, nbt::NbtReadError> {
+    let raw = read_varint(cursor, MAX_VARINT32_BYTES)? as u32;
+    Ok((raw >> 1) as i32 ^ -((raw & 1) as i32))
 }
 
-pub fn nbt_tag_string(cursor: &mut Cursor<Vec<u8>>) -> Result<String, nbt::NbtReadError> {
-    let name_len = cursor.read_i16::<BigEndian>()?;
+fn read_zigzag_varint64<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> Result<i64, nbt::NbtReadError> {
+    let raw = read_varint(cursor, MAX_VARINT64_BYTES)?;
+    Ok((raw >> 1) as i64 ^ -((raw & 1) as i64))
+}
+
+fn read_i16<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    options: &nbt::ParseOptions,
+) -> io::Result<i16> {
+    match options.encoding() {
+        nbt::NbtEncoding::BigEndian => cursor.read_i16::<BigEndian>(),
+        nbt::NbtEncoding::LittleEndian | nbt::NbtEncoding::NetworkLittleEndian => {
+            cursor.read_i16::<LittleEndian>()
+        }
+    }
+}
+
+fn read_i32<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    options: &nbt::ParseOptions,
+) -> Result<i32, nbt::NbtReadError> {
+    match options.encoding() {
+        nbt::NbtEncoding::BigEndian => Ok(cursor.read_i32::<BigEndian>()?),
+        nbt::NbtEncoding::LittleEndian => Ok(cursor.read_i32::<LittleEndian>()?),
+        nbt::NbtEncoding::NetworkLittleEndian => read_zigzag_varint32(cursor),
+    }
+}
 
-    if name_len < 0 {
+fn read_i64<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    options: &nbt::ParseOptions,
+) -> Result<i64, nbt::NbtReadError> {
+    match options.encoding() {
+        nbt::NbtEncoding::BigEndian => Ok(cursor.read_i64::<BigEndian>()?),
+        nbt::NbtEncoding::LittleEndian => Ok(cursor.read_i64::<LittleEndian>()?),
+        nbt::NbtEncoding::NetworkLittleEndian => read_zigzag_varint64(cursor),
+    }
+}
+
+fn read_f32<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    options: &nbt::ParseOptions,
+) -> io::Result<f32> {
+    match options.encoding() {
+        nbt::NbtEncoding::BigEndian => cursor.read_f32::<BigEndian>(),
+        nbt::NbtEncoding::LittleEndian | nbt::NbtEncoding::NetworkLittleEndian => {
+            cursor.read_f32::<LittleEndian>()
+        }
+    }
+}
+
+fn read_f64<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    options: &nbt::ParseOptions,
+) -> io::Result<f64> {
+    match options.encoding() {
+        nbt::NbtEncoding::BigEndian => cursor.read_f64::<BigEndian>(),
+        nbt::NbtEncoding::LittleEndian | nbt::NbtEncoding::NetworkLittleEndian => {
+            cursor.read_f64::<LittleEndian>()
+        }
+    }
+}
+
+/// String lengths are an unsigned 16 bit integer, as written by Java's DataOutput::writeUTF,
+/// except in network NBT where they are an unsigned VarInt
+fn read_string_len<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    options: &nbt::ParseOptions,
+) -> Result<i64, nbt::NbtReadError> {
+    match options.encoding() {
+        nbt::NbtEncoding::BigEndian => Ok(i64::from(cursor.read_u16::<BigEndian>()?)),
+        nbt::NbtEncoding::LittleEndian => Ok(i64::from(cursor.read_u16::<LittleEndian>()?)),
+        nbt::NbtEncoding::NetworkLittleEndian => {
+            Ok(i64::from(read_varint(cursor, MAX_VARINT32_BYTES)? as u32))
+        }
+    }
+}
+
+/// Check an array length before allocating it: the declared length must be positive and fit in the
+/// allocation budget, and the capacity is capped by the bytes left, so that a corrupted length
+/// cannot allocate more than the input itself
+fn array_capacity<T: AsRef<[u8]>>(
+    cursor: &Cursor<T>,
+    len: i32,
+    elem_size: usize,
+    options: &nbt::ParseOptions,
+) -> Result<usize, nbt::NbtReadError> {
+    if len < 0 {
         return Err(nbt::NbtReadError::NegativeNbtTagLenght);
     }
 
-    let mut name = String::with_capacity(name_len as usize);
+    let len = len as usize;
+    if len.saturating_mul(elem_size) > options.max_allocation() {
+        return Err(nbt::NbtReadError::AllocationBudgetExceeded(
+            options.max_allocation(),
+        ));
+    }
+
+    let remaining = cursor
+        .get_ref()
+        .as_ref()
+        .len()
+        .saturating_sub(cursor.position() as usize);
+    Ok(len.min(remaining))
+}
+
+pub fn nbt_tag_id<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+) -> Result<Option<nbt::NbtTagId>, nbt::NbtReadError> {
+    let id = cursor.read_u8()?;
+
+    Ok(nbt::NbtTagId::from_u8(id))
+}
+
+pub fn nbt_tag_string<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    options: &nbt::ParseOptions,
+) -> Result<String, nbt::NbtReadError> {
+    nbt_tag_string_with_validity(cursor, options).map(|(value, _)| value)
+}
+
+/// Read a string and decode it, the second element is false if the string could not be decoded
+/// and was kept byte by byte (see [`nbt::mutf8::decode_lossless`])
+pub fn nbt_tag_string_with_validity<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    options: &nbt::ParseOptions,
+) -> Result<(String, bool), nbt::NbtReadError> {
+    let name_len = read_string_len(cursor, options)?;
+
+    // a corrupted VarInt length could be huge, do not reserve more than what is left to read
+    let remaining = cursor.get_ref().as_ref().len() as u64
+        - cursor
+            .position()
+            .min(cursor.get_ref().as_ref().len() as u64);
+    let mut raw_name = Vec::with_capacity((name_len as u64).min(remaining) as usize);
 
     for _ in 0..name_len {
         let ch = match cursor.read_u8() {
             Ok(ch) => ch,
             Err(e) => return Err(nbt::NbtReadError::Io(e)),
         };
-        name.push(ch as char)
+        raw_name.push(ch)
     }
 
-    Ok(name)
+    Ok(nbt::mutf8::decode_lossless(&raw_name, options.encoding()))
 }
 
-pub fn nbt_tag(
-    cursor: &mut Cursor<Vec<u8>>,
+pub fn nbt_tag<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
     tag_id: &nbt::NbtTagId,
+    options: &nbt::ParseOptions,
 ) -> Result<nbt::NbtTagType, nbt::NbtReadError> {
     let tag_value = match tag_id {
         nbt::NbtTagId::End => nbt::NbtTagType::End(None),
@@ -52,7 +196,7 @@ pub fn nbt_tag(
         }
 
         nbt::NbtTagId::Short => {
-            let raw_tag_value = match cursor.read_i16::<BigEndian>() {
+            let raw_tag_value = match read_i16(cursor, options) {
                 Ok(x) => x,
                 Err(e) => return Err(nbt::NbtReadError::Io(e)),
             };
@@ -60,23 +204,17 @@ pub fn nbt_tag(
         }
 
         nbt::NbtTagId::Int => {
-            let raw_tag_value = match cursor.read_i32::<BigEndian>() {
-                Ok(x) => x,
-                Err(e) => return Err(nbt::NbtReadError::Io(e)),
-            };
+            let raw_tag_value = read_i32(cursor, options)?;
             nbt::NbtTagType::Int(raw_tag_value)
         }
 
         nbt::NbtTagId::Long => {
-            let raw_tag_value = match cursor.read_i64::<BigEndian>() {
-                Ok(x) => x,
-                Err(e) => return Err(nbt::NbtReadError::Io(e)),
-            };
+            let raw_tag_value = read_i64(cursor, options)?;
             nbt::NbtTagType::Long(raw_tag_value)
         }
 
         nbt::NbtTagId::Float => {
-            let raw_tag_value = match cursor.read_f32::<BigEndian>() {
+            let raw_tag_value = match read_f32(cursor, options) {
                 Ok(x) => x,
                 Err(e) => return Err(nbt::NbtReadError::Io(e)),
             };
@@ -84,7 +222,7 @@ pub fn nbt_tag(
         }
 
         nbt::NbtTagId::Double => {
-            let raw_tag_value = match cursor.read_f64::<BigEndian>() {
+            let raw_tag_value = match read_f64(cursor, options) {
                 Ok(x) => x,
                 Err(e) => return Err(nbt::NbtReadError::Io(e)),
             };
@@ -92,16 +230,13 @@ pub fn nbt_tag(
         }
 
         nbt::NbtTagId::ByteArray => {
-            let len = match cursor.read_i32::<BigEndian>() {
-                Ok(x) => x,
-                Err(e) => return Err(nbt::NbtReadError::Io(e)),
-            };
+            let len = read_i32(cursor, options)?;
 
-            if len > nbt::MAX_BYTE_ARRAY_LENGTH {
+            if len > options.max_byte_array_length() {
                 return Err(nbt::NbtReadError::InvalidNbtByteArrayLenght);
             }
 
-            let mut buf = Vec::with_capacity(len as usize);
+            let mut buf = Vec::with_capacity(array_capacity(cursor, len, 1, options)?);
             for _ in 0..len {
                 let x = cursor.read_i8()?;
                 buf.push(x);
@@ -111,7 +246,7 @@ pub fn nbt_tag(
         }
 
         nbt::NbtTagId::String => {
-            let raw_tag_value = nbt_tag_string(cursor);
+            let raw_tag_value = nbt_tag_string(cursor, options);
 
             match raw_tag_value {
                 Ok(value) => nbt::NbtTagType::String(value),
@@ -128,8 +263,13 @@ pub fn nbt_tag(
                 Err(e) => return Err(e),
             };
 
-            let len = cursor.read_i32::<BigEndian>()?;
-            if len > nbt::MAX_LIST_LENGTH {
+            let len = read_i32(cursor, options)?;
+            if len > options.max_list_length() {
+                return Err(nbt::NbtReadError::InvalidNbtListLenght);
+            }
+            // End elements have no bytes at all, so any length could be declared for free.
+            // Vanilla rejects lists of End tags which are not empty
+            if list_elem_tag_ids == nbt::NbtTagId::End && len > 0 {
                 return Err(nbt::NbtReadError::InvalidNbtListLenght);
             }
             nbt::NbtTagType::List((list_elem_tag_ids, len))
@@ -138,28 +278,28 @@ pub fn nbt_tag(
         nbt::NbtTagId::Compound => nbt::NbtTagType::Compound("".to_string()),
 
         nbt::NbtTagId::IntArray => {
-            let len = cursor.read_i32::<BigEndian>()?;
-            if len > nbt::MAX_INT_ARRAY_LENGTH {
+            let len = read_i32(cursor, options)?;
+            if len > options.max_int_array_length() {
                 return Err(nbt::NbtReadError::InvalidNbtIntArrayLenght);
             }
 
-            let mut buf = Vec::with_capacity(len as usize);
+            let mut buf = Vec::with_capacity(array_capacity(cursor, len, 4, options)?);
             for _ in 0..len {
-                let x = cursor.read_i32::<BigEndian>()?;
+                let x = read_i32(cursor, options)?;
                 buf.push(x);
             }
 
             nbt::NbtTagType::IntArray(buf)
         }
         nbt::NbtTagId::LongArray => {
-            let len = cursor.read_i32::<BigEndian>()?;
-            if len > nbt::MAX_LONG_ARRAY_LENGTH {
+            let len = read_i32(cursor, options)?;
+            if len > options.max_long_array_length() {
                 return Err(nbt::NbtReadError::InvalidNbtLongArrayLenght);
             }
 
-            let mut buf = Vec::with_capacity(len as usize);
+            let mut buf = Vec::with_capacity(array_capacity(cursor, len, 8, options)?);
             for _ in 0..len {
-                let x = cursor.read_i64::<BigEndian>()?;
+                let x = read_i64(cursor, options)?;
                 buf.push(x);
             }
 
@@ -170,6 +310,136 @@ pub fn nbt_tag(
     Ok(tag_value)
 }
 
+/// Read a value like [`nbt_tag`], the second element is false if the value is a string
+/// which could not be decoded and was kept byte by byte
+pub fn nbt_tag_with_validity<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    tag_id: &nbt::NbtTagId,
+    options: &nbt::ParseOptions,
+) -> Result<(nbt::NbtTagType, bool), nbt::NbtReadError> {
+    match tag_id {
+        nbt::NbtTagId::String => {
+            let (value, is_valid) = nbt_tag_string_with_validity(cursor, options)?;
+            Ok((nbt::NbtTagType::String(value), is_valid))
+        }
+        _ => Ok((nbt_tag(cursor, tag_id, options)?, true)),
+    }
+}
+
+/// Read the length prefix of the payload at the cursor, if it has one, and return the minimal number of bytes
+/// which follow it. The size is exact, except for network VarInts which take at least one byte each
+pub fn min_nbt_tag_payload_size<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    tag_id: &nbt::NbtTagId,
+    options: &nbt::ParseOptions,
+) -> Result<u64, nbt::NbtReadError> {
+    let is_network = options.encoding() == nbt::NbtEncoding::NetworkLittleEndian;
+    let (len, elem_size) = match tag_id {
+        nbt::NbtTagId::End | nbt::NbtTagId::Compound => return Ok(0),
+        nbt::NbtTagId::Byte => return Ok(1),
+        nbt::NbtTagId::Short => return Ok(2),
+        nbt::NbtTagId::Int | nbt::NbtTagId::Float if !is_network => return Ok(4),
+        nbt::NbtTagId::Long | nbt::NbtTagId::Double if !is_network => return Ok(8),
+        nbt::NbtTagId::Int | nbt::NbtTagId::Long => return Ok(1),
+        nbt::NbtTagId::Float => return Ok(4),
+        nbt::NbtTagId::Double => return Ok(8),
+        // the header of a list is its payload, the elements are separate tags
+        nbt::NbtTagId::List => {
+            cursor.read_u8()?;
+            read_i32(cursor, options)?;
+            return Ok(0);
+        }
+        nbt::NbtTagId::String => (read_string_len(cursor, options)?, 1),
+        nbt::NbtTagId::ByteArray => (i64::from(read_i32(cursor, options)?), 1),
+        nbt::NbtTagId::IntArray if !is_network => (i64::from(read_i32(cursor, options)?), 4),
+        nbt::NbtTagId::LongArray if !is_network => (i64::from(read_i32(cursor, options)?), 8),
+        nbt::NbtTagId::IntArray | nbt::NbtTagId::LongArray => {
+            (i64::from(read_i32(cursor, options)?), 1)
+        }
+    };
+
+    if len < 0 {
+        return Err(nbt::NbtReadError::NegativeNbtTagLenght);
+    }
+    Ok(len as u64 * elem_size)
+}
+
+/// True for the tags whose payload can be skipped by [`skip_nbt_tag_payload`]
+pub fn has_skippable_payload(tag_id: &nbt::NbtTagId) -> bool {
+    matches!(
+        tag_id,
+        nbt::NbtTagId::ByteArray
+            | nbt::NbtTagId::String
+            | nbt::NbtTagId::IntArray
+            | nbt::NbtTagId::LongArray
+    )
+}
+
+/// Check the length of an array or a string like [`nbt_tag`] does, then move the cursor past
+/// the payload without decoding it
+pub fn skip_nbt_tag_payload<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    tag_id: &nbt::NbtTagId,
+    options: &nbt::ParseOptions,
+) -> Result<(), nbt::NbtReadError> {
+    let (len, elem_size) = match tag_id {
+        nbt::NbtTagId::ByteArray => {
+            let len = read_i32(cursor, options)?;
+            if len > options.max_byte_array_length() {
+                return Err(nbt::NbtReadError::InvalidNbtByteArrayLenght);
+            }
+            (i64::from(len), 1)
+        }
+        nbt::NbtTagId::String => (read_string_len(cursor, options)?, 1),
+        nbt::NbtTagId::IntArray => {
+            let len = read_i32(cursor, options)?;
+            if len > options.max_int_array_length() {
+                return Err(nbt::NbtReadError::InvalidNbtIntArrayLenght);
+            }
+            (i64::from(len), 4)
+        }
+        nbt::NbtTagId::LongArray => {
+            let len = read_i32(cursor, options)?;
+            if len > options.max_long_array_length() {
+                return Err(nbt::NbtReadError::InvalidNbtLongArrayLenght);
+            }
+            (i64::from(len), 8)
+        }
+        _ => {
+            nbt_tag(cursor, tag_id, options)?;
+            return Ok(());
+        }
+    };
+
+    if len < 0 {
+        return Err(nbt::NbtReadError::NegativeNbtTagLenght);
+    }
+
+    // the elements of network int and long arrays are VarInts, which have no fixed size
+    if elem_size > 1 && options.encoding() == nbt::NbtEncoding::NetworkLittleEndian {
+        let max_bytes = if elem_size == 4 {
+            MAX_VARINT32_BYTES
+        } else {
+            MAX_VARINT64_BYTES
+        };
+        for _ in 0..len {
+            read_varint(cursor, max_bytes)?;
+        }
+        return Ok(());
+    }
+
+    let remaining = (cursor.get_ref().as_ref().len() as u64).saturating_sub(cursor.position());
+    let payload_size = len as u64 * elem_size;
+    if payload_size > remaining {
+        return Err(nbt::NbtReadError::Io(io::Error::from(
+            io::ErrorKind::UnexpectedEof,
+        )));
+    }
+    cursor.set_position(cursor.position() + payload_size);
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -231,7 +501,7 @@ mod tests {
         data.push(b'e');
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        let result = nbt_tag_string(&mut cursor).unwrap();
+        let result = nbt_tag_string(&mut cursor, &nbt::ParseOptions::default()).unwrap();
         assert_eq!(result, "Te");
     }
 
@@ -241,29 +511,19 @@ mod tests {
         data.extend(&0i16.to_be_bytes()); // name_len = 0
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        let result = nbt_tag_string(&mut cursor).unwrap();
+        let result = nbt_tag_string(&mut cursor, &nbt::ParseOptions::default()).unwrap();
         assert_eq!(result, "");
     }
 
     #[test]
-    fn test_nbt_tag_string_negative_length() -> Result<(), nbt::NbtReadError> {
+    fn test_nbt_tag_string_length_is_unsigned() {
         let mut data = Vec::new();
-        data.extend(&(-1i16).to_be_bytes()); // name_len = -1
-                                             // Since the loop is 0..-1, which is invalid, it will not execute
-                                             // The string will have capacity of usize::MAX, but we are not using it
-                                             // However, converting -1i16 to usize can cause unexpected behavior
-                                             // To prevent this, let's see how the code handles it
-                                             // In Rust, 0..-1i16 will not compile, so likely it's cast to usize
-                                             // Here, assuming it's cast as 0..(name_len as usize)
-                                             // name_len as usize for -1i16 is 65535
-                                             // To avoid creating a large string, let's limit the test
-                                             // Instead, we can check if the function handles it gracefully
-                                             // But as per the original code, it doesn't handle negative lengths
-                                             // So it will try to read 65535 bytes, which is not present, leading to an error
-        let cursor = make_cursor(data);
-        let mut cursor = cursor;
-        assert!(nbt_tag_string(&mut cursor).is_err());
-        Ok(())
+        data.extend(&0xFFFFu16.to_be_bytes()); // name_len = 65535, not -1
+        data.extend(vec![b'a'; 0xFFFF]);
+        let mut cursor = make_cursor(data);
+        let result = nbt_tag_string(&mut cursor, &nbt::ParseOptions::default()).unwrap();
+        assert_eq!(result.len(), 0xFFFF);
+        assert_eq!(cursor.position(), 2 + 0xFFFF);
     }
 
     #[test]
@@ -273,7 +533,7 @@ mod tests {
         data.extend(&vec![b'T', b'e']); // Only 2 bytes instead of 4
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        let result = nbt_tag_string(&mut cursor);
+        let result = nbt_tag_string(&mut cursor, &nbt::ParseOptions::default());
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -282,7 +542,12 @@ mod tests {
     fn test_nbt_tag_end() {
         let cursor = make_cursor(vec![]); // No data needed for End
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::End).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::End,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::End(None));
     }
 
@@ -290,7 +555,12 @@ mod tests {
     fn test_nbt_tag_byte() {
         let cursor = make_cursor(vec![0x7F]); // i8::MAX
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Byte).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Byte,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::Byte(127));
     }
 
@@ -298,7 +568,11 @@ mod tests {
     fn test_nbt_tag_byte_io_error() {
         let cursor = make_cursor(vec![]); // No data
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Byte);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Byte,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -308,7 +582,12 @@ mod tests {
         let cursor = make_cursor(data);
         // i16::from_be_bytes([0x00, 0x2A]) = 42
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Short).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Short,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::Short(42));
     }
 
@@ -316,7 +595,11 @@ mod tests {
     fn test_nbt_tag_short_io_error() {
         let cursor = make_cursor(vec![0x00]); // Incomplete i16
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Short);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Short,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -325,7 +608,12 @@ mod tests {
         let data = vec![0u8, 0u8, 0u8, 42u8];
         let cursor = make_cursor(data); // 42
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Int).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Int,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::Int(42));
     }
 
@@ -333,7 +621,11 @@ mod tests {
     fn test_nbt_tag_int_io_error() {
         let cursor = make_cursor(vec![0x00, 0x00, 0x00]); // Incomplete i32
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Int);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Int,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -342,7 +634,12 @@ mod tests {
         let data = vec![0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 42u8];
         let cursor = make_cursor(data); // 42
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Long).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Long,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::Long(42));
     }
 
@@ -350,7 +647,11 @@ mod tests {
     fn test_nbt_tag_long_io_error() {
         let cursor = make_cursor(vec![0x00; 7]); // Incomplete i64
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Long);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Long,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -358,7 +659,12 @@ mod tests {
     fn test_nbt_tag_float() {
         let cursor = make_cursor(42f32.to_be_bytes().to_vec());
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Float).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Float,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::Float(42f32));
     }
 
@@ -366,7 +672,11 @@ mod tests {
     fn test_nbt_tag_float_io_error() {
         let cursor = make_cursor(vec![0x00, 0x00, 0x00]); // Incomplete f32
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Float);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Float,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -374,7 +684,12 @@ mod tests {
     fn test_nbt_tag_double() {
         let cursor = make_cursor(42f64.to_be_bytes().to_vec());
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Double).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Double,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::Double(42f64));
     }
 
@@ -382,20 +697,29 @@ mod tests {
     fn test_nbt_tag_double_io_error() {
         let cursor = make_cursor(vec![0x00; 7]); // Incomplete f64
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Double);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Double,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
     #[test]
     fn test_nbt_tag_byte_array() {
         let len = 3i32.to_be_bytes();
-        let data = vec![1i8, 2i8, 3i8];
+        let data = [1i8, 2i8, 3i8];
         let mut combined = Vec::new();
         combined.extend(&len);
         combined.extend(&data.iter().map(|x| *x as u8).collect::<Vec<u8>>());
         let cursor = make_cursor(combined);
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::ByteArray).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::ByteArray,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::ByteArray(vec![1, 2, 3]));
     }
 
@@ -403,20 +727,28 @@ mod tests {
     fn test_nbt_tag_byte_array_io_error_length() {
         let cursor = make_cursor(vec![0x00, 0x00]); // Incomplete i32 for length
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::ByteArray);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::ByteArray,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
     #[test]
     fn test_nbt_tag_byte_array_io_error_data() {
         let len = 5i32.to_be_bytes();
-        let data = vec![1i8, 2i8]; // Only 2 bytes instead of 5
+        let data = [1i8, 2i8]; // Only 2 bytes instead of 5
         let mut combined = Vec::new();
         combined.extend(&len);
         combined.extend(&data.iter().map(|x| *x as u8).collect::<Vec<u8>>());
         let cursor = make_cursor(combined);
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::ByteArray);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::ByteArray,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -428,7 +760,12 @@ mod tests {
         data.extend(&b"Test".to_vec());
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::String).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::String,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::String("Test".to_string()));
     }
 
@@ -440,7 +777,11 @@ mod tests {
         data.extend(&b"Tes".to_vec());
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::String);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::String,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -452,10 +793,82 @@ mod tests {
         data.extend(&2i32.to_be_bytes()); // length = 2
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::List).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::List,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::List((nbt::NbtTagId::Byte, 2)));
     }
 
+    #[test]
+    fn test_nbt_tag_list_of_end_tags() {
+        // List tag: tag_id = 0 (End), length = 0 is the usual empty list
+        let mut data = Vec::new();
+        data.push(0u8);
+        data.extend(&0i32.to_be_bytes());
+        let mut cursor = make_cursor(data);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::List,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result, nbt::NbtTagType::List((nbt::NbtTagId::End, 0)));
+
+        // a non-empty list of End tags is rejected
+        let mut data = Vec::new();
+        data.push(0u8);
+        data.extend(&10_000_000i32.to_be_bytes());
+        let mut cursor = make_cursor(data);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::List,
+            &nbt::ParseOptions::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(nbt::NbtReadError::InvalidNbtListLenght)
+        ));
+    }
+
+    #[test]
+    fn test_min_nbt_tag_payload_size() {
+        let options = nbt::ParseOptions::default();
+        let mut cursor = make_cursor(Vec::new());
+        assert_eq!(
+            min_nbt_tag_payload_size(&mut cursor, &nbt::NbtTagId::Long, &options).unwrap(),
+            8
+        );
+
+        // only the length prefix is read
+        let mut cursor = make_cursor(300i32.to_be_bytes().to_vec());
+        assert_eq!(
+            min_nbt_tag_payload_size(&mut cursor, &nbt::NbtTagId::IntArray, &options).unwrap(),
+            1200
+        );
+        assert_eq!(cursor.position(), 4);
+
+        // network VarInts take at least one byte each
+        let mut cursor = make_cursor(vec![0x02]);
+        assert_eq!(
+            min_nbt_tag_payload_size(
+                &mut cursor,
+                &nbt::NbtTagId::LongArray,
+                &nbt::ParseOptions::bedrock_network()
+            )
+            .unwrap(),
+            1
+        );
+
+        let mut cursor = make_cursor((-1i32).to_be_bytes().to_vec());
+        assert!(matches!(
+            min_nbt_tag_payload_size(&mut cursor, &nbt::NbtTagId::ByteArray, &options),
+            Err(nbt::NbtReadError::NegativeNbtTagLenght)
+        ));
+    }
+
     #[test]
     fn test_nbt_tag_list_invalid_tag_id() -> Result<(), nbt::NbtReadError> {
         // List tag: invalid element tag_id
@@ -464,7 +877,12 @@ mod tests {
         data.extend(&2i32.to_be_bytes()); // length = 2
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        assert!(nbt_tag(&mut cursor, &nbt::NbtTagId::List).is_err());
+        assert!(nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::List,
+            &nbt::ParseOptions::default()
+        )
+        .is_err());
 
         Ok(())
     }
@@ -477,7 +895,11 @@ mod tests {
         data.extend(&2i32.to_be_bytes()); // length = 2
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::List);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::List,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -496,7 +918,22 @@ mod tests {
 
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        assert!(nbt_tag(&mut cursor, &nbt::NbtTagId::List).is_err());
+        assert!(matches!(
+            nbt_tag(
+                &mut cursor,
+                &nbt::NbtTagId::List,
+                &nbt::ParseOptions::strict()
+            ),
+            Err(nbt::NbtReadError::InvalidNbtListLenght)
+        ));
+
+        // the default limits accept any list length
+        cursor.set_position(0);
+        nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::List,
+            &nbt::ParseOptions::default(),
+        )?;
 
         Ok(())
     }
@@ -517,7 +954,7 @@ mod tests {
 
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        assert!(nbt_tag(&mut cursor, &nbt::NbtTagId::List).is_err());
+        assert!(nbt_tag(&mut cursor, &nbt::NbtTagId::List, &nbt::ParseOptions::default()).is_err());
 
         Ok(())
     } */
@@ -526,7 +963,12 @@ mod tests {
     fn test_nbt_tag_compound() {
         let cursor = make_cursor(vec![]); // No data needed
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::Compound).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Compound,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::Compound("".to_string()));
     }
 
@@ -540,7 +982,12 @@ mod tests {
         data.extend(&3i32.to_be_bytes());
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::IntArray).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::IntArray,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::IntArray(vec![1, 2, 3]));
     }
 
@@ -556,7 +1003,7 @@ mod tests {
         // Use the `should_panic` attribute
         // But Rust's #[should_panic] cannot be used here, so we need to use std::panic::catch_unwind
         let result = std::panic::catch_unwind(|| {
-            nbt_tag(&mut cursor, &nbt::NbtTagId::IntArray).unwrap();
+            nbt_tag(&mut cursor, &nbt::NbtTagId::IntArray, &nbt::ParseOptions::default()).unwrap();
         });
         assert!(result.is_err());
     } */
@@ -565,7 +1012,11 @@ mod tests {
     fn test_nbt_tag_int_array_io_error_length() {
         let cursor = make_cursor(vec![0x00, 0x00, 0x00]); // Incomplete i32 for length
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::IntArray);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::IntArray,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -579,7 +1030,11 @@ mod tests {
         // Missing the third integer
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::IntArray);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::IntArray,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -592,7 +1047,12 @@ mod tests {
         data.extend(&2i64.to_be_bytes());
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::LongArray).unwrap();
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::LongArray,
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
         assert_eq!(result, nbt::NbtTagType::LongArray(vec![1, 2]));
     }
 
@@ -603,7 +1063,7 @@ mod tests {
         let cursor = make_cursor(data);
         let mut cursor = cursor;
         let result = std::panic::catch_unwind(|| {
-            nbt_tag(&mut cursor, &nbt::NbtTagId::LongArray).unwrap();
+            nbt_tag(&mut cursor, &nbt::NbtTagId::LongArray, &nbt::ParseOptions::default()).unwrap();
         });
         assert!(result.is_err());
     } */
@@ -612,7 +1072,11 @@ mod tests {
     fn test_nbt_tag_long_array_io_error_length() {
         let cursor = make_cursor(vec![0x00, 0x00, 0x00]); // Incomplete i32 for length
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::LongArray);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::LongArray,
+            &nbt::ParseOptions::default(),
+        );
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
     }
 
@@ -625,7 +1089,82 @@ mod tests {
         // Missing the second long
         let cursor = make_cursor(data);
         let mut cursor = cursor;
-        let result = nbt_tag(&mut cursor, &nbt::NbtTagId::LongArray);
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::LongArray,
+            &nbt::ParseOptions::default(),
+        );
+        assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
+    }
+
+    #[test]
+    fn test_nbt_tag_network_varints() {
+        // zig-zag: 0x03 => -2, 0xAC 0x02 => 300 => 150
+        let cursor = make_cursor(vec![0x03, 0xAC, 0x02]);
+        let mut cursor = cursor;
+        let options = nbt::ParseOptions::bedrock_network();
+        assert_eq!(
+            nbt_tag(&mut cursor, &nbt::NbtTagId::Int, &options).unwrap(),
+            nbt::NbtTagType::Int(-2)
+        );
+        assert_eq!(cursor.position(), 1);
+        assert_eq!(
+            nbt_tag(&mut cursor, &nbt::NbtTagId::Long, &options).unwrap(),
+            nbt::NbtTagType::Long(150)
+        );
+        assert_eq!(cursor.position(), 3);
+    }
+
+    #[test]
+    fn test_nbt_tag_string_network_length() {
+        let cursor = make_cursor(vec![0x02, b'h', b'i']);
+        let mut cursor = cursor;
+        let result = nbt_tag_string(&mut cursor, &nbt::ParseOptions::bedrock_network());
+        assert_eq!(result.unwrap(), "hi");
+    }
+
+    #[test]
+    fn test_nbt_tag_network_varint_too_long() {
+        let cursor = make_cursor(vec![0xFF; 6]);
+        let mut cursor = cursor;
+        let result = nbt_tag(
+            &mut cursor,
+            &nbt::NbtTagId::Int,
+            &nbt::ParseOptions::bedrock_network(),
+        );
+        assert!(matches!(result, Err(nbt::NbtReadError::InvalidVarInt)));
+    }
+
+    #[test]
+    fn test_skip_nbt_tag_payload() {
+        let options = nbt::ParseOptions::default();
+
+        let mut data = 3i32.to_be_bytes().to_vec();
+        data.extend(&[0x00; 12]);
+        data.push(0x7F);
+        let mut cursor = make_cursor(data);
+        skip_nbt_tag_payload(&mut cursor, &nbt::NbtTagId::IntArray, &options).unwrap();
+        assert_eq!(cursor.position(), 16);
+
+        // the payload must be in the buffer, even if it is not decoded
+        let mut cursor = make_cursor(vec![0x00, 0x05, b'a']);
+        let result = skip_nbt_tag_payload(&mut cursor, &nbt::NbtTagId::String, &options);
         assert!(matches!(result, Err(nbt::NbtReadError::Io(_))));
+
+        let mut cursor = make_cursor((-1i32).to_be_bytes().to_vec());
+        let result = skip_nbt_tag_payload(&mut cursor, &nbt::NbtTagId::LongArray, &options);
+        assert!(matches!(
+            result,
+            Err(nbt::NbtReadError::NegativeNbtTagLenght)
+        ));
+    }
+
+    #[test]
+    fn test_skip_network_int_array() {
+        let options = nbt::ParseOptions::bedrock_network();
+        // 2 elements as zig-zag VarInts: 300 takes 2 bytes, 1 takes 1 byte
+        let mut cursor = make_cursor(vec![0x04, 0xD8, 0x04, 0x02, 0xFF]);
+        skip_nbt_tag_payload(&mut cursor, &nbt::NbtTagId::IntArray, &options).unwrap();
+        assert_eq!(cursor.position(), 4);
     }
 }
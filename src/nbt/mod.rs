@@ -1,16 +1,41 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io;
-use std::io::{Cursor, Seek, SeekFrom};
+use std::io::Cursor;
 use thiserror::Error;
 
+mod builder;
+mod de;
+mod edit;
+mod events;
 mod fsm;
+mod mutf8;
+mod ser;
+mod value;
+mod write;
 
-const MAX_LIST_LENGTH: i32 = 32767;
-const MAX_BYTE_ARRAY_LENGTH: i32 = 32767;
-const MAX_INT_ARRAY_LENGTH: i32 = 32767;
-const MAX_LONG_ARRAY_LENGTH: i32 = 32767;
+pub use builder::{NbtCompoundBuilder, NbtListBuilder};
+pub use de::{from_bytes, from_bytes_with_options};
+pub use events::{NbtEvent, NbtEventReader, NbtPushParser, NbtPushStatus};
+pub use fsm::ParseNbtFsmState;
+pub use ser::{
+    byte_array, int_array, long_array, to_bytes, to_bytes_with_options, NbtByteArray, NbtIntArray,
+    NbtLongArray,
+};
+pub use value::{NbtCompound, NbtList, NbtListElement, NbtValue};
+
+use events::TagEvent;
+
+const VANILLA_MAX_DEPTH: i64 = 512;
+const STRICT_MAX_LENGTH: i32 = 32767;
+const STRICT_MAX_ALLOCATION: usize = 2 * 1024 * 1024;
+
+const BEDROCK_HEADER_SIZE: usize = 8;
 
+#[cfg(test)]
+mod test_utils;
 #[cfg(test)]
 mod tests;
 
@@ -26,28 +51,227 @@ pub enum NbtReadError {
     InvalidNbtDepth, // Custom error for tag id validation
 
     #[error("Invalid NBT List lenght")]
-    InvalidNbtListLenght, // if list is longer than MAX_LIST_LENGTH
+    InvalidNbtListLenght, // if list is longer than ParseOptions::max_list_length
 
     #[error("NBT List longer than declared")]
     NbtListLongerThanDeclared, // if list is longer than the size read in the NBT file
 
     #[error("Invalid NBT ByteArray lenght")]
-    InvalidNbtByteArrayLenght, // if array is longer than MAX_BYTE_ARRAY_LENGTH
+    InvalidNbtByteArrayLenght, // if array is longer than ParseOptions::max_byte_array_length
 
     #[error("Invalid NBT IntArray lenght")]
-    InvalidNbtIntArrayLenght, // if array is longer than MAX_BYTE_ARRAY_LENGTH
+    InvalidNbtIntArrayLenght, // if array is longer than ParseOptions::max_int_array_length
 
     #[error("Invalid NBT LongArray lenght")]
-    InvalidNbtLongArrayLenght, // if array is longer than MAX_BYTE_ARRAY_LENGTH
+    InvalidNbtLongArrayLenght, // if array is longer than ParseOptions::max_long_array_length
 
-    #[error("Invalid NBT Root Tag Id: NbtFile must start with a compound tag")]
-    InvalidNbtRootTagId, // if root tag is not a compound
+    #[error("Invalid NBT Root Tag Id: the root tag is not a compound, set ParseOptions::any_root_tag to accept other root types")]
+    InvalidNbtRootTagId, // if root tag is not a compound and any_root_tag is not set
 
     #[error("Negative NBT Tag Length")]
     NegativeNbtTagLenght, // if tag length is negative
 
     #[error("NBT file is empty")]
     EmptyFile, // if file is empty
+
+    #[error("NBT Tree deeper than the maximum depth {0}")]
+    MaxDepthExceeded(i64), // if the tree is deeper than ParseOptions::max_depth
+
+    #[error("NBT Tree needs more memory than the allocation budget of {0} bytes")]
+    AllocationBudgetExceeded(usize), // if the parsed tree would take more than ParseOptions::max_allocation
+
+    #[error("Invalid VarInt: too many bytes")]
+    InvalidVarInt, // if a network NBT VarInt does not terminate within its maximum length
+
+    #[error("{0}")]
+    Custom(String), // if the NBT does not match the type deserialized with serde
+
+    #[error("NBT cannot be converted to NbtValue without losing its {0}")]
+    UnrepresentableValue(String), // if NbtData::to_value would drop a part of the NBT
+
+    #[error("{source} at {context}")]
+    WithContext {
+        source: Box<NbtReadError>,
+        context: Box<NbtReadErrorContext>,
+    }, // any of the errors above, with the position in the NbtTag tree where it happened
+}
+
+#[derive(Error, Debug)]
+pub enum NbtWriteError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error), // Automatically convert `io::Error` to `NbtWriteError`
+
+    #[error("NBT String is longer than 65535 bytes: {0} bytes")]
+    StringTooLong(usize), // if a string or a tag name does not fit in the u16 length prefix
+
+    #[error("NBT String contains characters which cannot be encoded: {0:?}")]
+    UnencodableString(String), // if a string or a tag name cannot be converted back to raw bytes
+
+    #[error("NBT Array is longer than 2147483647 elements: {0} elements")]
+    ArrayTooLong(usize), // if an array does not fit in the i32 length prefix
+
+    #[error("Invalid NBT tree: {0}")]
+    InvalidNbtTree(String), // if the NbtTag tree links are not consistent
+
+    #[error("{0}")]
+    Custom(String), // if a value serialized with serde has no NBT representation
+}
+
+#[derive(Error, Debug)]
+pub enum NbtEditError {
+    #[error("No NBT Tag at index {0}")]
+    InvalidIndex(usize), // if an index is out of the NbtTag tree
+
+    #[error("NBT Tag {0} is neither a compound nor a list")]
+    NotAContainer(usize), // if a child is inserted or moved into a tag which cannot have children
+
+    #[error("Invalid child position {0}: the parent has {1} children")]
+    InvalidPosition(usize, usize), // if a child is inserted or moved after the end of its parent
+
+    #[error("NBT End Tags are added and removed with their compound")]
+    EndTag, // if an End tag is inserted, edited or removed on its own
+
+    #[error("NBT Compound already has a child named {0:?}")]
+    DuplicateName(String), // if two children of a compound would have the same name
+
+    #[error("NBT List elements are {expected:?}, not {found:?}")]
+    ListTypeMismatch { expected: NbtTagId, found: NbtTagId }, // if a list would not be homogeneous anymore
+
+    #[error("NBT Tag {0} is an element of a list and has no name")]
+    UnnamedListElement(usize), // if an element of a list is renamed
+
+    #[error("NBT Tag {0} cannot be moved into itself or its children")]
+    MoveIntoItself(usize), // if a tag is moved into its own subtree
+
+    #[error("NBT Tag value is {expected:?}, not {found:?}")]
+    ValueTypeMismatch { expected: NbtTagId, found: NbtTagId }, // if a patched value has another type
+
+    #[error("NBT Tag {0} has no value bytes which can be patched")]
+    UnpatchableValue(usize), // if a compound, a list or an inserted tag is patched
+
+    #[error("Patched NBT value takes {found} bytes instead of {expected}")]
+    ValueWidthMismatch { expected: usize, found: usize }, // if a patched value does not fit in the original bytes
+
+    #[error("NBT write error: {0}")]
+    Write(#[from] NbtWriteError), // if a patched value cannot be encoded
+
+    #[error("NBT Lazy values only exist in the parsed raw bytes")]
+    LazyValue, // if a Lazy value is inserted or set: it has no bytes to be decoded from
+
+    #[error(
+        "NBT Tag {0} is a root and must stay a compound, unless ParseOptions::any_root_tag is set"
+    )]
+    NonCompoundRoot(usize), // if a root compound would be written as a tag which cannot be read back
+}
+
+impl NbtReadError {
+    /// The underlying error, without the parsing context
+    pub fn kind(&self) -> &NbtReadError {
+        match self {
+            NbtReadError::WithContext { source, .. } => source.kind(),
+            _ => self,
+        }
+    }
+
+    /// Where in the raw bytes and in the NbtTag tree the error happened, if known
+    pub fn context(&self) -> Option<&NbtReadErrorContext> {
+        match self {
+            NbtReadError::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
+pub struct NbtReadErrorContext {
+    offset: usize,
+    tag_start: usize,
+    state: ParseNbtFsmState,
+    depth: i64,
+    path: Vec<String>,
+}
+
+impl NbtReadErrorContext {
+    /// Cursor position in the raw bytes when the error happened
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// First byte of the NbtTag which was being parsed
+    pub fn tag_start(&self) -> usize {
+        self.tag_start
+    }
+
+    pub fn state(&self) -> ParseNbtFsmState {
+        self.state
+    }
+
+    pub fn depth(&self) -> i64 {
+        self.depth
+    }
+
+    /// Names of the parent NbtTags, starting from the root
+    pub fn path(&self) -> &Vec<String> {
+        &self.path
+    }
+}
+
+impl std::fmt::Display for NbtReadErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "byte {} (tag starting at byte {}, state {:?}, depth {}, path \"{}\")",
+            self.offset,
+            self.tag_start,
+            self.state,
+            self.depth,
+            self.path.join("/")
+        )
+    }
+}
+
+/// A parsing error skipped in recovery mode, see [`ParseOptions::recover_errors`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
+pub struct NbtParseDiagnostic {
+    message: String,
+    context: NbtReadErrorContext,
+    resync_offset: Option<usize>,
+}
+
+impl NbtParseDiagnostic {
+    fn new(error: &NbtReadError, resync_offset: Option<usize>) -> NbtParseDiagnostic {
+        NbtParseDiagnostic {
+            message: error.kind().to_string(),
+            context: error.context().cloned().unwrap_or_default(),
+            resync_offset,
+        }
+    }
+
+    /// Why parsing failed
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Where parsing failed
+    pub fn context(&self) -> &NbtReadErrorContext {
+        &self.context
+    }
+
+    /// First byte of the tag header where parsing resumed, None if parsing stopped here.
+    /// The bytes from the start of the failed tag up to this offset are not part of any NbtTag
+    pub fn resync_offset(&self) -> Option<usize> {
+        self.resync_offset
+    }
+}
+
+impl std::fmt::Display for NbtParseDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, self.context)?;
+        match self.resync_offset {
+            Some(offset) => write!(f, ", resumed at byte {}", offset),
+            None => write!(f, ", parsing stopped"),
+        }
+    }
 }
 
 #[derive(
@@ -128,6 +352,7 @@ pub enum NbtTagType {
     Compound(String),      //only store the name of the compound
     IntArray(Vec<i32>),
     LongArray(Vec<i64>),
+    Lazy(NbtTagId), //value not decoded because of ParseOptions::lazy_values, NbtData::tag_value decodes it
 }
 
 impl Default for NbtTagType {
@@ -152,6 +377,7 @@ impl NbtTagType {
             NbtTagType::Compound(_) => NbtTagId::Compound,
             NbtTagType::IntArray(_) => NbtTagId::IntArray,
             NbtTagType::LongArray(_) => NbtTagId::LongArray,
+            NbtTagType::Lazy(tag_id) => *tag_id,
         }
     }
 }
@@ -394,6 +620,8 @@ pub struct NbtTag {
     name: String,
     value: NbtTagType,
     position: NbtTagPosition,
+    invalid_name: bool,
+    invalid_value: bool,
 }
 
 impl NbtTag {
@@ -407,6 +635,7 @@ impl NbtTag {
 
     pub fn set_value(&mut self, value: NbtTagType) {
         self.value = value;
+        self.invalid_value = false;
     }
 
     pub fn name(&self) -> &str {
@@ -415,6 +644,24 @@ impl NbtTag {
 
     pub fn set_name(&mut self, name: String) {
         self.name = name;
+        self.invalid_name = false;
+    }
+
+    /// True if the name was not valid MUTF-8 (UTF-8 for Bedrock) and was kept byte by byte:
+    /// every char of the name is one of the original bytes
+    pub fn has_invalid_name(&self) -> bool {
+        self.invalid_name
+    }
+
+    /// True if the value is a string which was not valid MUTF-8 (UTF-8 for Bedrock) and was kept byte by byte
+    pub fn has_invalid_value(&self) -> bool {
+        self.invalid_value
+    }
+
+    /// True if the value was not decoded because of [`ParseOptions::lazy_values`]:
+    /// [`NbtTag::value`] is [`NbtTagType::Lazy`], [`NbtData::tag_value`] decodes the real one
+    pub fn has_lazy_value(&self) -> bool {
+        matches!(self.value, NbtTagType::Lazy(_))
     }
 
     pub fn position(&self) -> &NbtTagPosition {
@@ -434,34 +681,399 @@ impl NbtTag {
     }
 }
 
+/// Byte order of the numbers and length prefixes in the NBT payload
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
+pub enum NbtEncoding {
+    #[default]
+    BigEndian, // Java Edition
+    LittleEndian, // Bedrock Edition files: level.dat, .mcstructure, LevelDB values
+    NetworkLittleEndian, // Bedrock Edition network protocol: zig-zag VarInt ints, longs and lengths
+}
+
+/// Whether the root compound carries a name
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
+pub enum RootMode {
+    #[default]
+    Named,
+    Nameless, // Java Edition network protocol since 1.20.2 (protocol 764)
+}
+
+/// Options of the NBT parser: flavour of the binary format and limits on what is accepted.
+///
+/// The default limits accept every file written by vanilla Minecraft,
+/// use [`ParseOptions::strict`] for untrusted input
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ParseOptions {
+    encoding: NbtEncoding,
+    root_mode: RootMode,
+    any_root_tag: bool,
+    recover_errors: bool,
+    multiple_roots: bool,
+    lazy_values: bool,
+    max_list_length: i32,
+    max_byte_array_length: i32,
+    max_int_array_length: i32,
+    max_long_array_length: i32,
+    max_depth: i64,
+    max_allocation: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            encoding: NbtEncoding::default(),
+            root_mode: RootMode::default(),
+            any_root_tag: false,
+            recover_errors: false,
+            multiple_roots: false,
+            lazy_values: false,
+            max_list_length: i32::MAX,
+            max_byte_array_length: i32::MAX,
+            max_int_array_length: i32::MAX,
+            max_long_array_length: i32::MAX,
+            max_depth: VANILLA_MAX_DEPTH,
+            max_allocation: usize::MAX,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// Options for NBT files written by Bedrock Edition
+    pub fn bedrock() -> ParseOptions {
+        ParseOptions {
+            encoding: NbtEncoding::LittleEndian,
+            ..ParseOptions::default()
+        }
+    }
+
+    /// Options for NBT payloads sent over the Bedrock Edition network protocol
+    pub fn bedrock_network() -> ParseOptions {
+        ParseOptions {
+            encoding: NbtEncoding::NetworkLittleEndian,
+            ..ParseOptions::default()
+        }
+    }
+
+    /// Options for NBT payloads sent over the Java Edition network protocol
+    pub fn java_network() -> ParseOptions {
+        ParseOptions {
+            root_mode: RootMode::Nameless,
+            ..ParseOptions::default()
+        }
+    }
+
+    /// Tight limits for NBT coming from untrusted sources, e.g. user uploads.
+    /// The allocation budget is the same 2 MiB quota vanilla servers apply to NBT received from clients
+    pub fn strict() -> ParseOptions {
+        ParseOptions {
+            max_list_length: STRICT_MAX_LENGTH,
+            max_byte_array_length: STRICT_MAX_LENGTH,
+            max_int_array_length: STRICT_MAX_LENGTH,
+            max_long_array_length: STRICT_MAX_LENGTH,
+            max_allocation: STRICT_MAX_ALLOCATION,
+            ..ParseOptions::default()
+        }
+    }
+
+    pub fn encoding(&self) -> NbtEncoding {
+        self.encoding
+    }
+
+    pub fn set_encoding(&mut self, encoding: NbtEncoding) {
+        self.encoding = encoding;
+    }
+
+    pub fn root_mode(&self) -> RootMode {
+        self.root_mode
+    }
+
+    pub fn set_root_mode(&mut self, root_mode: RootMode) {
+        self.root_mode = root_mode;
+    }
+
+    /// If true the root can be any tag, e.g. a String or a List, instead of only a Compound
+    pub fn any_root_tag(&self) -> bool {
+        self.any_root_tag
+    }
+
+    pub fn set_any_root_tag(&mut self, any_root_tag: bool) {
+        self.any_root_tag = any_root_tag;
+    }
+
+    /// If true, parsing errors do not fail the parse: the NbtTags parsed so far are kept, the error is
+    /// recorded in [`NbtData::diagnostics`] and parsing resumes at the next plausible tag header, if any
+    pub fn recover_errors(&self) -> bool {
+        self.recover_errors
+    }
+
+    pub fn set_recover_errors(&mut self, recover_errors: bool) {
+        self.recover_errors = recover_errors;
+    }
+
+    /// If true, the bytes after the end of the root are parsed as more root tags, one after the other,
+    /// as written by some mod save formats. Otherwise they are left as unparsed trailer
+    pub fn multiple_roots(&self) -> bool {
+        self.multiple_roots
+    }
+
+    pub fn set_multiple_roots(&mut self, multiple_roots: bool) {
+        self.multiple_roots = multiple_roots;
+    }
+
+    /// If true, the payloads of strings and arrays are not decoded while parsing: their NbtTag keeps
+    /// a [`NbtTagType::Lazy`] value and the position of the payload, use [`NbtData::tag_value`] to decode it on demand
+    pub fn lazy_values(&self) -> bool {
+        self.lazy_values
+    }
+
+    pub fn set_lazy_values(&mut self, lazy_values: bool) {
+        self.lazy_values = lazy_values;
+    }
+
+    pub fn max_list_length(&self) -> i32 {
+        self.max_list_length
+    }
+
+    pub fn set_max_list_length(&mut self, max_list_length: i32) {
+        self.max_list_length = max_list_length;
+    }
+
+    pub fn max_byte_array_length(&self) -> i32 {
+        self.max_byte_array_length
+    }
+
+    pub fn set_max_byte_array_length(&mut self, max_byte_array_length: i32) {
+        self.max_byte_array_length = max_byte_array_length;
+    }
+
+    pub fn max_int_array_length(&self) -> i32 {
+        self.max_int_array_length
+    }
+
+    pub fn set_max_int_array_length(&mut self, max_int_array_length: i32) {
+        self.max_int_array_length = max_int_array_length;
+    }
+
+    pub fn max_long_array_length(&self) -> i32 {
+        self.max_long_array_length
+    }
+
+    pub fn set_max_long_array_length(&mut self, max_long_array_length: i32) {
+        self.max_long_array_length = max_long_array_length;
+    }
+
+    /// Maximum depth of the NbtTag tree, the root compound has depth 0
+    pub fn max_depth(&self) -> i64 {
+        self.max_depth
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: i64) {
+        self.max_depth = max_depth;
+    }
+
+    /// Maximum number of bytes the parsed NbtTag tree may take in memory, including the values of
+    /// arrays and strings
+    pub fn max_allocation(&self) -> usize {
+        self.max_allocation
+    }
+
+    pub fn set_max_allocation(&mut self, max_allocation: usize) {
+        self.max_allocation = max_allocation;
+    }
+}
+
+/// 8 bytes header found before the root tag of Bedrock `level.dat` files
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
+pub struct BedrockHeader {
+    storage_version: i32,
+    length: i32,
+}
+
+impl BedrockHeader {
+    pub fn storage_version(&self) -> i32 {
+        self.storage_version
+    }
+
+    /// Length of the NBT payload following the header, as declared in the file
+    pub fn length(&self) -> i32 {
+        self.length
+    }
+
+    pub fn byte_start(&self) -> usize {
+        0
+    }
+
+    pub fn byte_end(&self) -> usize {
+        BEDROCK_HEADER_SIZE - 1
+    }
+
+    /// The header is only recognized if the declared length matches the rest of the buffer
+    fn detect(buffer: &[u8]) -> Option<BedrockHeader> {
+        if buffer.len() < BEDROCK_HEADER_SIZE {
+            return None;
+        }
+
+        let mut cursor = Cursor::new(&buffer[..BEDROCK_HEADER_SIZE]);
+        let storage_version = cursor.read_i32::<LittleEndian>().ok()?;
+        let length = cursor.read_i32::<LittleEndian>().ok()?;
+
+        if length >= 0 && length as usize == buffer.len() - BEDROCK_HEADER_SIZE {
+            Some(BedrockHeader {
+                storage_version,
+                length,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Bytes found after the end of the root tag, e.g. padding or garbage, which are not part of any NbtTag
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
+pub struct NbtTrailer {
+    byte_start: usize,
+    byte_end: usize,
+}
+
+impl NbtTrailer {
+    pub fn byte_start(&self) -> usize {
+        self.byte_start
+    }
+
+    pub fn byte_end(&self) -> usize {
+        self.byte_end
+    }
+}
+
+/// Inclusive range of bytes in [`NbtData::raw_bytes`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
+pub struct NbtByteRange {
+    byte_start: usize,
+    byte_end: usize,
+}
+
+impl NbtByteRange {
+    pub fn byte_start(&self) -> usize {
+        self.byte_start
+    }
+
+    pub fn byte_end(&self) -> usize {
+        self.byte_end
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.byte_start <= offset && offset <= self.byte_end
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct NbtData {
     tags: Vec<NbtTag>,
-    nbt_parser: fsm::NbtParser,
     raw_bytes: Vec<u8>,
     tags_map: HashMap<String, usize>,
+    options: ParseOptions,
+    bedrock_header: Option<BedrockHeader>,
+    diagnostics: Vec<NbtParseDiagnostic>,
+    trailer: Option<NbtTrailer>,
+    patched_ranges: Vec<NbtByteRange>,
 }
 
 impl NbtData {
     pub fn from_buf(file_buffer: Vec<u8>) -> Result<NbtData, NbtReadError> {
+        NbtData::from_buf_with_options(file_buffer, &ParseOptions::default())
+    }
+
+    pub fn from_buf_with_options(
+        file_buffer: Vec<u8>,
+        options: &ParseOptions,
+    ) -> Result<NbtData, NbtReadError> {
         if file_buffer.is_empty() {
             return Err(NbtReadError::EmptyFile);
         }
         let mut nbt_data = NbtData::new(file_buffer);
+        nbt_data.options = options.clone();
         nbt_data.parse()?;
 
         Ok(nbt_data)
     }
 
+    /// Parse NBT from a byte slice, e.g. a chunk of a region file or a network capture.
+    /// The bytes are copied once, since NbtData keeps the raw bytes the NbtTag positions refer to
+    pub fn from_slice(buffer: &[u8]) -> Result<NbtData, NbtReadError> {
+        NbtData::from_buf(buffer.to_vec())
+    }
+
+    pub fn from_slice_with_options(
+        buffer: &[u8],
+        options: &ParseOptions,
+    ) -> Result<NbtData, NbtReadError> {
+        NbtData::from_buf_with_options(buffer.to_vec(), options)
+    }
+
+    /// Parse uncompressed NBT read until the end of `reader`, e.g. stdin
+    pub fn from_reader<R: io::Read>(reader: R) -> Result<NbtData, NbtReadError> {
+        NbtData::from_reader_with_options(reader, &ParseOptions::default())
+    }
+
+    pub fn from_reader_with_options<R: io::Read>(
+        mut reader: R,
+        options: &ParseOptions,
+    ) -> Result<NbtData, NbtReadError> {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        NbtData::from_buf_with_options(buffer, options)
+    }
+
     pub fn new(file_buffer: Vec<u8>) -> NbtData {
         NbtData {
             tags: Vec::<NbtTag>::new(),
-            nbt_parser: fsm::NbtParser::new(),
             raw_bytes: file_buffer,
             tags_map: HashMap::new(),
+            options: ParseOptions::default(),
+            bedrock_header: None,
+            diagnostics: Vec::new(),
+            trailer: None,
+            patched_ranges: Vec::new(),
         }
     }
 
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    pub fn bedrock_header(&self) -> Option<&BedrockHeader> {
+        self.bedrock_header.as_ref()
+    }
+
+    /// Errors skipped while parsing in recovery mode, in the order they were found
+    pub fn diagnostics(&self) -> &Vec<NbtParseDiagnostic> {
+        &self.diagnostics
+    }
+
+    /// Bytes left after the end of the last parsed root, if any
+    pub fn trailer(&self) -> Option<&NbtTrailer> {
+        self.trailer.as_ref()
+    }
+
+    /// Indexes of the root tags: only the first NbtTag, unless [`ParseOptions::multiple_roots`] is set
+    pub fn roots(&self) -> Vec<usize> {
+        self.tags
+            .iter()
+            .enumerate()
+            .filter(|(index, tag)| tag.position().parent() == *index)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Bytes changed by [`NbtData::patch_value`], in the order they were patched
+    pub fn patched_ranges(&self) -> &Vec<NbtByteRange> {
+        &self.patched_ranges
+    }
+
     pub fn nbt_tags(&self) -> &Vec<NbtTag> {
         &self.tags
     }
@@ -474,360 +1086,343 @@ impl NbtData {
         &self.tags_map
     }
 
-    pub fn parse(&mut self) -> Result<(), NbtReadError> {
-        // #1 Initialize
-        // #11 Initialize NbtTag content
-        let mut new_tag_position = NbtTagPosition::new();
-        let mut new_nbt_tag = NbtTag {
-            name: "".to_string(),
-            value: NbtTagType::End(None),
-            position: new_tag_position.clone(),
-        };
-
-        // #12 Initialize auxiliary information for parsing and building the NbtTag tree
-        let mut cursor = Cursor::new(self.raw_bytes.clone());
-        let total_bytes = cursor.seek(SeekFrom::End(0)).unwrap() as usize;
-        let mut nbt_parent_index = 0;
-        let mut depth_delta = 0;
+    /// Serialize the NbtTag tree back to binary NBT.
+    ///
+    /// The tags are written in the order of the tree, so parsing a buffer and writing it back
+    /// without modifications gives the same bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, NbtWriteError> {
+        self.to_bytes_with_options(&self.options)
+    }
+
+    /// Serialize the NbtTag tree to binary NBT with a different encoding or root mode than the parsed one
+    ///
+    /// The Bedrock level.dat header only exists in front of little-endian NBT,
+    /// it is dropped when writing with another encoding
+    pub fn to_bytes_with_options(&self, options: &ParseOptions) -> Result<Vec<u8>, NbtWriteError> {
+        let mut buffer = Vec::with_capacity(self.raw_bytes.len());
+        let encoding = options.encoding();
+        let bedrock_header = self
+            .bedrock_header
+            .filter(|_| encoding == NbtEncoding::LittleEndian);
+
+        // the length in the header is only known once the whole payload is written
+        if let Some(bedrock_header) = bedrock_header {
+            buffer.write_i32::<LittleEndian>(bedrock_header.storage_version())?;
+            buffer.write_i32::<LittleEndian>(0)?;
+        }
 
-        cursor.seek(SeekFrom::Start(0)).unwrap();
-        loop {
-            // #2 Reinit the loop for a new NbtTag
-            // #21 set the current Nbt tree depth and update the parent index (who is the parent of the processed NbtTag)
-            nbt_parent_index = self.update_nbttree_depth(nbt_parent_index, depth_delta)?;
-
-            // #22 reset the NbtTag information and start parsing a new NbtTag
-            new_nbt_tag.set_name("".to_string());
-            new_nbt_tag.set_value(NbtTagType::End(None));
-            self.init_tag_position(&mut new_tag_position, &mut cursor);
-
-            // #3 Start parsing a new NbtTag
-            match self.nbt_parser.state() {
-                // #31  ParseFSM is in NbtRoot state: the first NbtTag of a NbtFile must always be a Root Compound Tag
-                //      in this state we parse a normal tag, but we exit if the root is not a compound
-                //      if the root is a compound we switch to Normal state
-                fsm::ParseNbtFsmState::NbtRoot => {
-                    depth_delta = self.parse_normal_state(
-                        &mut new_nbt_tag,
-                        &mut new_tag_position,
-                        nbt_parent_index,
-                        &mut cursor,
-                        true,
-                    )?;
-                    self.nbt_parser
-                        .change_state_to(fsm::ParseNbtFsmState::Normal);
+        for (index, nbt_tag) in self.tags.iter().enumerate() {
+            let parent_index = nbt_tag.position().parent();
+            let parent = match self.tags.get(parent_index) {
+                Some(parent) => parent,
+                None => {
+                    return Err(NbtWriteError::InvalidNbtTree(format!(
+                        "NbtTag {} has a non existing parent {}",
+                        index, parent_index
+                    )))
                 }
-                // #32 ParseFSM is in normal state: we are parsing any NbtTag that is NOT a List child
-                fsm::ParseNbtFsmState::Normal => {
-                    depth_delta = self.parse_normal_state(
-                        &mut new_nbt_tag,
-                        &mut new_tag_position,
-                        nbt_parent_index,
-                        &mut cursor,
-                        false,
-                    )?;
-                }
-                // #33 ParseFSM is in List state: NbtTag which are chidlrend ofLists NbtTags have no names and no values
-                fsm::ParseNbtFsmState::List => {
-                    depth_delta = self.parse_list_state(
-                        &mut new_nbt_tag,
-                        &mut new_tag_position,
-                        &mut cursor,
-                    )?;
-                }
-                // #34 ParseFSM is in EndOfFile: there are no more bytes to read
-                fsm::ParseNbtFsmState::EndOfFile => {
-                    break;
+            };
+
+            // the root has itself as parent
+            let is_root = parent_index == index;
+
+            // children of a list have neither id nor name, the list header defines their type
+            if !is_root {
+                if let NbtTagType::List(_) = parent.value() {
+                    self.write_tag_value(&mut buffer, nbt_tag, encoding)?;
+                    continue;
                 }
             }
-            // #4 Append the parsed NbtTag to the NbtTag tree and prepare for the next iteration
-            // #41 Update the NbtTag position in the NbtTag tree
-            self.update_tag_position(
-                &new_nbt_tag.value().into_id(),
-                &mut new_tag_position,
-                &mut cursor,
-                nbt_parent_index,
-            );
-            new_nbt_tag.set_position(new_tag_position.clone());
-
-            // #42 Append the parsed NbtTag to the NbtTag tree
-            self.append_nbt_tag(&new_nbt_tag, nbt_parent_index);
-            self.nbt_parser.increment_index();
-
-            // #43 check if we are at the end of the file or we need to proceed to the next loop
-            if new_nbt_tag.position().byte_end_all() >= (total_bytes - 1) {
-                self.nbt_parser
-                    .change_state_to(fsm::ParseNbtFsmState::EndOfFile);
-                break; //TODO Remove
+
+            write::nbt_tag_id(&mut buffer, &nbt_tag.value().into_id())?;
+            if let NbtTagType::End(_) = nbt_tag.value() {
+                continue;
             }
+            if !(is_root && options.root_mode() == RootMode::Nameless) {
+                if nbt_tag.has_invalid_name() {
+                    write::nbt_tag_string_raw(&mut buffer, nbt_tag.name(), encoding)?;
+                } else {
+                    write::nbt_tag_string(&mut buffer, nbt_tag.name(), encoding)?;
+                }
+            }
+            self.write_tag_value(&mut buffer, nbt_tag, encoding)?;
         }
 
-        Ok(())
-    }
-
-    fn update_tag_position(
-        &mut self,
-        tag_id: &NbtTagId,
-        new_tag_position: &mut NbtTagPosition,
-        cursor: &mut Cursor<Vec<u8>>,
-        nbt_parent_index: usize,
-    ) {
-        let new_end_all = cursor.position() as usize;
+        // the trailer is kept as it is, so that an unmodified NbtData is written back byte for byte
+        if let Some(trailer) = self.trailer {
+            buffer.extend(&self.raw_bytes[trailer.byte_start()..=trailer.byte_end()]);
+        }
 
-        // byte_end must always be greater or equal to byte_start
-        // in case of a list of compound, byte_end would be 1 less then byte_start if we do not check this
-        // compound tag only has 1 byte id, but list children do not have tag ids, since tag id is defined by the list itself
-        if new_end_all > new_tag_position.byte_start_all() {
-            new_tag_position.set_byte_end_all(new_end_all - 1);
-        } else {
-            new_tag_position.set_byte_end_all(new_end_all);
+        if bedrock_header.is_some() {
+            let length = (buffer.len() - BEDROCK_HEADER_SIZE) as i32;
+            buffer[4..BEDROCK_HEADER_SIZE].copy_from_slice(&length.to_le_bytes());
         }
-        new_tag_position.set_index(*self.nbt_parser.index());
-        new_tag_position.set_depth(*self.nbt_parser.tree_depth());
-        new_tag_position.set_parent(nbt_parent_index);
 
-        match tag_id {
-            NbtTagId::Compound | NbtTagId::List => {
-                // do nothing, byte_end_with_children will be set when a new child is appended
-            }
-            _ => {
-                //all the other tag ids cannot have children so we set byte_end_with_children to the same value as byte_end_all
-                new_tag_position.set_byte_end_all_with_children(new_tag_position.byte_end_all());
+        Ok(buffer)
+    }
+
+    // strings which could not be decoded are written back with their original bytes,
+    // and so are the values which were never decoded, as long as the encoding does not change
+    fn write_tag_value(
+        &self,
+        buffer: &mut Vec<u8>,
+        nbt_tag: &NbtTag,
+        encoding: NbtEncoding,
+    ) -> Result<(), NbtWriteError> {
+        if nbt_tag.has_lazy_value() {
+            let position = nbt_tag.position();
+            if let (true, Some(start), Some(end)) = (
+                encoding == self.options.encoding(),
+                position.byte_start_value(),
+                position.byte_end_value(),
+            ) {
+                buffer.extend(&self.raw_bytes[start..=end]);
+                return Ok(());
             }
+
+            let (value, is_valid) = self.decode_lazy_value(nbt_tag).map_err(|e| {
+                NbtWriteError::InvalidNbtTree(format!(
+                    "value of NbtTag {} cannot be decoded: {}",
+                    position.index(),
+                    e
+                ))
+            })?;
+            return match value {
+                NbtTagType::String(value) if !is_valid => {
+                    write::nbt_tag_string_raw(buffer, &value, encoding)
+                }
+                value => write::nbt_tag(buffer, &value, encoding),
+            };
         }
-    }
 
-    fn init_tag_position(
-        &mut self,
-        new_tag_position: &mut NbtTagPosition,
-        cursor: &mut Cursor<Vec<u8>>,
-    ) {
-        new_tag_position.reset();
-        new_tag_position.set_byte_start_all(cursor.position() as usize);
-        new_tag_position.set_byte_end_all(new_tag_position.byte_start_all());
+        match nbt_tag.value() {
+            NbtTagType::String(value) if nbt_tag.has_invalid_value() => {
+                write::nbt_tag_string_raw(buffer, value, encoding)
+            }
+            value => write::nbt_tag(buffer, value, encoding),
+        }
     }
 
-    fn append_nbt_tag(&mut self, nbt_tag: &NbtTag, nbt_parent_index: usize) {
-        let index = nbt_tag.position().index();
-        let name = nbt_tag.name().to_string();
+    /// Value of the NbtTag at `index`, decoded from the raw bytes if it was skipped because of
+    /// [`ParseOptions::lazy_values`]. Panics if `index` is out of bounds
+    pub fn tag_value(&self, index: usize) -> Result<Cow<'_, NbtTagType>, NbtReadError> {
+        let nbt_tag = &self.tags[index];
+        if !nbt_tag.has_lazy_value() {
+            return Ok(Cow::Borrowed(nbt_tag.value()));
+        }
 
-        self.tags.push(nbt_tag.clone());
-        self.tags_map.insert(name, index);
-        self.add_child_to_parent(&nbt_tag, nbt_parent_index);
+        let (value, _) = self.decode_lazy_value(nbt_tag)?;
+        Ok(Cow::Owned(value))
     }
 
-    fn parse_list_state(
-        &mut self,
-        new_nbt_tag: &mut NbtTag,
-        new_tag_position: &mut NbtTagPosition,
-        cursor: &mut Cursor<Vec<u8>>,
-    ) -> Result<i64, NbtReadError> {
-        let mut depth_delta = 0;
-        let tag_id = *self.nbt_parser.list_tag_id();
-        new_nbt_tag.set_name("".to_string());
-
-        new_tag_position.set_byte_start_value(cursor.position() as usize);
-        new_nbt_tag.set_value(fsm::parse::nbt_tag(cursor, &tag_id)?);
-        new_tag_position.set_byte_end_value(cursor.position() as usize);
-
-        if self.nbt_parser.is_list_end() {
-            self.nbt_parser
-                .change_state_to(fsm::ParseNbtFsmState::Normal);
-            self.nbt_parser.reset_list();
-
-            if let NbtTagId::Compound = tag_id {
-                depth_delta += 1;
-                // if we are in a list of compound, we need to exist the list parser and go back to normal
-                // the list is finished, so we do not need to store the list parser status
-                self.nbt_parser
-                    .change_state_to(fsm::ParseNbtFsmState::Normal);
-            } else {
-                depth_delta -= 1
-            }
-        } else {
-            if (self.nbt_parser.list_index() >= MAX_LIST_LENGTH - 1)
-                || (self.nbt_parser.list_index() >= self.nbt_parser.list_len() - 1)
-            {
-                return Err(NbtReadError::NbtListLongerThanDeclared);
+    /// Decode every value skipped because of [`ParseOptions::lazy_values`], e.g. before editing the tags
+    pub fn decode_values(&mut self) -> Result<(), NbtReadError> {
+        for index in 0..self.tags.len() {
+            if !self.tags[index].has_lazy_value() {
+                continue;
             }
-            self.nbt_parser.increment_list_index();
 
-            if let NbtTagId::Compound = tag_id {
-                depth_delta += 1;
-
-                // if we are in a list of compound, we need to exist the list parser and go back to normal
-                // but we also need to store the point in the list were we are
-                self.nbt_parser
-                    .change_state_to(fsm::ParseNbtFsmState::Normal);
-                self.nbt_parser.switch_list_ctx();
-            }
+            let (value, is_valid) = self.decode_lazy_value(&self.tags[index])?;
+            let nbt_tag = &mut self.tags[index];
+            nbt_tag.set_value(value);
+            nbt_tag.invalid_value = !is_valid;
         }
 
-        Ok(depth_delta)
+        Ok(())
     }
 
-    fn parse_normal_state(
-        &mut self,
-        new_nbt_tag: &mut NbtTag,
-        new_tag_position: &mut NbtTagPosition,
-        nbt_parent_index: usize,
-        cursor: &mut Cursor<Vec<u8>>,
-        error_if_not_tag_compound: bool,
-    ) -> Result<i64, NbtReadError> {
-        let mut depth_delta = 0;
+    fn decode_lazy_value(&self, nbt_tag: &NbtTag) -> Result<(NbtTagType, bool), NbtReadError> {
+        let byte_start = match nbt_tag.position().byte_start_value() {
+            Some(byte_start) if byte_start <= self.raw_bytes.len() => byte_start,
+            _ => return Err(NbtReadError::InvalidContent),
+        };
 
-        let tag_id = self.parse_nbt_tag_id(new_tag_position, cursor)?;
+        let mut cursor = Cursor::new(&self.raw_bytes[byte_start..]);
+        fsm::parse::nbt_tag_with_validity(&mut cursor, &nbt_tag.value().into_id(), &self.options)
+    }
 
-        if error_if_not_tag_compound && tag_id != NbtTagId::Compound {
-            return Err(NbtReadError::InvalidNbtRootTagId);
-        }
+    pub fn parse(&mut self) -> Result<(), NbtReadError> {
+        // the raw bytes are moved out while parsing, so that the event reader can borrow them
+        // while the NbtTag tree is built, instead of working on a copy
+        let raw_bytes = std::mem::take(&mut self.raw_bytes);
+        let result = self.parse_raw_bytes(&raw_bytes);
+        self.raw_bytes = raw_bytes;
 
-        if let NbtTagId::End = tag_id {
-            depth_delta = self.exit_nbttag_compound(nbt_parent_index);
-        } else {
-            self.parse_nbt_tag_name_and_value(new_nbt_tag, new_tag_position, tag_id, cursor)?;
+        result
+    }
 
-            if let NbtTagType::List(ref list_elem_tag_ids) = new_nbt_tag.value() {
-                self.nbt_parser.set_list_tag_id(list_elem_tag_ids.0);
-                self.nbt_parser.set_list_len(list_elem_tag_ids.1);
-                self.nbt_parser.change_state_to(fsm::ParseNbtFsmState::List);
-                depth_delta += 1;
-            }
+    fn parse_raw_bytes(&mut self, raw_bytes: &[u8]) -> Result<(), NbtReadError> {
+        // #1 Initialize the event reader, it skips the header of Bedrock level.dat files
+        let mut reader = NbtEventReader::new(raw_bytes, &self.options);
+        self.bedrock_header = reader.bedrock_header().copied();
+        let mut allocated: usize = 0;
+
+        loop {
+            let result = match reader.next_tag_event() {
+                // #2 Append every NbtTag read to the NbtTag tree, lists have no closing NbtTag
+                Some(Ok(TagEvent::Tag(nbt_tag))) => {
+                    self.append_nbt_tag(nbt_tag, &reader, &mut allocated)
+                }
+                Some(Ok(TagEvent::ListEnd)) => Ok(()),
+                Some(Err(error)) => Err(error),
+                // #3 The root is closed: read the next root, or keep the rest of the bytes as trailer
+                None => {
+                    let byte_start = reader.offset();
+                    if byte_start >= raw_bytes.len() {
+                        break;
+                    }
+                    if self.options.multiple_roots() {
+                        reader.start_next_root();
+                        continue;
+                    }
+                    self.trailer = Some(NbtTrailer {
+                        byte_start,
+                        byte_end: raw_bytes.len() - 1,
+                    });
+                    break;
+                }
+            };
 
-            if let NbtTagId::Compound = tag_id {
-                depth_delta += 1;
+            // #4 Recovery mode: keep the NbtTags parsed so far and resume at the next plausible tag header
+            if let Err(error) = result {
+                if !self.options.recover_errors() {
+                    return Err(error);
+                }
+                let resync_offset = reader.resync(&error);
+                self.diagnostics
+                    .push(NbtParseDiagnostic::new(&error, resync_offset));
+                if resync_offset.is_none() {
+                    break;
+                }
             }
         }
 
-        Ok(depth_delta)
-    }
-
-    fn parse_nbt_tag_id(
-        &mut self,
-        new_tag_position: &mut NbtTagPosition,
-        cursor: &mut Cursor<Vec<u8>>,
-    ) -> Result<NbtTagId, NbtReadError> {
-        new_tag_position.set_byte_start_id(cursor.position() as usize);
-        let tag_id = match fsm::parse::nbt_tag_id(cursor) {
-            Ok(id) => match id {
-                Some(id) => id,
-                None => return Err(NbtReadError::InvalidContent),
-            },
-            Err(e) => return Err(e),
-        };
-        new_tag_position.set_byte_end_id((cursor.position() - 1) as usize);
-
-        Ok(tag_id)
+        Ok(())
     }
 
-    fn parse_nbt_tag_name_and_value(
+    // the NbtTag is appended only if it fits in the allocation budget
+    fn append_nbt_tag(
         &mut self,
-        new_nbt_tag: &mut NbtTag,
-        new_tag_position: &mut NbtTagPosition,
-        tag_id: NbtTagId,
-        cursor: &mut Cursor<Vec<u8>>,
+        nbt_tag: NbtTag,
+        reader: &NbtEventReader<&[u8]>,
+        allocated: &mut usize,
     ) -> Result<(), NbtReadError> {
-        //parse NbtTag Name
-        new_tag_position.set_byte_start_name(cursor.position() as usize);
-        new_nbt_tag.set_name(fsm::parse::nbt_tag_string(cursor)?);
-        new_tag_position.set_byte_end_name((cursor.position() - 1) as usize);
+        *allocated = allocated.saturating_add(Self::allocation_size(&nbt_tag));
+        if *allocated > self.options.max_allocation() {
+            return Err(self.error_with_context(
+                NbtReadError::AllocationBudgetExceeded(self.options.max_allocation()),
+                &nbt_tag,
+                reader,
+            ));
+        }
 
-        //parse NbtTag Value
-        new_tag_position.set_byte_start_value(cursor.position() as usize);
-        new_nbt_tag.set_value(fsm::parse::nbt_tag(cursor, &tag_id)?);
-        new_tag_position.set_byte_end_value((cursor.position() - 1) as usize);
+        let index = nbt_tag.position().index();
+        let parent_index = nbt_tag.position().parent();
+        let byte_end = nbt_tag.position().byte_end_all();
+
+        self.tags_map.insert(nbt_tag.name().to_string(), index);
+        self.tags.push(nbt_tag);
+        self.add_child_to_parent(index, byte_end, parent_index);
 
         Ok(())
     }
 
-    fn update_nbttree_depth(
-        &mut self,
-        nbt_parent_index: usize,
-        depth_delta: i64,
-    ) -> Result<usize, NbtReadError> {
-        let new_parent_index;
-        self.nbt_parser
-            .set_tree_depth(self.nbt_parser.tree_depth() + depth_delta);
-        new_parent_index = self.set_new_parent_index(depth_delta, nbt_parent_index)?;
+    fn error_with_context(
+        &self,
+        error: NbtReadError,
+        nbt_tag: &NbtTag,
+        reader: &NbtEventReader<&[u8]>,
+    ) -> NbtReadError {
+        let context = NbtReadErrorContext {
+            offset: reader.offset(),
+            tag_start: nbt_tag.position().byte_start_all(),
+            state: reader.state(),
+            depth: nbt_tag.position().depth(),
+            path: self.tag_path(nbt_tag.position().parent()),
+        };
 
-        Ok(new_parent_index)
+        NbtReadError::WithContext {
+            source: Box::new(error),
+            context: Box::new(context),
+        }
     }
 
-    fn add_child_to_parent(&mut self, new_nbt_tag: &NbtTag, nbt_parent_index: usize) {
-        let child_index = new_nbt_tag.position().index();
-        let new_end_byte = new_nbt_tag.position().byte_end_all();
-
-        self.tags[nbt_parent_index]
-            .position_as_mut()
-            .children()
-            .push(child_index);
-        self.tags[nbt_parent_index]
-            .position_as_mut()
-            .set_byte_end_all_with_children(new_end_byte);
-    }
+    /// Names of the NbtTags from the root down to the tag at `index` (included).
+    /// Children of a list have no name, so they are identified by their position in the list, e.g. `[2]`
+    fn tag_path(&self, index: usize) -> Vec<String> {
+        let mut path = Vec::new();
+        if index >= self.tags.len() {
+            return path;
+        }
 
-    fn set_new_parent_index(
-        &mut self,
-        depth_delta: i64,
-        nbt_parent_index: usize,
-    ) -> Result<usize, NbtReadError> {
-        let new_parent_index;
-        match depth_delta {
-            0 => {
-                // nothing to do, old parent remains valid since we didnt go deeper
-                new_parent_index = nbt_parent_index;
-            }
-            1 => {
-                // we moved down in the nbt tree. This tag is the children of the tag in previous depth level
-                new_parent_index = self.nbt_parser.index() - 1;
-            }
-            -1 => {
-                //we moved up in the nbt tree. we need to restore the previous parent index
-                //the new parent is the parent of the previous parent
-                new_parent_index = self.tags[nbt_parent_index].position().parent();
-            }
-            -2 => {
-                //we moved up in the nbt tree. we need to restore the previous parent index
-                //this case is only hit when a list of compound is finished
-                // -1 because we exit the compound
-                // -1 because we exit the list
-                let nbt_grandparent_index = self.tags[nbt_parent_index].position().parent();
-                new_parent_index = self.tags[nbt_grandparent_index].position().parent();
+        let mut current = index;
+        loop {
+            let parent = self.tags[current].position().parent();
+            let is_root = parent == current;
+
+            match self.tags[parent].value() {
+                NbtTagType::List(_) if !is_root => {
+                    let list_position = self.tags[parent]
+                        .position()
+                        .children
+                        .iter()
+                        .position(|&child| child == current)
+                        .unwrap_or_default();
+                    path.push(format!("[{}]", list_position));
+                }
+                _ => path.push(self.tags[current].name().to_string()),
             }
-            _ => {
-                //this should never happen, because delta_depth can only be -2, -1, 0, 1
-                return Err(NbtReadError::InvalidNbtDepth);
+
+            if is_root {
+                break;
             }
+            current = parent;
         }
-        Ok(new_parent_index)
+
+        path.reverse();
+        path
     }
 
-    fn exit_nbttag_compound(&mut self, nbt_parent_index: usize) -> i64 {
-        let mut depth_delta = -1;
+    fn allocation_size(nbt_tag: &NbtTag) -> usize {
+        let value_size = match nbt_tag.value() {
+            NbtTagType::ByteArray(values) => values.len(),
+            NbtTagType::String(value) => value.len(),
+            NbtTagType::IntArray(values) => values.len() * std::mem::size_of::<i32>(),
+            NbtTagType::LongArray(values) => values.len() * std::mem::size_of::<i64>(),
+            _ => 0,
+        };
 
-        // the tag End is the last in a compound, so its parent is the compound
-        // if the grandparent is a list, we need to change the state back to list
-        // because reading a list is different than reading any other tag
-        let nbt_grandparent_index = self.tags[nbt_parent_index].position().parent();
-        let gp_nbt_tag = self.tags[nbt_grandparent_index].value();
+        // every NbtTag costs its own struct, its name twice (in the tag and as key of tags_map),
+        // the tags_map entry and its index in the children of its parent, even when it has no payload
+        std::mem::size_of::<NbtTag>()
+            + 2 * nbt_tag.name().len()
+            + std::mem::size_of::<(String, usize)>()
+            + std::mem::size_of::<usize>()
+            + value_size
+    }
 
-        match gp_nbt_tag {
-            NbtTagType::List(_) => {
-                if self.nbt_parser.restore_list_ctx() {
-                    self.nbt_parser.change_state_to(fsm::ParseNbtFsmState::List);
-                } else {
-                    // only in this case we will have a depth_delta of -2 because
-                    // compound is finished (=-1) and the list as well (=-1)
+    fn add_child_to_parent(
+        &mut self,
+        child_index: usize,
+        new_end_byte: usize,
+        nbt_parent_index: usize,
+    ) {
+        self.tags[nbt_parent_index]
+            .position_as_mut()
+            .children()
+            .push(child_index);
 
-                    //TOTO: i think there could be a bug here if we are the end of two lists
-                    // example: list of compounds of list of compounds
-                    depth_delta -= 1;
-                }
-            }
-            _ => {
-                //nothing to do
+        // the new tag extends all its ancestors, not only its parent:
+        // e.g. the last compound of a list ends after its End tag, and so does the list
+        let mut ancestor_index = nbt_parent_index;
+        loop {
+            let ancestor_position = self.tags[ancestor_index].position_as_mut();
+            ancestor_position.set_byte_end_all_with_children(new_end_byte);
+
+            if ancestor_position.parent() == ancestor_index {
+                break;
             }
+            ancestor_index = ancestor_position.parent();
         }
-        depth_delta
     }
 }
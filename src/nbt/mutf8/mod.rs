@@ -0,0 +1,187 @@
+use crate::nbt;
+
+/// Decode a Java Modified UTF-8 string.
+///
+/// Differences from standard UTF-8: the null char is encoded on 2 bytes as `C0 80` and characters
+/// outside of the Basic Multilingual Plane are encoded as a surrogate pair, 3 bytes per surrogate.
+/// Returns None if the bytes are not valid MUTF-8, including a bare 0x00 byte and overlong sequences:
+/// [`encode`] would not give back the same bytes for them
+pub fn decode(raw: &[u8]) -> Option<String> {
+    let mut code_units = Vec::with_capacity(raw.len());
+    let mut bytes = raw.iter();
+
+    while let Some(&first) = bytes.next() {
+        let code_unit = match first {
+            0x01..=0x7F => u16::from(first),
+            0xC0..=0xDF => {
+                let second = continuation(bytes.next())?;
+                let code_unit = (u16::from(first & 0x1F) << 6) | second;
+                // C0 80 is the only 2 bytes form allowed below 0x80
+                if code_unit != 0 && code_unit < 0x80 {
+                    return None;
+                }
+                code_unit
+            }
+            0xE0..=0xEF => {
+                let second = continuation(bytes.next())?;
+                let third = continuation(bytes.next())?;
+                let code_unit = (u16::from(first & 0x0F) << 12) | (second << 6) | third;
+                if code_unit < 0x800 {
+                    return None;
+                }
+                code_unit
+            }
+            _ => return None,
+        };
+        code_units.push(code_unit);
+    }
+
+    // unpaired surrogates are rejected here
+    String::from_utf16(&code_units).ok()
+}
+
+fn continuation(byte: Option<&u8>) -> Option<u16> {
+    match byte {
+        Some(&byte) if byte & 0xC0 == 0x80 => Some(u16::from(byte & 0x3F)),
+        _ => None,
+    }
+}
+
+/// Encode a string to Java Modified UTF-8
+pub fn encode(value: &str) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(value.len());
+
+    for code_unit in value.encode_utf16() {
+        match code_unit {
+            0x0001..=0x007F => raw.push(code_unit as u8),
+            // the null char is included here, it is never encoded as a single 0x00 byte
+            0x0000 | 0x0080..=0x07FF => {
+                raw.push(0xC0 | (code_unit >> 6) as u8);
+                raw.push(0x80 | (code_unit & 0x3F) as u8);
+            }
+            _ => {
+                raw.push(0xE0 | (code_unit >> 12) as u8);
+                raw.push(0x80 | ((code_unit >> 6) & 0x3F) as u8);
+                raw.push(0x80 | (code_unit & 0x3F) as u8);
+            }
+        }
+    }
+
+    raw
+}
+
+/// Decode the raw bytes of a NBT string with the text encoding of the given NBT flavour:
+/// Java uses MUTF-8 and Bedrock uses standard UTF-8.
+///
+/// If the bytes cannot be decoded, every byte is mapped to the char with the same code point,
+/// so that [`encode_raw`] gives back the original bytes, and `false` is returned as second element
+pub fn decode_lossless(raw: &[u8], encoding: nbt::NbtEncoding) -> (String, bool) {
+    let decoded = match encoding {
+        nbt::NbtEncoding::BigEndian => decode(raw),
+        nbt::NbtEncoding::LittleEndian | nbt::NbtEncoding::NetworkLittleEndian => {
+            String::from_utf8(raw.to_vec()).ok()
+        }
+    };
+
+    match decoded {
+        Some(value) => (value, true),
+        None => (raw.iter().map(|&byte| byte as char).collect(), false),
+    }
+}
+
+/// Encode a string with the text encoding of the given NBT flavour
+pub fn encode_with(value: &str, encoding: nbt::NbtEncoding) -> Vec<u8> {
+    match encoding {
+        nbt::NbtEncoding::BigEndian => encode(value),
+        nbt::NbtEncoding::LittleEndian | nbt::NbtEncoding::NetworkLittleEndian => {
+            value.as_bytes().to_vec()
+        }
+    }
+}
+
+/// Inverse of the fallback of [`decode_lossless`]: every char is written back as a single byte.
+/// Returns None if the string contains a char that does not fit in a byte
+pub fn encode_raw(value: &str) -> Option<Vec<u8>> {
+    value.chars().map(|ch| u8::try_from(ch).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_ascii() {
+        assert_eq!(decode(b"Hello").unwrap(), "Hello");
+        assert_eq!(encode("Hello"), b"Hello".to_vec());
+    }
+
+    #[test]
+    fn test_decode_null_char() {
+        assert_eq!(decode(&[b'a', 0xC0, 0x80, b'b']).unwrap(), "a\0b");
+        assert_eq!(encode("a\0b"), vec![b'a', 0xC0, 0x80, b'b']);
+    }
+
+    #[test]
+    fn test_decode_cyrillic() {
+        let value = "Привет";
+        // characters of the BMP are encoded as in standard UTF-8
+        assert_eq!(encode(value), value.as_bytes().to_vec());
+        assert_eq!(decode(value.as_bytes()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_decode_surrogate_pair() {
+        // U+1F600 is the surrogate pair D83D DE00
+        let raw = vec![0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80];
+        assert_eq!(decode(&raw).unwrap(), "\u{1F600}");
+        assert_eq!(encode("\u{1F600}"), raw);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        // 4 bytes sequences do not exist in MUTF-8
+        assert_eq!(decode("\u{1F600}".as_bytes()), None);
+        // truncated sequence
+        assert_eq!(decode(&[0xE0, 0x80]), None);
+        // unpaired surrogate
+        assert_eq!(decode(&[0xED, 0xA0, 0xBD]), None);
+    }
+
+    #[test]
+    fn test_decode_non_canonical() {
+        // the null char is always C0 80, never a bare 0x00
+        assert_eq!(decode(&[b'a', 0x00]), None);
+        // overlong 2 bytes form of 'A'
+        assert_eq!(decode(&[0xC1, 0x81]), None);
+        // overlong 2 bytes form of U+0001
+        assert_eq!(decode(&[0xC0, 0x81]), None);
+        // overlong 3 bytes form of the null char
+        assert_eq!(decode(&[0xE0, 0x80, 0x80]), None);
+        // overlong 3 bytes form of U+07FF
+        assert_eq!(decode(&[0xE0, 0x9F, 0xBF]), None);
+        // shortest forms are still accepted
+        assert_eq!(decode(&[0xDF, 0xBF]).unwrap(), "\u{7FF}");
+        assert_eq!(decode(&[0xE0, 0xA0, 0x80]).unwrap(), "\u{800}");
+    }
+
+    #[test]
+    fn test_decode_lossless_keeps_non_canonical_bytes() {
+        for raw in [vec![0x00], vec![0xC1, 0x81], vec![0xE0, 0x80, 0x80]] {
+            let (value, is_valid) = decode_lossless(&raw, nbt::NbtEncoding::BigEndian);
+            assert!(!is_valid);
+            assert_eq!(encode_raw(&value).unwrap(), raw);
+        }
+    }
+
+    #[test]
+    fn test_decode_lossless_fallback() {
+        let raw = vec![b'a', 0xFF, 0xE0];
+        let (value, is_valid) = decode_lossless(&raw, nbt::NbtEncoding::BigEndian);
+        assert!(!is_valid);
+        assert_eq!(encode_raw(&value).unwrap(), raw);
+
+        let (value, is_valid) = decode_lossless("ü".as_bytes(), nbt::NbtEncoding::LittleEndian);
+        assert!(is_valid);
+        assert_eq!(value, "ü");
+    }
+}
@@ -0,0 +1,717 @@
+use serde::ser::{self, Serialize};
+use serde::{Deserialize, Deserializer};
+
+use crate::nbt::{NbtCompound, NbtList, NbtTagId, NbtValue, NbtWriteError, ParseOptions};
+
+// names of the newtype structs which the serializer writes as arrays instead of lists
+const BYTE_ARRAY_NAME: &str = "__nbtsniper_byte_array";
+const INT_ARRAY_NAME: &str = "__nbtsniper_int_array";
+const LONG_ARRAY_NAME: &str = "__nbtsniper_long_array";
+
+/// Serialize `value` as binary Java NBT: a root tag named "" holding the value, which must be a compound
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, NbtWriteError> {
+    to_bytes_with_options(value, &ParseOptions::default())
+}
+
+/// Serialize `value` with the encoding and root mode of `options`.
+/// With [`ParseOptions::any_root_tag`] the root may be any type
+pub fn to_bytes_with_options<T: Serialize + ?Sized>(
+    value: &T,
+    options: &ParseOptions,
+) -> Result<Vec<u8>, NbtWriteError> {
+    let value = value
+        .serialize(ValueSerializer)?
+        .ok_or_else(|| NbtWriteError::Custom("the root value is None".to_string()))?;
+
+    if value.tag_id() != NbtTagId::Compound && !options.any_root_tag() {
+        return Err(NbtWriteError::Custom(format!(
+            "the root must be a compound, found {:?}",
+            value.tag_id()
+        )));
+    }
+
+    let mut roots = NbtCompound::new();
+    roots.insert(String::new(), value);
+    roots.write_roots(options)
+}
+
+impl ser::Error for NbtWriteError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        NbtWriteError::Custom(msg.to_string())
+    }
+}
+
+macro_rules! impl_array_wrapper {
+    ($(#[$meta:meta])* $name:ident, $element:ty, $newtype_name:expr) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+        pub struct $name(pub Vec<$element>);
+
+        impl Serialize for $name {
+            fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct($newtype_name, &self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                Vec::deserialize(deserializer).map($name)
+            }
+        }
+    };
+}
+
+impl_array_wrapper!(
+    /// Sequence serialized as a NBT ByteArray instead of a List of Bytes
+    NbtByteArray,
+    i8,
+    BYTE_ARRAY_NAME
+);
+impl_array_wrapper!(
+    /// Sequence serialized as a NBT IntArray instead of a List of Ints
+    NbtIntArray,
+    i32,
+    INT_ARRAY_NAME
+);
+impl_array_wrapper!(
+    /// Sequence serialized as a NBT LongArray instead of a List of Longs
+    NbtLongArray,
+    i64,
+    LONG_ARRAY_NAME
+);
+
+macro_rules! impl_array_with_module {
+    ($(#[$meta:meta])* $module:ident, $newtype_name:expr) => {
+        $(#[$meta])*
+        pub mod $module {
+            use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+            pub fn serialize<T, S>(values: &T, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                T: Serialize + ?Sized,
+                S: Serializer,
+            {
+                serializer.serialize_newtype_struct($newtype_name, values)
+            }
+
+            pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+            where
+                T: Deserialize<'de>,
+                D: Deserializer<'de>,
+            {
+                T::deserialize(deserializer)
+            }
+        }
+    };
+}
+
+impl_array_with_module!(
+    /// `#[serde(with = "nbtsniper::nbt::byte_array")]` writes a sequence of 8-bit integers as a ByteArray
+    byte_array,
+    super::BYTE_ARRAY_NAME
+);
+impl_array_with_module!(
+    /// `#[serde(with = "nbtsniper::nbt::int_array")]` writes a sequence of 32-bit integers as an IntArray
+    int_array,
+    super::INT_ARRAY_NAME
+);
+impl_array_with_module!(
+    /// `#[serde(with = "nbtsniper::nbt::long_array")]` writes a sequence of 64-bit integers as a LongArray
+    long_array,
+    super::LONG_ARRAY_NAME
+);
+
+// a value of a newtype struct named after an array type, converted from the List it was serialized to
+fn into_array(name: &str, value: Option<NbtValue>) -> Result<Option<NbtValue>, NbtWriteError> {
+    let array = match (name, value) {
+        (BYTE_ARRAY_NAME, Some(NbtValue::List(NbtList::Empty))) => NbtValue::ByteArray(Vec::new()),
+        (BYTE_ARRAY_NAME, Some(NbtValue::List(NbtList::Byte(values)))) => {
+            NbtValue::ByteArray(values)
+        }
+        (BYTE_ARRAY_NAME, Some(value @ NbtValue::ByteArray(_))) => value,
+        (INT_ARRAY_NAME, Some(NbtValue::List(NbtList::Empty))) => NbtValue::IntArray(Vec::new()),
+        (INT_ARRAY_NAME, Some(NbtValue::List(NbtList::Int(values)))) => NbtValue::IntArray(values),
+        (INT_ARRAY_NAME, Some(value @ NbtValue::IntArray(_))) => value,
+        (LONG_ARRAY_NAME, Some(NbtValue::List(NbtList::Empty))) => NbtValue::LongArray(Vec::new()),
+        (LONG_ARRAY_NAME, Some(NbtValue::List(NbtList::Long(values)))) => {
+            NbtValue::LongArray(values)
+        }
+        (LONG_ARRAY_NAME, Some(value @ NbtValue::LongArray(_))) => value,
+        (BYTE_ARRAY_NAME | INT_ARRAY_NAME | LONG_ARRAY_NAME, value) => {
+            return Err(NbtWriteError::Custom(format!(
+                "an array must be a sequence of integers of its width, found {:?}",
+                value.map(|value| value.tag_id())
+            )))
+        }
+        (_, value) => return Ok(value),
+    };
+    Ok(Some(array))
+}
+
+fn unsigned_to_signed<U, I>(value: U) -> Result<I, NbtWriteError>
+where
+    U: Copy + std::fmt::Display,
+    I: TryFrom<U>,
+{
+    I::try_from(value).map_err(|_| {
+        NbtWriteError::Custom(format!(
+            "{value} is out of the range of the NBT {}",
+            std::any::type_name::<I>()
+        ))
+    })
+}
+
+// enum variants with data are written as a compound with a single child named as the variant
+fn wrap_variant(variant: Option<&'static str>, value: NbtValue) -> NbtValue {
+    match variant {
+        Some(variant) => {
+            let mut compound = NbtCompound::new();
+            compound.insert(variant.to_string(), value);
+            NbtValue::Compound(compound)
+        }
+        None => value,
+    }
+}
+
+// Serializer to owned NbtValues, which are then written with the binary writer.
+// None is returned for the values which are not written at all, e.g. Option::None fields
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Option<NbtValue>;
+    type Error = NbtWriteError;
+
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = ListSerializer;
+    type SerializeMap = CompoundSerializer;
+    type SerializeStruct = CompoundSerializer;
+    type SerializeStructVariant = CompoundSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::Byte(v as i8)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::Long(v)))
+    }
+
+    // NBT has no unsigned types, unsigned integers are only written when they fit in the signed type of the same width
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        unsigned_to_signed(v).map(|v| Some(NbtValue::Byte(v)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        unsigned_to_signed(v).map(|v| Some(NbtValue::Short(v)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        unsigned_to_signed(v).map(|v| Some(NbtValue::Int(v)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        unsigned_to_signed(v).map(|v| Some(NbtValue::Long(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::String(v.to_string())))
+    }
+
+    // a byte buffer is opaque data, its bytes keep their bits in the ByteArray
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::ByteArray(
+            v.iter().map(|&byte| byte as i8).collect(),
+        )))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    // NBT has no unit type, a unit is an empty compound so that it is not lost like a None
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::Compound(NbtCompound::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(NbtValue::String(variant.to_string())))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        into_array(name, value.serialize(self)?)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        let value = value.serialize(self)?.ok_or_else(|| {
+            NbtWriteError::Custom(format!("the value of the variant {variant} is None"))
+        })?;
+        Ok(Some(wrap_variant(Some(variant), value)))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(ListSerializer::new(None))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Ok(ListSerializer::new(None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Ok(ListSerializer::new(None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(ListSerializer::new(Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(CompoundSerializer::new(None))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(CompoundSerializer::new(None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(CompoundSerializer::new(Some(variant)))
+    }
+}
+
+struct ListSerializer {
+    list: NbtList,
+    variant: Option<&'static str>,
+}
+
+impl ListSerializer {
+    fn new(variant: Option<&'static str>) -> ListSerializer {
+        ListSerializer {
+            list: NbtList::Empty,
+            variant,
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtWriteError> {
+        let value = value
+            .serialize(ValueSerializer)?
+            .ok_or_else(|| NbtWriteError::Custom("NBT List elements cannot be None".to_string()))?;
+        self.list
+            .push(value)
+            .map_err(|e| NbtWriteError::Custom(e.to_string()))
+    }
+
+    fn finish(self) -> Result<Option<NbtValue>, NbtWriteError> {
+        Ok(Some(wrap_variant(self.variant, NbtValue::List(self.list))))
+    }
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Option<NbtValue>;
+    type Error = NbtWriteError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Option<NbtValue>;
+    type Error = NbtWriteError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Option<NbtValue>;
+    type Error = NbtWriteError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for ListSerializer {
+    type Ok = Option<NbtValue>;
+    type Error = NbtWriteError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+struct CompoundSerializer {
+    compound: NbtCompound,
+    next_key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl CompoundSerializer {
+    fn new(variant: Option<&'static str>) -> CompoundSerializer {
+        CompoundSerializer {
+            compound: NbtCompound::new(),
+            next_key: None,
+            variant,
+        }
+    }
+
+    // children serialized to None, e.g. Option::None fields, are left out of the compound
+    fn insert<T: Serialize + ?Sized>(
+        &mut self,
+        key: String,
+        value: &T,
+    ) -> Result<(), NbtWriteError> {
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            self.compound.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<NbtValue>, NbtWriteError> {
+        Ok(Some(wrap_variant(
+            self.variant,
+            NbtValue::Compound(self.compound),
+        )))
+    }
+}
+
+impl ser::SerializeMap for CompoundSerializer {
+    type Ok = Option<NbtValue>;
+    type Error = NbtWriteError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        match key.serialize(ValueSerializer)? {
+            Some(NbtValue::String(key)) => {
+                self.next_key = Some(key);
+                Ok(())
+            }
+            key => Err(NbtWriteError::Custom(format!(
+                "NBT Compound keys must be strings, found {:?}",
+                key.map(|key| key.tag_id())
+            ))),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self.next_key.take().ok_or_else(|| {
+            NbtWriteError::Custom("serialize_value called before serialize_key".to_string())
+        })?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for CompoundSerializer {
+    type Ok = Option<NbtValue>;
+    type Error = NbtWriteError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for CompoundSerializer {
+    type Ok = Option<NbtValue>;
+    type Error = NbtWriteError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::NbtData;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Item {
+        id: String,
+        #[serde(rename = "Count")]
+        count: u8,
+    }
+
+    #[derive(Serialize)]
+    struct Player {
+        name: String,
+        health: f32,
+        on_ground: bool,
+        uuid: NbtIntArray,
+        inventory: Vec<Item>,
+        pos: (f64, f64, f64),
+        spawn: Option<(i32, i32, i32)>,
+    }
+
+    fn player() -> Player {
+        Player {
+            name: "Steve".to_string(),
+            health: 20.0,
+            on_ground: true,
+            uuid: NbtIntArray(vec![1, 2, 3, 4]),
+            inventory: vec![Item {
+                id: "minecraft:stone".to_string(),
+                count: 64,
+            }],
+            pos: (0.5, 64.0, -3.5),
+            spawn: None,
+        }
+    }
+
+    #[test]
+    fn test_to_bytes_matches_nbt_macro() {
+        let roots = crate::nbt! {
+            "": {
+                "name": "Steve",
+                "health": 20.0f32,
+                "on_ground": 1i8,
+                "uuid": [I; 1, 2, 3, 4],
+                "inventory": [{ "id": "minecraft:stone", "Count": 64i8 }],
+                "pos": [0.5f64, 64.0f64, -3.5f64],
+            }
+        }
+        .unwrap();
+        let nbt_data = NbtData::from_value(&roots).unwrap();
+
+        assert_eq!(&to_bytes(&player()).unwrap(), nbt_data.raw_bytes());
+    }
+
+    #[test]
+    fn test_to_bytes_array_encodings() {
+        #[derive(Serialize)]
+        struct Arrays {
+            #[serde(with = "byte_array")]
+            bytes: Vec<i8>,
+            #[serde(with = "long_array")]
+            longs: Vec<i64>,
+            #[serde(with = "int_array")]
+            empty: Vec<i32>,
+            ints: Vec<i32>,
+        }
+
+        let arrays = Arrays {
+            bytes: vec![-1, 1],
+            longs: vec![7],
+            empty: Vec::new(),
+            ints: vec![1, 2],
+        };
+        let roots = NbtData::from_buf(to_bytes(&arrays).unwrap())
+            .unwrap()
+            .to_value()
+            .unwrap();
+        let root = roots.get("").unwrap().as_compound().unwrap();
+
+        assert_eq!(root.get("bytes"), Some(&NbtValue::ByteArray(vec![-1, 1])));
+        assert_eq!(root.get("longs"), Some(&NbtValue::LongArray(vec![7])));
+        assert_eq!(root.get("empty"), Some(&NbtValue::IntArray(Vec::new())));
+        assert_eq!(
+            root.get("ints"),
+            Some(&NbtValue::List(NbtList::Int(vec![1, 2])))
+        );
+    }
+
+    #[test]
+    fn test_to_bytes_enums() {
+        #[derive(Serialize)]
+        enum Shape {
+            Point,
+            Circle(f32),
+            Rect { w: i32, h: i32 },
+        }
+
+        let mut options = ParseOptions::default();
+        options.set_any_root_tag(true);
+        let bytes = to_bytes_with_options(&vec![Shape::Rect { w: 2, h: 3 }], &options).unwrap();
+        let roots = NbtData::from_buf_with_options(bytes, &options)
+            .unwrap()
+            .to_value()
+            .unwrap();
+        let expected = crate::nbt! { "": [{ "Rect": { "w": 2i32, "h": 3i32 } }] }.unwrap();
+        assert_eq!(roots, expected);
+
+        // a unit variant is a String, the others a Compound: they cannot share a List
+        let error =
+            to_bytes_with_options(&vec![Shape::Point, Shape::Circle(1.0)], &options).unwrap_err();
+        assert!(matches!(error, NbtWriteError::Custom(_)));
+
+        // a variant without a value would be an empty Compound, which cannot be read back
+        #[derive(Serialize)]
+        enum Slot {
+            Item(Option<i32>),
+        }
+        let error = to_bytes_with_options(&Slot::Item(None), &options).unwrap_err();
+        assert!(matches!(error, NbtWriteError::Custom(_)));
+    }
+
+    #[test]
+    fn test_to_bytes_unsigned_integers() {
+        struct Buffer(Vec<u8>);
+
+        impl Serialize for Buffer {
+            fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(&self.0)
+            }
+        }
+
+        let mut options = ParseOptions::default();
+        options.set_any_root_tag(true);
+        let value = |bytes: Vec<u8>| {
+            NbtData::from_buf_with_options(bytes, &options)
+                .unwrap()
+                .to_value()
+                .unwrap()
+                .remove("")
+                .unwrap()
+        };
+
+        assert_eq!(
+            value(to_bytes_with_options(&127u8, &options).unwrap()),
+            NbtValue::Byte(127)
+        );
+        assert_eq!(
+            value(to_bytes_with_options(&(i64::MAX as u64), &options).unwrap()),
+            NbtValue::Long(i64::MAX)
+        );
+
+        // out of the signed range, the value is not reinterpreted
+        for error in [
+            to_bytes_with_options(&200u8, &options),
+            to_bytes_with_options(&u16::MAX, &options),
+            to_bytes_with_options(&u32::MAX, &options),
+            to_bytes_with_options(&u64::MAX, &options),
+            to_bytes_with_options(&vec![1u8, 255], &options),
+        ] {
+            assert!(matches!(error, Err(NbtWriteError::Custom(_))));
+        }
+
+        // a byte buffer keeps its bits
+        assert_eq!(
+            value(to_bytes_with_options(&Buffer(vec![255, 1]), &options).unwrap()),
+            NbtValue::ByteArray(vec![-1, 1])
+        );
+    }
+
+    #[test]
+    fn test_to_bytes_invalid_values() {
+        // the root must be a compound unless any_root_tag is set
+        assert!(matches!(to_bytes(&1i32), Err(NbtWriteError::Custom(_))));
+        assert!(to_bytes(&None::<i32>).is_err());
+
+        let mut options = ParseOptions::default();
+        options.set_any_root_tag(true);
+        assert_eq!(
+            to_bytes_with_options(&1i32, &options).unwrap(),
+            vec![3, 0, 0, 0, 0, 0, 1]
+        );
+
+        let mut map = std::collections::BTreeMap::new();
+        map.insert(1i32, "one");
+        assert!(matches!(to_bytes(&map), Err(NbtWriteError::Custom(_))));
+
+        // a List element cannot be left out
+        assert!(to_bytes_with_options(&vec![Some(1i32), None], &options).is_err());
+    }
+}
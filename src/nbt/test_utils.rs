@@ -0,0 +1,31 @@
+use crate::nbt::NbtTagId;
+
+pub(crate) fn list_header(buffer: &mut Vec<u8>, tag_id: NbtTagId, len: i32) {
+    buffer.push(tag_id.into_u8());
+    buffer.extend(&len.to_be_bytes());
+}
+
+pub(crate) fn named_tag_header(buffer: &mut Vec<u8>, tag_id: NbtTagId, name: &str) {
+    buffer.push(tag_id.into_u8());
+    buffer.extend(&(name.len() as u16).to_be_bytes());
+    buffer.extend(name.as_bytes());
+}
+
+// Root { Name: "Steve", Scores: [1, 2], Pos: { X: 7L } }
+pub(crate) fn sample() -> Vec<u8> {
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+    named_tag_header(&mut buffer, NbtTagId::String, "Name");
+    buffer.extend(&5u16.to_be_bytes());
+    buffer.extend(b"Steve");
+    named_tag_header(&mut buffer, NbtTagId::List, "Scores");
+    list_header(&mut buffer, NbtTagId::Int, 2);
+    buffer.extend(&1i32.to_be_bytes());
+    buffer.extend(&2i32.to_be_bytes());
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Pos");
+    named_tag_header(&mut buffer, NbtTagId::Long, "X");
+    buffer.extend(&7i64.to_be_bytes());
+    buffer.push(NbtTagId::End.into_u8());
+    buffer.push(NbtTagId::End.into_u8());
+    buffer
+}
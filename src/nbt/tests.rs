@@ -1,472 +1,1504 @@
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json;
-    use std::io::Cursor;
-
-    // Mocking the fsm module
-    use crate::nbt::fsm;
-    use crate::nbt::{
-        NbtData, NbtReadError, NbtTag, NbtTagId, NbtTagPosition, NbtTagPositionRawBytes, NbtTagType,
-    };
-
-    // Now, write tests for NbtTagId
-    #[test]
-    fn test_nbt_tag_id_from_u8_valid() {
-        for (value, expected) in vec![
-            (0u8, NbtTagId::End),
-            (1, NbtTagId::Byte),
-            (2, NbtTagId::Short),
-            (3, NbtTagId::Int),
-            (4, NbtTagId::Long),
-            (5, NbtTagId::Float),
-            (6, NbtTagId::Double),
-            (7, NbtTagId::ByteArray),
-            (8, NbtTagId::String),
-            (9, NbtTagId::List),
-            (10, NbtTagId::Compound),
-            (11, NbtTagId::IntArray),
-            (12, NbtTagId::LongArray),
-        ] {
-            assert_eq!(NbtTagId::from_u8(value), Some(expected));
-        }
+use serde_json;
+
+use crate::nbt::test_utils::{list_header, named_tag_header};
+use crate::nbt::{
+    NbtData, NbtEditError, NbtEncoding, NbtReadError, NbtTag, NbtTagId, NbtTagPosition,
+    NbtTagPositionRawBytes, NbtTagType, ParseNbtFsmState, ParseOptions, RootMode,
+};
+
+// Now, write tests for NbtTagId
+#[test]
+fn test_nbt_tag_id_from_u8_valid() {
+    for (value, expected) in vec![
+        (0u8, NbtTagId::End),
+        (1, NbtTagId::Byte),
+        (2, NbtTagId::Short),
+        (3, NbtTagId::Int),
+        (4, NbtTagId::Long),
+        (5, NbtTagId::Float),
+        (6, NbtTagId::Double),
+        (7, NbtTagId::ByteArray),
+        (8, NbtTagId::String),
+        (9, NbtTagId::List),
+        (10, NbtTagId::Compound),
+        (11, NbtTagId::IntArray),
+        (12, NbtTagId::LongArray),
+    ] {
+        assert_eq!(NbtTagId::from_u8(value), Some(expected));
     }
+}
 
-    #[test]
-    fn test_nbt_tag_id_from_u8_invalid() {
-        let invalid_values = vec![13u8, 255, 100, 14, 20];
-        for value in invalid_values {
-            assert_eq!(NbtTagId::from_u8(value), None);
-        }
+#[test]
+fn test_nbt_tag_id_from_u8_invalid() {
+    let invalid_values = vec![13u8, 255, 100, 14, 20];
+    for value in invalid_values {
+        assert_eq!(NbtTagId::from_u8(value), None);
     }
+}
 
-    #[test]
-    fn test_nbt_tag_id_into_u8() {
-        let variants = vec![
-            (NbtTagId::End, 0u8),
-            (NbtTagId::Byte, 1),
-            (NbtTagId::Short, 2),
-            (NbtTagId::Int, 3),
-            (NbtTagId::Long, 4),
-            (NbtTagId::Float, 5),
-            (NbtTagId::Double, 6),
-            (NbtTagId::ByteArray, 7),
-            (NbtTagId::String, 8),
-            (NbtTagId::List, 9),
-            (NbtTagId::Compound, 10),
-            (NbtTagId::IntArray, 11),
-            (NbtTagId::LongArray, 12),
-        ];
-
-        for (variant, expected) in variants {
-            assert_eq!(variant.into_u8(), expected);
-        }
+#[test]
+fn test_nbt_tag_id_into_u8() {
+    let variants = vec![
+        (NbtTagId::End, 0u8),
+        (NbtTagId::Byte, 1),
+        (NbtTagId::Short, 2),
+        (NbtTagId::Int, 3),
+        (NbtTagId::Long, 4),
+        (NbtTagId::Float, 5),
+        (NbtTagId::Double, 6),
+        (NbtTagId::ByteArray, 7),
+        (NbtTagId::String, 8),
+        (NbtTagId::List, 9),
+        (NbtTagId::Compound, 10),
+        (NbtTagId::IntArray, 11),
+        (NbtTagId::LongArray, 12),
+    ];
+
+    for (variant, expected) in variants {
+        assert_eq!(variant.into_u8(), expected);
     }
+}
 
-    #[test]
-    fn test_nbt_tag_id_default() {
-        let default_tag = NbtTagId::default();
-        assert_eq!(default_tag, NbtTagId::End);
-    }
+#[test]
+fn test_nbt_tag_id_default() {
+    let default_tag = NbtTagId::default();
+    assert_eq!(default_tag, NbtTagId::End);
+}
 
-    // Tests for NbtTagType
-    #[test]
-    fn test_nbt_tag_type_default() {
-        let default_tag = NbtTagType::default();
-        assert_eq!(default_tag, NbtTagType::End(None));
-    }
+// Tests for NbtTagType
+#[test]
+fn test_nbt_tag_type_default() {
+    let default_tag = NbtTagType::default();
+    assert_eq!(default_tag, NbtTagType::End(None));
+}
 
-    #[test]
-    fn test_nbt_tag_type_serialization() {
-        let tag = NbtTagType::Int(42);
-        let serialized = serde_json::to_string(&tag).unwrap();
-        assert_eq!(serialized, r#"{"Int":42}"#);
+#[test]
+fn test_nbt_tag_type_serialization() {
+    let tag = NbtTagType::Int(42);
+    let serialized = serde_json::to_string(&tag).unwrap();
+    assert_eq!(serialized, r#"{"Int":42}"#);
 
-        let tag = NbtTagType::String("Test".to_string());
-        let serialized = serde_json::to_string(&tag).unwrap();
-        assert_eq!(serialized, r#"{"String":"Test"}"#);
-    }
+    let tag = NbtTagType::String("Test".to_string());
+    let serialized = serde_json::to_string(&tag).unwrap();
+    assert_eq!(serialized, r#"{"String":"Test"}"#);
+}
 
-    // Tests for NbtTagPositionRawBytes
-    #[test]
-    fn test_nbt_tag_position_raw_bytes_new() {
-        let pos = NbtTagPositionRawBytes::new();
-        assert_eq!(pos.byte_start_all(), 0);
-        assert_eq!(pos.byte_end_all(), 0);
-        assert_eq!(pos.byte_end_all_with_children(), 0);
-        assert_eq!(pos.byte_start_id(), None);
-        assert_eq!(pos.byte_end_id(), None);
-        assert_eq!(pos.byte_start_name(), None);
-        assert_eq!(pos.byte_end_name(), None);
-        assert_eq!(pos.byte_start_value(), None);
-        assert_eq!(pos.byte_end_value(), None);
-    }
+// Tests for NbtTagPositionRawBytes
+#[test]
+fn test_nbt_tag_position_raw_bytes_new() {
+    let pos = NbtTagPositionRawBytes::new();
+    assert_eq!(pos.byte_start_all(), 0);
+    assert_eq!(pos.byte_end_all(), 0);
+    assert_eq!(pos.byte_end_all_with_children(), 0);
+    assert_eq!(pos.byte_start_id(), None);
+    assert_eq!(pos.byte_end_id(), None);
+    assert_eq!(pos.byte_start_name(), None);
+    assert_eq!(pos.byte_end_name(), None);
+    assert_eq!(pos.byte_start_value(), None);
+    assert_eq!(pos.byte_end_value(), None);
+}
 
-    #[test]
-    fn test_nbt_tag_position_raw_bytes_setters_getters() {
-        let mut pos = NbtTagPositionRawBytes::new();
-        pos.set_byte_start_all(10);
-        pos.set_byte_end_all(20);
-        pos.set_byte_end_all_with_children(30);
-        pos.set_byte_start_id(40);
-        pos.set_byte_end_id(50);
-        pos.set_byte_start_name(60);
-        pos.set_byte_end_name(70);
-        pos.set_byte_start_value(80);
-        pos.set_byte_end_value(90);
-
-        assert_eq!(pos.byte_start_all(), 10);
-        assert_eq!(pos.byte_end_all(), 20);
-        assert_eq!(pos.byte_end_all_with_children(), 30);
-        assert_eq!(pos.byte_start_id(), Some(40));
-        assert_eq!(pos.byte_end_id(), Some(50));
-        assert_eq!(pos.byte_start_name(), Some(60));
-        assert_eq!(pos.byte_end_name(), Some(70));
-        assert_eq!(pos.byte_start_value(), Some(80));
-        assert_eq!(pos.byte_end_value(), Some(90));
-    }
+#[test]
+fn test_nbt_tag_position_raw_bytes_setters_getters() {
+    let mut pos = NbtTagPositionRawBytes::new();
+    pos.set_byte_start_all(10);
+    pos.set_byte_end_all(20);
+    pos.set_byte_end_all_with_children(30);
+    pos.set_byte_start_id(40);
+    pos.set_byte_end_id(50);
+    pos.set_byte_start_name(60);
+    pos.set_byte_end_name(70);
+    pos.set_byte_start_value(80);
+    pos.set_byte_end_value(90);
+
+    assert_eq!(pos.byte_start_all(), 10);
+    assert_eq!(pos.byte_end_all(), 20);
+    assert_eq!(pos.byte_end_all_with_children(), 30);
+    assert_eq!(pos.byte_start_id(), Some(40));
+    assert_eq!(pos.byte_end_id(), Some(50));
+    assert_eq!(pos.byte_start_name(), Some(60));
+    assert_eq!(pos.byte_end_name(), Some(70));
+    assert_eq!(pos.byte_start_value(), Some(80));
+    assert_eq!(pos.byte_end_value(), Some(90));
+}
 
-    #[test]
-    fn test_nbt_tag_position_raw_bytes_reset() {
-        let mut pos = NbtTagPositionRawBytes::new();
-        pos.set_byte_start_all(10);
-        pos.set_byte_end_all(20);
-        pos.set_byte_end_all_with_children(30);
-        pos.set_byte_start_id(40);
-        pos.set_byte_end_id(50);
-        pos.set_byte_start_name(60);
-        pos.set_byte_end_name(70);
-        pos.set_byte_start_value(80);
-        pos.set_byte_end_value(90);
-
-        pos.reset();
-
-        assert_eq!(pos.byte_start_all(), 0);
-        assert_eq!(pos.byte_end_all(), 0);
-        assert_eq!(pos.byte_end_all_with_children(), 0);
-        assert_eq!(pos.byte_start_id(), None);
-        assert_eq!(pos.byte_end_id(), None);
-        assert_eq!(pos.byte_start_name(), None);
-        assert_eq!(pos.byte_end_name(), None);
-        assert_eq!(pos.byte_start_value(), None);
-        assert_eq!(pos.byte_end_value(), None);
-    }
+#[test]
+fn test_nbt_tag_position_raw_bytes_reset() {
+    let mut pos = NbtTagPositionRawBytes::new();
+    pos.set_byte_start_all(10);
+    pos.set_byte_end_all(20);
+    pos.set_byte_end_all_with_children(30);
+    pos.set_byte_start_id(40);
+    pos.set_byte_end_id(50);
+    pos.set_byte_start_name(60);
+    pos.set_byte_end_name(70);
+    pos.set_byte_start_value(80);
+    pos.set_byte_end_value(90);
+
+    pos.reset();
+
+    assert_eq!(pos.byte_start_all(), 0);
+    assert_eq!(pos.byte_end_all(), 0);
+    assert_eq!(pos.byte_end_all_with_children(), 0);
+    assert_eq!(pos.byte_start_id(), None);
+    assert_eq!(pos.byte_end_id(), None);
+    assert_eq!(pos.byte_start_name(), None);
+    assert_eq!(pos.byte_end_name(), None);
+    assert_eq!(pos.byte_start_value(), None);
+    assert_eq!(pos.byte_end_value(), None);
+}
 
-    // Tests for NbtTagPosition
-    #[test]
-    fn test_nbt_tag_position_new() {
-        let pos = NbtTagPosition::new();
-        assert_eq!(pos.byte_start_all(), 0);
-        assert_eq!(pos.byte_end_all(), 0);
-        assert_eq!(pos.byte_end_all_with_children(), 0);
-        assert_eq!(pos.byte_start_id(), None);
-        assert_eq!(pos.byte_end_id(), None);
-        assert_eq!(pos.byte_start_name(), None);
-        assert_eq!(pos.byte_end_name(), None);
-        assert_eq!(pos.byte_start_value(), None);
-        assert_eq!(pos.byte_end_value(), None);
-        assert_eq!(pos.index(), 0);
-        assert_eq!(pos.depth(), 0);
-        assert_eq!(pos.parent(), 0);
-        //assert!(pos.children().is_empty());
-    }
+// Tests for NbtTagPosition
+#[test]
+fn test_nbt_tag_position_new() {
+    let pos = NbtTagPosition::new();
+    assert_eq!(pos.byte_start_all(), 0);
+    assert_eq!(pos.byte_end_all(), 0);
+    assert_eq!(pos.byte_end_all_with_children(), 0);
+    assert_eq!(pos.byte_start_id(), None);
+    assert_eq!(pos.byte_end_id(), None);
+    assert_eq!(pos.byte_start_name(), None);
+    assert_eq!(pos.byte_end_name(), None);
+    assert_eq!(pos.byte_start_value(), None);
+    assert_eq!(pos.byte_end_value(), None);
+    assert_eq!(pos.index(), 0);
+    assert_eq!(pos.depth(), 0);
+    assert_eq!(pos.parent(), 0);
+    //assert!(pos.children().is_empty());
+}
 
-    #[test]
-    fn test_nbt_tag_position_setters_getters() {
-        let mut pos = NbtTagPosition::new();
-        pos.set_byte_start_all(100);
-        pos.set_byte_end_all(200);
-        pos.set_byte_end_all_with_children(300);
-        pos.set_byte_start_id(400);
-        pos.set_byte_end_id(500);
-        pos.set_byte_start_name(600);
-        pos.set_byte_end_name(700);
-        pos.set_byte_start_value(800);
-        pos.set_byte_end_value(900);
-        pos.set_index(1);
-        pos.set_depth(2);
-        pos.set_parent(3);
-
-        assert_eq!(pos.byte_start_all(), 100);
-        assert_eq!(pos.byte_end_all(), 200);
-        assert_eq!(pos.byte_end_all_with_children(), 300);
-        assert_eq!(pos.byte_start_id(), Some(400));
-        assert_eq!(pos.byte_end_id(), Some(500));
-        assert_eq!(pos.byte_start_name(), Some(600));
-        assert_eq!(pos.byte_end_name(), Some(700));
-        assert_eq!(pos.byte_start_value(), Some(800));
-        assert_eq!(pos.byte_end_value(), Some(900));
-        assert_eq!(pos.index(), 1);
-        assert_eq!(pos.depth(), 2);
-        assert_eq!(pos.parent(), 3);
-    }
+#[test]
+fn test_nbt_tag_position_setters_getters() {
+    let mut pos = NbtTagPosition::new();
+    pos.set_byte_start_all(100);
+    pos.set_byte_end_all(200);
+    pos.set_byte_end_all_with_children(300);
+    pos.set_byte_start_id(400);
+    pos.set_byte_end_id(500);
+    pos.set_byte_start_name(600);
+    pos.set_byte_end_name(700);
+    pos.set_byte_start_value(800);
+    pos.set_byte_end_value(900);
+    pos.set_index(1);
+    pos.set_depth(2);
+    pos.set_parent(3);
+
+    assert_eq!(pos.byte_start_all(), 100);
+    assert_eq!(pos.byte_end_all(), 200);
+    assert_eq!(pos.byte_end_all_with_children(), 300);
+    assert_eq!(pos.byte_start_id(), Some(400));
+    assert_eq!(pos.byte_end_id(), Some(500));
+    assert_eq!(pos.byte_start_name(), Some(600));
+    assert_eq!(pos.byte_end_name(), Some(700));
+    assert_eq!(pos.byte_start_value(), Some(800));
+    assert_eq!(pos.byte_end_value(), Some(900));
+    assert_eq!(pos.index(), 1);
+    assert_eq!(pos.depth(), 2);
+    assert_eq!(pos.parent(), 3);
+}
 
-    #[test]
-    fn test_nbt_tag_position_reset() {
-        let mut pos = NbtTagPosition::new();
-        pos.set_byte_start_all(100);
-        pos.set_byte_end_all(200);
-        pos.set_byte_end_all_with_children(300);
-        pos.set_byte_start_id(400);
-        pos.set_byte_end_id(500);
-        pos.set_byte_start_name(600);
-        pos.set_byte_end_name(700);
-        pos.set_byte_start_value(800);
-        pos.set_byte_end_value(900);
-        pos.set_index(1);
-        pos.set_depth(2);
-        pos.set_parent(3);
-        pos.children().push(4);
-
-        pos.reset();
-
-        assert_eq!(pos.byte_start_all(), 0);
-        assert_eq!(pos.byte_end_all(), 0);
-        assert_eq!(pos.byte_end_all_with_children(), 0);
-        assert_eq!(pos.byte_start_id(), None);
-        assert_eq!(pos.byte_end_id(), None);
-        assert_eq!(pos.byte_start_name(), None);
-        assert_eq!(pos.byte_end_name(), None);
-        assert_eq!(pos.byte_start_value(), None);
-        assert_eq!(pos.byte_end_value(), None);
-        assert_eq!(pos.index(), 0);
-        assert_eq!(pos.depth(), 0);
-        assert_eq!(pos.parent(), 0);
-        assert!(pos.children().is_empty());
-    }
+#[test]
+fn test_nbt_tag_position_reset() {
+    let mut pos = NbtTagPosition::new();
+    pos.set_byte_start_all(100);
+    pos.set_byte_end_all(200);
+    pos.set_byte_end_all_with_children(300);
+    pos.set_byte_start_id(400);
+    pos.set_byte_end_id(500);
+    pos.set_byte_start_name(600);
+    pos.set_byte_end_name(700);
+    pos.set_byte_start_value(800);
+    pos.set_byte_end_value(900);
+    pos.set_index(1);
+    pos.set_depth(2);
+    pos.set_parent(3);
+    pos.children().push(4);
+
+    pos.reset();
+
+    assert_eq!(pos.byte_start_all(), 0);
+    assert_eq!(pos.byte_end_all(), 0);
+    assert_eq!(pos.byte_end_all_with_children(), 0);
+    assert_eq!(pos.byte_start_id(), None);
+    assert_eq!(pos.byte_end_id(), None);
+    assert_eq!(pos.byte_start_name(), None);
+    assert_eq!(pos.byte_end_name(), None);
+    assert_eq!(pos.byte_start_value(), None);
+    assert_eq!(pos.byte_end_value(), None);
+    assert_eq!(pos.index(), 0);
+    assert_eq!(pos.depth(), 0);
+    assert_eq!(pos.parent(), 0);
+    assert!(pos.children().is_empty());
+}
+
+// Tests for NbtTag
+#[test]
+fn test_nbt_tag_default() {
+    let tag = NbtTag::default();
+    assert_eq!(tag.name(), "");
+    assert_eq!(tag.value(), &NbtTagType::End(None));
+    assert_eq!(tag.position().byte_start_all(), 0);
+}
+
+#[test]
+fn test_nbt_tag_setters_getters() {
+    let mut tag = NbtTag::default();
+    tag.set_name("TestTag".to_string());
+    tag.set_value(NbtTagType::Int(123));
+
+    let mut pos = NbtTagPosition::new();
+    pos.set_byte_start_all(10);
+    pos.set_byte_end_all(20);
+    tag.set_position(pos.clone());
 
-    // Tests for NbtTag
-    #[test]
-    fn test_nbt_tag_default() {
-        let tag = NbtTag::default();
-        assert_eq!(tag.name(), "");
-        assert_eq!(tag.value(), &NbtTagType::End(None));
-        assert_eq!(tag.position().byte_start_all(), 0);
+    assert_eq!(tag.name(), "TestTag");
+    assert_eq!(tag.value(), &NbtTagType::Int(123));
+    assert_eq!(tag.position().byte_start_all(), 10);
+}
+
+// Tests for NbtData
+#[test]
+fn test_nbt_data_new() {
+    let buffer = vec![];
+    let nbt_data = NbtData::new(buffer.clone());
+    assert!(nbt_data.nbt_tags().is_empty());
+    assert_eq!(nbt_data.raw_bytes(), &buffer);
+}
+
+#[test]
+fn test_nbt_data_from_buf_empty() {
+    let buffer = vec![];
+    let result = NbtData::from_buf(buffer);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_nbt_data_from_buf_invalid() {
+    let buffer = vec![255]; // Invalid tag id
+    let result = NbtData::from_buf(buffer);
+    assert!(result.is_err());
+    match result.err().unwrap().kind() {
+        NbtReadError::InvalidContent => (),
+        _ => panic!("Expected InvalidContent error"),
     }
+}
+
+#[test]
+fn test_nbt_data_parse_single_tag() {
+    // Create a buffer representing a single Int tag with name "Test" and value 42
+    // Tag structure:
+    // [Tag ID][Name Length][Name][Int Value]
+    // Tag ID: 3 (Int)
+    // Name Length: 4 (u16 big endian)
+    // Name: "Test"
+    // Int Value: 42 (i32 big endian)
+
+    let mut buffer = Vec::new();
 
-    #[test]
-    fn test_nbt_tag_setters_getters() {
-        let mut tag = NbtTag::default();
-        tag.set_name("TestTag".to_string());
-        tag.set_value(NbtTagType::Int(123));
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&8u16.to_be_bytes()); // Name length
+    buffer.extend("Compound".as_bytes());
 
-        let mut pos = NbtTagPosition::new();
-        pos.set_byte_start_all(10);
-        pos.set_byte_end_all(20);
-        tag.set_position(pos.clone());
+    buffer.push(NbtTagId::Int.into_u8());
 
-        assert_eq!(tag.name(), "TestTag");
-        assert_eq!(tag.value(), &NbtTagType::Int(123));
-        assert_eq!(tag.position().byte_start_all(), 10);
+    // Name length: 4
+    buffer.extend(&4u16.to_be_bytes());
+
+    // Name: "Test"
+    buffer.extend("Test".as_bytes());
+
+    // Int value: 42
+    buffer.extend(&42i32.to_be_bytes());
+
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf(buffer).unwrap();
+    assert_eq!(nbt_data.nbt_tags().len(), 3);
+    let tag = &nbt_data.nbt_tags()[1];
+    assert_eq!(tag.name(), "Test");
+    assert_eq!(tag.value(), &NbtTagType::Int(42));
+}
+
+#[test]
+fn test_nbt_data_parse_multiple_tags() {
+    // Create a buffer representing two tags:
+    // 1. Byte tag with name "A" and value 1
+    // 2. String tag with name "B" and value "Hello"
+
+    let mut buffer = Vec::new();
+
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&8u16.to_be_bytes()); // Name length
+    buffer.extend("Compound".as_bytes());
+
+    // First Tag: Byte
+    buffer.push(NbtTagId::Byte.into_u8());
+    buffer.extend(&1u16.to_be_bytes()); // Name length
+    buffer.extend("A".as_bytes());
+    buffer.push(1i8 as u8); // Byte value
+
+    // Second Tag: String
+    buffer.push(NbtTagId::String.into_u8());
+    buffer.extend(&1u16.to_be_bytes()); // Name length
+    buffer.extend("B".as_bytes());
+    buffer.extend(&5u16.to_be_bytes()); // String length
+    buffer.extend("Hello".as_bytes());
+
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf(buffer).unwrap();
+    assert_eq!(nbt_data.nbt_tags().len(), 4);
+
+    let tag1 = &nbt_data.nbt_tags()[1];
+    assert_eq!(tag1.name(), "A");
+    assert_eq!(tag1.value(), &NbtTagType::Byte(1));
+
+    let tag2 = &nbt_data.nbt_tags()[2];
+    assert_eq!(tag2.name(), "B");
+    assert_eq!(tag2.value(), &NbtTagType::String("Hello".to_string()));
+}
+
+#[test]
+fn test_nbt_data_parse_nested_compound() {
+    // Create a buffer representing a Compound tag containing an Int tag
+    // Structure:
+    // Compound Tag:
+    // [Tag ID][Name Length][Name]
+    // Inside Compound:
+    // [Int Tag ID][Name Length][Name][Int Value]
+    // [End Tag ID]
+
+    let mut buffer = Vec::new();
+
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&8u16.to_be_bytes()); // Name length
+    buffer.extend("Compound".as_bytes());
+
+    // Nested Int Tag
+    buffer.push(NbtTagId::Int.into_u8());
+    buffer.extend(&3u16.to_be_bytes()); // Name length
+    buffer.extend("Int".as_bytes());
+    buffer.extend(&100i32.to_be_bytes()); // Int value
+
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf(buffer).unwrap();
+    assert_eq!(nbt_data.nbt_tags().len(), 3);
+
+    let compound_tag = &nbt_data.nbt_tags()[0];
+    assert_eq!(compound_tag.name(), "Compound");
+    assert_eq!(compound_tag.value(), &NbtTagType::Compound("".to_string()));
+    //assert_eq!(compound_tag.position().children().len(), 1); TODO: Not implemented
+
+    let nested_int_tag = &nbt_data.nbt_tags()[1];
+    assert_eq!(nested_int_tag.name(), "Int");
+    assert_eq!(nested_int_tag.value(), &NbtTagType::Int(100));
+    assert_eq!(nested_int_tag.position().parent(), 0);
+
+    let end_tag = &nbt_data.nbt_tags()[2];
+    assert_eq!(end_tag.name(), "");
+    assert_eq!(end_tag.value(), &NbtTagType::End(None));
+    assert_eq!(end_tag.position().parent(), 0);
+}
+
+#[test]
+fn test_nbt_data_parse_list_of_strings() {
+    // Create a buffer representing a List tag containing Strings
+    // Structure:
+    // [Root Compound]
+    // [List Tag ID][Name Length][Name][List Element Type][List Length]
+    // [String 1]
+    // [String 2]
+    // ...
+    // [End Tag ID]
+
+    let mut buffer = Vec::new();
+
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&8u16.to_be_bytes()); // Name length
+    buffer.extend("Compound".as_bytes());
+
+    // List Tag
+    buffer.push(NbtTagId::List.into_u8());
+    buffer.extend(&4u16.to_be_bytes()); // Name length
+    buffer.extend("List".as_bytes());
+    buffer.push(NbtTagId::String.into_u8()); // List Element Type
+    buffer.extend(&2i32.to_be_bytes()); // List Length
+
+    // String 1
+    buffer.extend(&5u16.to_be_bytes()); // String length
+    buffer.extend("Hello".as_bytes());
+
+    // String 2
+    buffer.extend(&5u16.to_be_bytes()); // String length
+    buffer.extend("World".as_bytes());
+
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf(buffer).unwrap();
+    assert_eq!(nbt_data.nbt_tags().len(), 5);
+
+    let list_tag = &nbt_data.nbt_tags()[1];
+    assert_eq!(list_tag.name(), "List");
+    assert_eq!(list_tag.value(), &NbtTagType::List((NbtTagId::String, 2)));
+    //assert_eq!(list_tag.position().children().len(), 2); TODO: Not implemented
+
+    let string1 = &nbt_data.nbt_tags()[2];
+    assert_eq!(string1.value(), &NbtTagType::String("Hello".to_string()));
+    assert_eq!(string1.position().parent(), 1);
+
+    let string2 = &nbt_data.nbt_tags()[3];
+    assert_eq!(string2.value(), &NbtTagType::String("World".to_string()));
+    assert_eq!(string2.position().parent(), 1);
+}
+
+#[test]
+fn test_nbt_data_parse_error_context() {
+    // Root compound containing a list of 2 strings, but the second string is truncated
+    let mut buffer = Vec::new();
+
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&8u16.to_be_bytes()); // Name length
+    buffer.extend("Compound".as_bytes());
+
+    // List Tag
+    buffer.push(NbtTagId::List.into_u8());
+    buffer.extend(&4u16.to_be_bytes()); // Name length
+    buffer.extend("List".as_bytes());
+    buffer.push(NbtTagId::String.into_u8()); // List Element Type
+    buffer.extend(&2i32.to_be_bytes()); // List Length
+
+    // String 1
+    buffer.extend(&5u16.to_be_bytes()); // String length
+    buffer.extend("Hello".as_bytes());
+
+    // String 2, declared 5 bytes long but only 2 bytes are available
+    buffer.extend(&5u16.to_be_bytes()); // String length
+    buffer.extend("Wo".as_bytes());
+
+    let error = NbtData::from_buf(buffer).unwrap_err();
+    assert!(matches!(error.kind(), NbtReadError::Io(_)));
+
+    let context = error.context().unwrap();
+    assert_eq!(context.tag_start(), 30);
+    assert!(context.offset() >= context.tag_start());
+    assert_eq!(context.state(), ParseNbtFsmState::List);
+    assert_eq!(context.depth(), 2);
+    assert_eq!(
+        context.path(),
+        &vec!["Compound".to_string(), "List".to_string()]
+    );
+}
+
+#[test]
+fn test_nbt_data_parse_error_context_in_list_of_compounds() {
+    let mut buffer = Vec::new();
+
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&4u16.to_be_bytes()); // Name length
+    buffer.extend("Root".as_bytes());
+
+    // List of 2 compounds
+    buffer.push(NbtTagId::List.into_u8());
+    buffer.extend(&4u16.to_be_bytes()); // Name length
+    buffer.extend("List".as_bytes());
+    buffer.push(NbtTagId::Compound.into_u8()); // List Element Type
+    buffer.extend(&2i32.to_be_bytes()); // List Length
+
+    // first compound, empty
+    buffer.push(NbtTagId::End.into_u8());
+
+    // second compound with an invalid tag id
+    buffer.push(42);
+
+    let error = NbtData::from_buf(buffer).unwrap_err();
+    assert!(matches!(error.kind(), NbtReadError::InvalidContent));
+
+    let context = error.context().unwrap();
+    assert_eq!(context.offset(), 21);
+    assert_eq!(context.state(), ParseNbtFsmState::Normal);
+    assert_eq!(
+        context.path(),
+        &vec!["Root".to_string(), "List".to_string(), "[1]".to_string()]
+    );
+    assert!(error.to_string().contains("Root/List/[1]"));
+}
+
+fn little_endian_compound() -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&4u16.to_le_bytes()); // Name length
+    buffer.extend("Root".as_bytes());
+
+    // Int Tag
+    buffer.push(NbtTagId::Int.into_u8());
+    buffer.extend(&3u16.to_le_bytes()); // Name length
+    buffer.extend("Int".as_bytes());
+    buffer.extend(&0x01020304i32.to_le_bytes());
+
+    // List of two Shorts
+    buffer.push(NbtTagId::List.into_u8());
+    buffer.extend(&4u16.to_le_bytes()); // Name length
+    buffer.extend("List".as_bytes());
+    buffer.push(NbtTagId::Short.into_u8());
+    buffer.extend(&2i32.to_le_bytes()); // List length
+    buffer.extend(&(-2i16).to_le_bytes());
+    buffer.extend(&300i16.to_le_bytes());
+
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
+
+    buffer
+}
+
+#[test]
+fn test_nbt_data_parse_little_endian() {
+    let nbt_data =
+        NbtData::from_buf_with_options(little_endian_compound(), &ParseOptions::bedrock()).unwrap();
+
+    assert_eq!(nbt_data.options().encoding(), NbtEncoding::LittleEndian);
+    assert!(nbt_data.bedrock_header().is_none());
+    assert_eq!(nbt_data.nbt_tags()[0].name(), "Root");
+    assert_eq!(nbt_data.nbt_tags()[1].value(), &NbtTagType::Int(0x01020304));
+    assert_eq!(
+        nbt_data.nbt_tags()[2].value(),
+        &NbtTagType::List((NbtTagId::Short, 2))
+    );
+    assert_eq!(nbt_data.nbt_tags()[3].value(), &NbtTagType::Short(-2));
+    assert_eq!(nbt_data.nbt_tags()[4].value(), &NbtTagType::Short(300));
+
+    // the same bytes are not valid big endian NBT: the name length would be 0x0400
+    assert!(NbtData::from_buf(little_endian_compound()).is_err());
+
+    assert_eq!(nbt_data.to_bytes().unwrap(), little_endian_compound());
+}
+
+#[test]
+fn test_nbt_data_parse_bedrock_header() {
+    let payload = little_endian_compound();
+    let mut buffer = Vec::new();
+    buffer.extend(&10i32.to_le_bytes()); // storage version
+    buffer.extend(&(payload.len() as i32).to_le_bytes());
+    buffer.extend(&payload);
+
+    let nbt_data =
+        NbtData::from_buf_with_options(buffer.clone(), &ParseOptions::bedrock()).unwrap();
+
+    let header = nbt_data.bedrock_header().unwrap();
+    assert_eq!(header.storage_version(), 10);
+    assert_eq!(header.length() as usize, payload.len());
+
+    // positions are absolute in the buffer, including the header
+    let root = &nbt_data.nbt_tags()[0];
+    assert_eq!(root.position().byte_start_all(), 8);
+    assert_eq!(
+        root.position().byte_end_all_with_children(),
+        buffer.len() - 1
+    );
+
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+}
+
+#[test]
+fn test_nbt_data_bedrock_header_other_encodings() {
+    let mut buffer = Vec::new();
+    buffer.extend(&10i32.to_le_bytes()); // storage version
+    buffer.extend(&(little_endian_compound().len() as i32).to_le_bytes());
+    buffer.extend(&little_endian_compound());
+
+    let nbt_data = NbtData::from_buf_with_options(buffer, &ParseOptions::bedrock()).unwrap();
+
+    // the header only exists in front of little endian NBT
+    for options in [ParseOptions::default(), ParseOptions::bedrock_network()] {
+        let converted = nbt_data.to_bytes_with_options(&options).unwrap();
+        let reparsed = NbtData::from_buf_with_options(converted, &options).unwrap();
+
+        assert!(reparsed.bedrock_header().is_none());
+        assert_eq!(reparsed.nbt_tags()[0].name(), "Root");
+        assert_eq!(reparsed.nbt_tags()[1].value(), &NbtTagType::Int(0x01020304));
+        assert_eq!(reparsed.nbt_tags()[4].value(), &NbtTagType::Short(300));
     }
+}
+
+#[test]
+fn test_nbt_data_parse_network_positions() {
+    let mut buffer = Vec::new();
+
+    // Compound Tag, name length as VarInt
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.push(1);
+    buffer.extend("R".as_bytes());
+
+    // Int Tag with a 3 bytes zig-zag VarInt: 20000 => 40000
+    buffer.push(NbtTagId::Int.into_u8());
+    buffer.push(1);
+    buffer.extend("I".as_bytes());
+    buffer.extend([0xC0, 0xB8, 0x02]);
+
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data =
+        NbtData::from_buf_with_options(buffer.clone(), &ParseOptions::bedrock_network()).unwrap();
+
+    let int_tag = &nbt_data.nbt_tags()[1];
+    assert_eq!(int_tag.value(), &NbtTagType::Int(20000));
+    assert_eq!(int_tag.position().byte_start_name(), Some(4));
+    assert_eq!(int_tag.position().byte_end_name(), Some(5));
+    assert_eq!(int_tag.position().byte_start_value(), Some(6));
+    assert_eq!(int_tag.position().byte_end_value(), Some(8));
+
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+}
+
+#[test]
+fn test_nbt_data_parse_nameless_root() {
+    let mut buffer = Vec::new();
+
+    // Compound Tag without name
+    buffer.push(NbtTagId::Compound.into_u8());
+
+    // Byte Tag
+    buffer.push(NbtTagId::Byte.into_u8());
+    buffer.extend(&1u16.to_be_bytes());
+    buffer.extend("A".as_bytes());
+    buffer.push(7);
 
-    // Tests for NbtData
-    #[test]
-    fn test_nbt_data_new() {
-        let buffer = vec![];
-        let nbt_data = NbtData::new(buffer.clone());
-        assert!(nbt_data.nbt_tags().is_empty());
-        assert_eq!(nbt_data.raw_bytes(), &buffer);
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
+
+    // a named root would read 0x01 0x00 as name length and fail
+    assert!(NbtData::from_buf(buffer.clone()).is_err());
+
+    let options = ParseOptions::java_network();
+    assert_eq!(options.root_mode(), RootMode::Nameless);
+    let nbt_data = NbtData::from_buf_with_options(buffer.clone(), &options).unwrap();
+
+    let root = &nbt_data.nbt_tags()[0];
+    assert_eq!(root.name(), "");
+    assert_eq!(root.position().byte_start_name(), None);
+    assert_eq!(root.position().byte_end_name(), None);
+    assert_eq!(root.position().byte_start_value(), Some(1));
+
+    let byte_tag = &nbt_data.nbt_tags()[1];
+    assert_eq!(byte_tag.position().byte_start_all(), 1);
+    assert_eq!(byte_tag.value(), &NbtTagType::Byte(7));
+
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+
+    // the same tree can be written with a named (empty) root
+    let named = nbt_data
+        .to_bytes_with_options(&ParseOptions::default())
+        .unwrap();
+    let reparsed = NbtData::from_buf(named).unwrap();
+    assert_eq!(reparsed.nbt_tags()[1].value(), &NbtTagType::Byte(7));
+}
+
+#[test]
+fn test_nbt_data_parse_mutf8_strings() {
+    let mut buffer = Vec::new();
+
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
+
+    // String Tag with a cyrillic name and an emoji value encoded as surrogate pair
+    let name = "Имя".as_bytes();
+    buffer.push(NbtTagId::String.into_u8());
+    buffer.extend(&(name.len() as u16).to_be_bytes());
+    buffer.extend(name);
+    buffer.extend(&6u16.to_be_bytes());
+    buffer.extend([0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]);
+
+    // String Tag with a value that is not valid MUTF-8
+    buffer.push(NbtTagId::String.into_u8());
+    buffer.extend(&1u16.to_be_bytes());
+    buffer.extend("B".as_bytes());
+    buffer.extend(&2u16.to_be_bytes());
+    buffer.extend([0xFF, 0xFE]);
+
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf(buffer.clone()).unwrap();
+
+    let valid = &nbt_data.nbt_tags()[1];
+    assert_eq!(valid.name(), "Имя");
+    assert_eq!(valid.value(), &NbtTagType::String("\u{1F600}".to_string()));
+    assert!(!valid.has_invalid_name());
+    assert!(!valid.has_invalid_value());
+
+    let invalid = &nbt_data.nbt_tags()[2];
+    assert!(!invalid.has_invalid_name());
+    assert!(invalid.has_invalid_value());
+    assert_eq!(
+        invalid.value(),
+        &NbtTagType::String("\u{FF}\u{FE}".to_string())
+    );
+
+    // both the re-encoded and the raw strings give back the original bytes
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+}
+
+#[test]
+fn test_nbt_data_non_canonical_mutf8_round_trip() {
+    let mut buffer = Vec::new();
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
+
+    // a bare 0x00 and overlong forms decode to chars that MUTF-8 encodes differently,
+    // they must be kept as raw bytes to be written back unchanged
+    let non_canonical: [&[u8]; 3] = [&[0x00], &[0xC1, 0x81], &[0xE0, 0x80, 0x80]];
+    for raw in non_canonical {
+        buffer.push(NbtTagId::String.into_u8());
+        buffer.extend(&(raw.len() as u16).to_be_bytes());
+        buffer.extend(raw);
+        buffer.extend(&(raw.len() as u16).to_be_bytes());
+        buffer.extend(raw);
     }
+    buffer.push(NbtTagId::End.into_u8());
 
-    #[test]
-    fn test_nbt_data_from_buf_empty() {
-        let buffer = vec![];
-        let result = NbtData::from_buf(buffer);
-        assert!(result.is_err());
+    let nbt_data = NbtData::from_buf(buffer.clone()).unwrap();
+    for tag in &nbt_data.nbt_tags()[1..4] {
+        assert!(tag.has_invalid_name());
+        assert!(tag.has_invalid_value());
     }
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+}
 
-    #[test]
-    fn test_nbt_data_from_buf_invalid() {
-        let buffer = vec![255]; // Invalid tag id
-        let result = NbtData::from_buf(buffer);
-        assert!(result.is_err());
-        match result.err().unwrap() {
-            NbtReadError::InvalidContent => (),
-            _ => panic!("Expected InvalidContent error"),
-        }
+fn nested_compounds(depth: usize) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    for _ in 0..=depth {
+        buffer.push(NbtTagId::Compound.into_u8());
+        buffer.extend(&1u16.to_be_bytes());
+        buffer.extend("C".as_bytes());
     }
+    for _ in 0..=depth {
+        buffer.push(NbtTagId::End.into_u8());
+    }
+    buffer
+}
 
-    #[test]
-    fn test_nbt_data_parse_single_tag() {
-        // Create a buffer representing a single Int tag with name "Test" and value 42
-        // Tag structure:
-        // [Tag ID][Name Length][Name][Int Value]
-        // Tag ID: 3 (Int)
-        // Name Length: 4 (u16 big endian)
-        // Name: "Test"
-        // Int Value: 42 (i32 big endian)
+#[test]
+fn test_nbt_data_parse_max_depth() {
+    let mut options = ParseOptions::default();
+    assert_eq!(options.max_depth(), 512);
+    options.set_max_depth(3);
 
-        let mut buffer = Vec::new();
+    // the compounds are at depth 0 to 3, the last End tag at depth 4
+    assert!(NbtData::from_buf_with_options(nested_compounds(2), &options).is_ok());
 
-        // Compound Tag
-        buffer.push(NbtTagId::Compound.into_u8());
-        buffer.extend(&8u16.to_be_bytes()); // Name length
-        buffer.extend("Compound".as_bytes());
+    let error = NbtData::from_buf_with_options(nested_compounds(3), &options).unwrap_err();
+    assert!(matches!(error.kind(), NbtReadError::MaxDepthExceeded(3)));
+}
 
-        buffer.push(NbtTagId::Int.into_u8());
+#[test]
+fn test_nbt_data_parse_list_longer_than_strict_limit() {
+    let mut buffer = Vec::new();
 
-        // Name length: 4
-        buffer.extend(&4u16.to_be_bytes());
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
 
-        // Name: "Test"
-        buffer.extend("Test".as_bytes());
+    // List of 40000 Bytes, longer than the old hard-coded limit
+    buffer.push(NbtTagId::List.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
+    buffer.push(NbtTagId::Byte.into_u8());
+    buffer.extend(&40000i32.to_be_bytes());
+    buffer.extend(vec![1u8; 40000]);
 
-        // Int value: 42
-        buffer.extend(&42i32.to_be_bytes());
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
 
-        // End Tag
-        buffer.push(NbtTagId::End.into_u8());
+    let nbt_data = NbtData::from_buf(buffer.clone()).unwrap();
+    assert_eq!(nbt_data.nbt_tags().len(), 40003);
 
-        let nbt_data = NbtData::from_buf(buffer).unwrap();
-        assert_eq!(nbt_data.nbt_tags().len(), 3);
-        let tag = &nbt_data.nbt_tags()[1];
-        assert_eq!(tag.name(), "Test");
-        assert_eq!(tag.value(), &NbtTagType::Int(42));
-    }
+    let error = NbtData::from_buf_with_options(buffer, &ParseOptions::strict()).unwrap_err();
+    assert!(matches!(error.kind(), NbtReadError::InvalidNbtListLenght));
+}
 
-    #[test]
-    fn test_nbt_data_parse_multiple_tags() {
-        // Create a buffer representing two tags:
-        // 1. Byte tag with name "A" and value 1
-        // 2. String tag with name "B" and value "Hello"
+#[test]
+fn test_nbt_data_parse_allocation_budget() {
+    let mut buffer = Vec::new();
+
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
+
+    // LongArray Tag of 100 elements: 800 bytes once decoded
+    buffer.push(NbtTagId::LongArray.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
+    buffer.extend(&100i32.to_be_bytes());
+    buffer.extend(vec![0u8; 800]);
+
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
+
+    let mut options = ParseOptions::default();
+    options.set_max_allocation(4096);
+    assert!(NbtData::from_buf_with_options(buffer.clone(), &options).is_ok());
+
+    // rejected before the array is allocated
+    options.set_max_allocation(799);
+    let error = NbtData::from_buf_with_options(buffer.clone(), &options).unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        NbtReadError::AllocationBudgetExceeded(799)
+    ));
+
+    // the array itself fits, but not together with the tags around it
+    options.set_max_allocation(800);
+    let error = NbtData::from_buf_with_options(buffer, &options).unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        NbtReadError::AllocationBudgetExceeded(800)
+    ));
+}
 
-        let mut buffer = Vec::new();
+#[test]
+fn test_nbt_data_parse_allocation_budget_counts_every_tag() {
+    let mut buffer = Vec::new();
+
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
+
+    // List Tag of 30000 empty Compounds: one byte each, but two NbtTags each once parsed
+    buffer.push(NbtTagId::List.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&30_000i32.to_be_bytes());
+    buffer.extend(vec![NbtTagId::End.into_u8(); 30_000]);
+
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
+
+    assert!(NbtData::from_buf(buffer.clone()).is_ok());
+
+    // 30 KiB of input, but far more than 1 MiB of NbtTags
+    let mut options = ParseOptions::default();
+    options.set_max_allocation(1024 * 1024);
+    let error = NbtData::from_buf_with_options(buffer, &options).unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        NbtReadError::AllocationBudgetExceeded(_)
+    ));
+}
 
-        // Compound Tag
-        buffer.push(NbtTagId::Compound.into_u8());
-        buffer.extend(&8u16.to_be_bytes()); // Name length
-        buffer.extend("Compound".as_bytes());
+#[test]
+fn test_nbt_data_parse_non_empty_list_of_end_tags() {
+    let mut buffer = Vec::new();
 
-        // First Tag: Byte
-        buffer.push(NbtTagId::Byte.into_u8());
-        buffer.extend(&1u16.to_be_bytes()); // Name length
-        buffer.extend("A".as_bytes());
-        buffer.push(1i8 as u8); // Byte value
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
 
-        // Second Tag: String
-        buffer.push(NbtTagId::String.into_u8());
-        buffer.extend(&1u16.to_be_bytes()); // Name length
-        buffer.extend("B".as_bytes());
-        buffer.extend(&5u16.to_be_bytes()); // String length
-        buffer.extend("Hello".as_bytes());
+    // List Tag of End Tags declaring elements which take no bytes
+    buffer.push(NbtTagId::List.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
+    buffer.push(NbtTagId::End.into_u8());
+    buffer.extend(&i32::MAX.to_be_bytes());
 
-        // End Tag
-        buffer.push(NbtTagId::End.into_u8());
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
 
-        let nbt_data = NbtData::from_buf(buffer).unwrap();
-        assert_eq!(nbt_data.nbt_tags().len(), 4);
+    let error = NbtData::from_buf(buffer).unwrap_err();
+    assert!(matches!(error.kind(), NbtReadError::InvalidNbtListLenght));
+}
 
-        let tag1 = &nbt_data.nbt_tags()[1];
-        assert_eq!(tag1.name(), "A");
-        assert_eq!(tag1.value(), &NbtTagType::Byte(1));
+#[test]
+fn test_nbt_data_parse_negative_array_length() {
+    let mut buffer = Vec::new();
 
-        let tag2 = &nbt_data.nbt_tags()[2];
-        assert_eq!(tag2.name(), "B");
-        assert_eq!(tag2.value(), &NbtTagType::String("Hello".to_string()));
-    }
+    // Compound Tag
+    buffer.push(NbtTagId::Compound.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
 
-    #[test]
-    fn test_nbt_data_parse_nested_compound() {
-        // Create a buffer representing a Compound tag containing an Int tag
-        // Structure:
-        // Compound Tag:
-        // [Tag ID][Name Length][Name]
-        // Inside Compound:
-        // [Int Tag ID][Name Length][Name][Int Value]
-        // [End Tag ID]
+    // IntArray Tag with a negative length
+    buffer.push(NbtTagId::IntArray.into_u8());
+    buffer.extend(&0u16.to_be_bytes());
+    buffer.extend(&(-1i32).to_be_bytes());
 
-        let mut buffer = Vec::new();
+    // End Tag
+    buffer.push(NbtTagId::End.into_u8());
 
-        // Compound Tag
-        buffer.push(NbtTagId::Compound.into_u8());
-        buffer.extend(&8u16.to_be_bytes()); // Name length
-        buffer.extend("Compound".as_bytes());
+    let error = NbtData::from_buf(buffer).unwrap_err();
+    assert!(matches!(error.kind(), NbtReadError::NegativeNbtTagLenght));
+}
+
+/// Check the invariants of a parsed NbtTag tree: parents, children and depths agree,
+/// and the byte ranges of the NbtTags cover the whole buffer without gaps
+fn assert_consistent_tree(nbt_data: &NbtData) {
+    let tags = nbt_data.nbt_tags();
+    let mut next_byte = tags[0].position().byte_start_all();
+
+    for (index, tag) in tags.iter().enumerate() {
+        assert_eq!(tag.position().index(), index);
+        assert_eq!(tag.position().byte_start_all(), next_byte, "tag {}", index);
+        next_byte = tag.position().byte_end_all() + 1;
+
+        let parent = &tags[tag.position().parent()];
+        // roots are their own parent
+        if tag.position().parent() == index {
+            assert_eq!(tag.position().depth(), 0);
+        } else {
+            assert!(parent.position().children.contains(&index), "tag {}", index);
+            assert_eq!(tag.position().depth(), parent.position().depth() + 1);
+        }
+
+        for &child in tag.position().children.iter() {
+            assert_eq!(tags[child].position().parent(), index);
+            assert!(
+                tags[child].position().byte_end_all_with_children()
+                    <= tag.position().byte_end_all_with_children()
+            );
+        }
+    }
 
-        // Nested Int Tag
-        buffer.push(NbtTagId::Int.into_u8());
-        buffer.extend(&3u16.to_be_bytes()); // Name length
-        buffer.extend("Int".as_bytes());
-        buffer.extend(&100i32.to_be_bytes()); // Int value
+    assert_eq!(next_byte, nbt_data.raw_bytes().len());
+    let last_root = *nbt_data.roots().last().unwrap();
+    assert_eq!(
+        tags[last_root].position().byte_end_all_with_children(),
+        nbt_data.raw_bytes().len() - 1
+    );
+}
+
+#[test]
+fn test_nbt_data_parse_list_of_lists() {
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+
+    // [[1, 2], [3]]
+    named_tag_header(&mut buffer, NbtTagId::List, "Lists");
+    list_header(&mut buffer, NbtTagId::List, 2);
+    list_header(&mut buffer, NbtTagId::Int, 2);
+    buffer.extend(&1i32.to_be_bytes());
+    buffer.extend(&2i32.to_be_bytes());
+    list_header(&mut buffer, NbtTagId::Int, 1);
+    buffer.extend(&3i32.to_be_bytes());
+
+    named_tag_header(&mut buffer, NbtTagId::Byte, "After");
+    buffer.push(9);
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf(buffer.clone()).unwrap();
+    assert_consistent_tree(&nbt_data);
+
+    let tags = nbt_data.nbt_tags();
+    assert_eq!(tags.len(), 9);
+    assert_eq!(tags[1].position().children, vec![2, 5]);
+    assert_eq!(tags[2].value(), &NbtTagType::List((NbtTagId::Int, 2)));
+    assert_eq!(tags[2].position().children, vec![3, 4]);
+    assert_eq!(tags[4].value(), &NbtTagType::Int(2));
+    assert_eq!(tags[4].position().depth(), 3);
+    assert_eq!(tags[6].value(), &NbtTagType::Int(3));
+    assert_eq!(tags[6].position().parent(), 5);
+    assert_eq!(tags[7].name(), "After");
+    assert_eq!(tags[7].position().parent(), 0);
+    assert_eq!(tags[7].position().depth(), 1);
+
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+}
+
+#[test]
+fn test_nbt_data_parse_list_of_arrays() {
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+
+    named_tag_header(&mut buffer, NbtTagId::List, "IntArrays");
+    list_header(&mut buffer, NbtTagId::IntArray, 2);
+    buffer.extend(&2i32.to_be_bytes());
+    buffer.extend(&1i32.to_be_bytes());
+    buffer.extend(&2i32.to_be_bytes());
+    buffer.extend(&0i32.to_be_bytes());
+
+    named_tag_header(&mut buffer, NbtTagId::List, "LongArrays");
+    list_header(&mut buffer, NbtTagId::LongArray, 1);
+    buffer.extend(&1i32.to_be_bytes());
+    buffer.extend(&7i64.to_be_bytes());
+
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf(buffer.clone()).unwrap();
+    assert_consistent_tree(&nbt_data);
+
+    let tags = nbt_data.nbt_tags();
+    assert_eq!(tags[2].value(), &NbtTagType::IntArray(vec![1, 2]));
+    assert_eq!(tags[2].position().byte_start_value(), Some(24));
+    assert_eq!(tags[2].position().byte_end_value(), Some(35));
+    assert_eq!(tags[3].value(), &NbtTagType::IntArray(vec![]));
+    assert_eq!(tags[5].value(), &NbtTagType::LongArray(vec![7]));
+    assert_eq!(tags[5].position().parent(), 4);
+
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+}
 
-        // End Tag
+#[test]
+fn test_nbt_data_parse_list_of_compounds_of_lists_of_compounds() {
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+
+    // two compounds, each holding a list of one compound, the last ones end together
+    named_tag_header(&mut buffer, NbtTagId::List, "Outer");
+    list_header(&mut buffer, NbtTagId::Compound, 2);
+    for value in [1i8, 2] {
+        named_tag_header(&mut buffer, NbtTagId::List, "Inner");
+        list_header(&mut buffer, NbtTagId::Compound, 1);
+        named_tag_header(&mut buffer, NbtTagId::Byte, "Value");
+        buffer.push(value as u8);
         buffer.push(NbtTagId::End.into_u8());
+        buffer.push(NbtTagId::End.into_u8());
+    }
+
+    named_tag_header(&mut buffer, NbtTagId::Byte, "After");
+    buffer.push(9);
+    buffer.push(NbtTagId::End.into_u8());
 
-        let nbt_data = NbtData::from_buf(buffer).unwrap();
-        assert_eq!(nbt_data.nbt_tags().len(), 3);
+    let nbt_data = NbtData::from_buf(buffer.clone()).unwrap();
+    assert_consistent_tree(&nbt_data);
 
-        let compound_tag = &nbt_data.nbt_tags()[0];
-        assert_eq!(compound_tag.name(), "Compound");
-        assert_eq!(compound_tag.value(), &NbtTagType::Compound("".to_string()));
-        //assert_eq!(compound_tag.position().children().len(), 1); TODO: Not implemented
+    let tags = nbt_data.nbt_tags();
+    let values: Vec<(usize, i64)> = tags
+        .iter()
+        .filter(|tag| tag.name() == "Value")
+        .map(|tag| (tag.position().index(), tag.position().depth()))
+        .collect();
+    assert_eq!(values, vec![(5, 5), (11, 5)]);
 
-        let nested_int_tag = &nbt_data.nbt_tags()[1];
-        assert_eq!(nested_int_tag.name(), "Int");
-        assert_eq!(nested_int_tag.value(), &NbtTagType::Int(100));
-        assert_eq!(nested_int_tag.position().parent(), 0);
+    let after = tags.iter().find(|tag| tag.name() == "After").unwrap();
+    assert_eq!(after.position().parent(), 0);
+    assert_eq!(after.position().depth(), 1);
+    assert_eq!(tags[1].position().children.len(), 2);
+
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+}
 
-        let end_tag = &nbt_data.nbt_tags()[2];
-        assert_eq!(end_tag.name(), "");
-        assert_eq!(end_tag.value(), &NbtTagType::End(None));
-        assert_eq!(end_tag.position().parent(), 0);
+#[test]
+fn test_nbt_data_parse_deeply_nested_lists() {
+    // a compound holding 20 nested lists of lists, the innermost holds one compound
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+    named_tag_header(&mut buffer, NbtTagId::List, "Nested");
+    for _ in 0..20 {
+        list_header(&mut buffer, NbtTagId::List, 1);
     }
+    list_header(&mut buffer, NbtTagId::Compound, 1);
+    named_tag_header(&mut buffer, NbtTagId::String, "Leaf");
+    buffer.extend(&2u16.to_be_bytes());
+    buffer.extend("ok".as_bytes());
+    buffer.push(NbtTagId::End.into_u8());
+    named_tag_header(&mut buffer, NbtTagId::Byte, "After");
+    buffer.push(9);
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf(buffer.clone()).unwrap();
+    assert_consistent_tree(&nbt_data);
+
+    let leaf = nbt_data
+        .nbt_tags()
+        .iter()
+        .find(|tag| tag.name() == "Leaf")
+        .unwrap();
+    assert_eq!(leaf.position().depth(), 23);
+    let after = nbt_data.nbt_tags().last().unwrap();
+    assert_eq!(after.value(), &NbtTagType::End(None));
+    assert_eq!(after.position().parent(), 0);
+
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+}
 
-    #[test]
-    fn test_nbt_data_parse_list_of_strings() {
-        // Create a buffer representing a List tag containing Strings
-        // Structure:
-        // [Root Compound]
-        // [List Tag ID][Name Length][Name][List Element Type][List Length]
-        // [String 1]
-        // [String 2]
-        // ...
-        // [End Tag ID]
+#[test]
+fn test_nbt_data_parse_empty_lists() {
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+
+    // vanilla writes empty lists with the End tag type
+    named_tag_header(&mut buffer, NbtTagId::List, "Passengers");
+    list_header(&mut buffer, NbtTagId::End, 0);
+
+    // an empty list of ints must not read the next sibling as its element
+    named_tag_header(&mut buffer, NbtTagId::List, "Tags");
+    list_header(&mut buffer, NbtTagId::Int, 0);
+
+    named_tag_header(&mut buffer, NbtTagId::List, "Negative");
+    list_header(&mut buffer, NbtTagId::Compound, -1);
+
+    named_tag_header(&mut buffer, NbtTagId::IntArray, "EmptyArray");
+    buffer.extend(&0i32.to_be_bytes());
+
+    named_tag_header(&mut buffer, NbtTagId::Byte, "After");
+    buffer.push(9);
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf(buffer.clone()).unwrap();
+    assert_consistent_tree(&nbt_data);
+
+    let tags = nbt_data.nbt_tags();
+    assert_eq!(tags.len(), 7);
+    // the root is its own parent, so it is also its first child
+    assert_eq!(tags[0].position().children, vec![0, 1, 2, 3, 4, 5, 6]);
+    for list in &tags[1..=3] {
+        assert!(list.position().children.is_empty());
+        assert_eq!(list.position().depth(), 1);
+        assert_eq!(
+            list.position().byte_end_all_with_children(),
+            list.position().byte_end_all()
+        );
+    }
+    assert_eq!(tags[4].value(), &NbtTagType::IntArray(vec![]));
+    assert_eq!(tags[5].name(), "After");
+    assert_eq!(tags[5].value(), &NbtTagType::Byte(9));
 
-        let mut buffer = Vec::new();
+    let json = serde_json::to_value(tags).unwrap();
+    assert_eq!(json[2]["position"]["children"], serde_json::json!([]));
 
-        // Compound Tag
-        buffer.push(NbtTagId::Compound.into_u8());
-        buffer.extend(&8u16.to_be_bytes()); // Name length
-        buffer.extend("Compound".as_bytes());
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+}
 
-        // List Tag
-        buffer.push(NbtTagId::List.into_u8());
-        buffer.extend(&4u16.to_be_bytes()); // Name length
-        buffer.extend("List".as_bytes());
-        buffer.push(NbtTagId::String.into_u8()); // List Element Type
-        buffer.extend(&2i32.to_be_bytes()); // List Length
+#[test]
+fn test_nbt_data_parse_empty_lists_as_list_elements() {
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+
+    // [[], [1], []]
+    named_tag_header(&mut buffer, NbtTagId::List, "Lists");
+    list_header(&mut buffer, NbtTagId::List, 3);
+    list_header(&mut buffer, NbtTagId::End, 0);
+    list_header(&mut buffer, NbtTagId::Int, 1);
+    buffer.extend(&1i32.to_be_bytes());
+    list_header(&mut buffer, NbtTagId::Int, 0);
+
+    // a compound in a list which holds an empty list as last tag
+    named_tag_header(&mut buffer, NbtTagId::List, "Compounds");
+    list_header(&mut buffer, NbtTagId::Compound, 1);
+    named_tag_header(&mut buffer, NbtTagId::List, "Empty");
+    list_header(&mut buffer, NbtTagId::End, 0);
+    buffer.push(NbtTagId::End.into_u8());
+
+    named_tag_header(&mut buffer, NbtTagId::Byte, "After");
+    buffer.push(9);
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf(buffer.clone()).unwrap();
+    assert_consistent_tree(&nbt_data);
+
+    let tags = nbt_data.nbt_tags();
+    assert_eq!(tags[1].position().children, vec![2, 3, 5]);
+    assert!(tags[2].position().children.is_empty());
+    assert_eq!(tags[4].value(), &NbtTagType::Int(1));
+    assert!(tags[5].position().children.is_empty());
+
+    let after = tags.iter().find(|tag| tag.name() == "After").unwrap();
+    assert_eq!(after.position().parent(), 0);
+    assert_eq!(after.value(), &NbtTagType::Byte(9));
+
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+}
 
-        // String 1
-        buffer.extend(&5u16.to_be_bytes()); // String length
-        buffer.extend("Hello".as_bytes());
+#[test]
+fn test_nbt_data_parse_any_root_tag() {
+    let mut options = ParseOptions::default();
+    assert!(!options.any_root_tag());
+    options.set_any_root_tag(true);
+
+    // root String
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::String, "Text");
+    buffer.extend(&2u16.to_be_bytes());
+    buffer.extend("hi".as_bytes());
+
+    let error = NbtData::from_buf(buffer.clone()).unwrap_err();
+    assert!(matches!(error.kind(), NbtReadError::InvalidNbtRootTagId));
+
+    let nbt_data = NbtData::from_buf_with_options(buffer.clone(), &options).unwrap();
+    assert_consistent_tree(&nbt_data);
+    assert_eq!(nbt_data.nbt_tags().len(), 1);
+    assert_eq!(
+        nbt_data.nbt_tags()[0].value(),
+        &NbtTagType::String("hi".to_string())
+    );
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+
+    // root List of compounds
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::List, "");
+    list_header(&mut buffer, NbtTagId::Compound, 2);
+    named_tag_header(&mut buffer, NbtTagId::Byte, "A");
+    buffer.push(1);
+    buffer.push(NbtTagId::End.into_u8());
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf_with_options(buffer.clone(), &options).unwrap();
+    assert_consistent_tree(&nbt_data);
+    let tags = nbt_data.nbt_tags();
+    assert_eq!(tags.len(), 6);
+    assert_eq!(tags[4].value(), &NbtTagType::Compound("".to_string()));
+    assert_eq!(tags[4].position().parent(), 0);
+    assert_eq!(tags[5].position().depth(), 2);
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+
+    // root empty List and root End, as written by Java for an empty NBT
+    for buffer in [vec![9, 0, 0, 0, 0, 0, 0, 0], vec![0]] {
+        let nbt_data = NbtData::from_buf_with_options(buffer.clone(), &options).unwrap();
+        assert_eq!(nbt_data.nbt_tags().len(), 1);
+        assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+    }
+}
 
-        // String 2
-        buffer.extend(&5u16.to_be_bytes()); // String length
-        buffer.extend("World".as_bytes());
+// Additional tests can be added here to cover more scenarios, such as:
+// - Parsing ByteArray, IntArray, LongArray
+// - Parsing nested compounds
+// - Handling maximum depth
+// - Error scenarios for incomplete data, etc.
 
-        // End Tag
-        buffer.push(NbtTagId::End.into_u8());
+fn recovery_options() -> ParseOptions {
+    let mut options = ParseOptions::default();
+    options.set_recover_errors(true);
+    options
+}
 
-        let nbt_data = NbtData::from_buf(buffer).unwrap();
-        assert_eq!(nbt_data.nbt_tags().len(), 5);
+#[test]
+fn test_nbt_data_recover_corrupted_tag_id() {
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+    named_tag_header(&mut buffer, NbtTagId::Int, "a");
+    buffer.extend(&1i32.to_be_bytes());
+    // bytes 15 and 16 are garbage
+    buffer.extend(&[0xEE, 0x01]);
+    named_tag_header(&mut buffer, NbtTagId::Int, "c");
+    buffer.extend(&3i32.to_be_bytes());
+    buffer.push(NbtTagId::End.into_u8());
+
+    assert!(NbtData::from_buf(buffer.clone()).is_err());
+
+    let nbt_data = NbtData::from_buf_with_options(buffer, &recovery_options()).unwrap();
+    let names: Vec<&str> = nbt_data.nbt_tags().iter().map(|tag| tag.name()).collect();
+    assert_eq!(names, vec!["Root", "a", "c", ""]);
+    assert_eq!(nbt_data.nbt_tags()[2].value(), &NbtTagType::Int(3));
+    assert_eq!(nbt_data.nbt_tags()[2].position().byte_start_all(), 17);
+    assert_eq!(nbt_data.nbt_tags()[2].position().parent(), 0);
+    assert_eq!(nbt_data.nbt_tags()[0].position().children.len(), 4);
+
+    let diagnostics = nbt_data.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message(), "Invalid NBT Tag Id");
+    assert_eq!(diagnostics[0].context().tag_start(), 15);
+    assert_eq!(diagnostics[0].context().path(), &vec!["Root".to_string()]);
+    assert_eq!(diagnostics[0].resync_offset(), Some(17));
+}
+
+#[test]
+fn test_nbt_data_recover_truncated_file() {
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+    named_tag_header(&mut buffer, NbtTagId::Int, "a");
+    buffer.extend(&1i32.to_be_bytes());
+    named_tag_header(&mut buffer, NbtTagId::Int, "b");
+    buffer.extend(&[0x00, 0x00]);
+
+    let nbt_data = NbtData::from_buf_with_options(buffer, &recovery_options()).unwrap();
+    assert_eq!(nbt_data.nbt_tags().len(), 2);
+    assert_eq!(nbt_data.nbt_tags()[1].value(), &NbtTagType::Int(1));
+
+    let diagnostics = nbt_data.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].message().starts_with("I/O error"));
+    assert_eq!(diagnostics[0].context().tag_start(), 15);
+    assert_eq!(diagnostics[0].resync_offset(), None);
+    assert!(diagnostics[0].to_string().ends_with("parsing stopped"));
+}
+
+#[test]
+fn test_nbt_data_recover_inside_list() {
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+    named_tag_header(&mut buffer, NbtTagId::List, "Strings");
+    list_header(&mut buffer, NbtTagId::String, 2);
+    buffer.extend(&2u16.to_be_bytes());
+    buffer.extend(b"ok");
+    // the second string declares more bytes than the file has
+    buffer.extend(&0x7000u16.to_be_bytes());
+    let resync_offset = buffer.len();
+    named_tag_header(&mut buffer, NbtTagId::Byte, "After");
+    buffer.push(9);
+    buffer.push(NbtTagId::End.into_u8());
+
+    let nbt_data = NbtData::from_buf_with_options(buffer, &recovery_options()).unwrap();
+    let names: Vec<&str> = nbt_data.nbt_tags().iter().map(|tag| tag.name()).collect();
+    assert_eq!(names, vec!["Root", "Strings", "", "After", ""]);
+
+    // the unfinished list is dropped, the next tag is a child of the compound around it
+    let after = &nbt_data.nbt_tags()[3];
+    assert_eq!(after.value(), &NbtTagType::Byte(9));
+    assert_eq!(after.position().parent(), 0);
+    assert_eq!(after.position().depth(), 1);
+    assert_eq!(nbt_data.diagnostics().len(), 1);
+    assert_eq!(
+        nbt_data.diagnostics()[0].resync_offset(),
+        Some(resync_offset)
+    );
+}
+
+#[test]
+fn test_nbt_data_recover_invalid_root() {
+    let buffer = vec![NbtTagId::Int.into_u8(), 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+    let nbt_data = NbtData::from_buf_with_options(buffer, &recovery_options()).unwrap();
+    assert!(nbt_data.nbt_tags().is_empty());
+    assert_eq!(nbt_data.diagnostics().len(), 1);
+    assert_eq!(nbt_data.diagnostics()[0].resync_offset(), None);
+}
+
+fn small_root(buffer: &mut Vec<u8>, name: &str, value: i32) {
+    named_tag_header(buffer, NbtTagId::Compound, name);
+    named_tag_header(buffer, NbtTagId::Int, "Value");
+    buffer.extend(&value.to_be_bytes());
+    buffer.push(NbtTagId::End.into_u8());
+}
+
+#[test]
+fn test_nbt_data_parse_trailer() {
+    let mut buffer = Vec::new();
+    small_root(&mut buffer, "Root", 1);
+    let root_len = buffer.len();
+    buffer.extend(&[0x00, 0x00, 0xAB]);
+
+    let nbt_data = NbtData::from_buf(buffer.clone()).unwrap();
+    assert_eq!(nbt_data.nbt_tags().len(), 3);
+    assert_eq!(nbt_data.roots(), vec![0]);
 
-        let list_tag = &nbt_data.nbt_tags()[1];
-        assert_eq!(list_tag.name(), "List");
-        assert_eq!(list_tag.value(), &NbtTagType::List((NbtTagId::String, 2)));
-        //assert_eq!(list_tag.position().children().len(), 2); TODO: Not implemented
+    let trailer = nbt_data.trailer().unwrap();
+    assert_eq!(trailer.byte_start(), root_len);
+    assert_eq!(trailer.byte_end(), root_len + 2);
 
-        let string1 = &nbt_data.nbt_tags()[2];
-        assert_eq!(string1.value(), &NbtTagType::String("Hello".to_string()));
-        assert_eq!(string1.position().parent(), 1);
+    // the trailer is written back as it is
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+
+    let mut buffer = Vec::new();
+    small_root(&mut buffer, "Root", 1);
+    assert_eq!(NbtData::from_buf(buffer).unwrap().trailer(), None);
+}
+
+#[test]
+fn test_nbt_data_parse_multiple_roots() {
+    let mut buffer = Vec::new();
+    small_root(&mut buffer, "First", 1);
+    small_root(&mut buffer, "Second", 2);
+
+    // without the option the second root is a trailer
+    let nbt_data = NbtData::from_buf(buffer.clone()).unwrap();
+    assert_eq!(nbt_data.roots(), vec![0]);
+    assert!(nbt_data.trailer().is_some());
+
+    let mut options = ParseOptions::default();
+    options.set_multiple_roots(true);
+    let nbt_data = NbtData::from_buf_with_options(buffer.clone(), &options).unwrap();
+    assert_consistent_tree(&nbt_data);
+    assert_eq!(nbt_data.roots(), vec![0, 3]);
+    assert_eq!(nbt_data.trailer(), None);
+
+    let second = &nbt_data.nbt_tags()[3];
+    assert_eq!(second.name(), "Second");
+    assert_eq!(second.position().depth(), 0);
+    assert_eq!(nbt_data.nbt_tags()[4].value(), &NbtTagType::Int(2));
+    assert_eq!(nbt_data.nbt_tags()[4].position().parent(), 3);
+
+    assert_eq!(nbt_data.to_bytes().unwrap(), buffer);
+}
+
+#[test]
+fn test_nbt_data_from_slice_and_reader() {
+    let mut buffer = Vec::new();
+    small_root(&mut buffer, "Root", 7);
+
+    let from_buf = NbtData::from_buf(buffer.clone()).unwrap();
+    let from_slice = NbtData::from_slice(&buffer).unwrap();
+    let from_reader = NbtData::from_reader(std::io::Cursor::new(&buffer)).unwrap();
+    assert_eq!(from_slice, from_buf);
+    assert_eq!(from_reader, from_buf);
+
+    // the raw bytes are given back after parsing
+    assert_eq!(from_buf.raw_bytes(), &buffer);
+    assert!(matches!(
+        NbtData::from_reader(std::io::empty()),
+        Err(NbtReadError::EmptyFile)
+    ));
+}
 
-        let string2 = &nbt_data.nbt_tags()[3];
-        assert_eq!(string2.value(), &NbtTagType::String("World".to_string()));
-        assert_eq!(string2.position().parent(), 1);
+#[test]
+fn test_nbt_data_parse_lazy_values() {
+    let mut buffer = Vec::new();
+    named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+    named_tag_header(&mut buffer, NbtTagId::String, "Name");
+    buffer.extend(&5u16.to_be_bytes());
+    buffer.extend(b"Steve");
+    named_tag_header(&mut buffer, NbtTagId::IntArray, "Ints");
+    buffer.extend(&2i32.to_be_bytes());
+    buffer.extend(&7i32.to_be_bytes());
+    buffer.extend(&(-1i32).to_be_bytes());
+    named_tag_header(&mut buffer, NbtTagId::Byte, "Flag");
+    buffer.push(1);
+    buffer.push(NbtTagId::End.into_u8());
+
+    let eager = NbtData::from_buf(buffer.clone()).unwrap();
+    let mut options = ParseOptions::default();
+    options.set_lazy_values(true);
+    let mut lazy = NbtData::from_buf_with_options(buffer.clone(), &options).unwrap();
+    assert_consistent_tree(&lazy);
+
+    // the index is the same, only string and array payloads are skipped
+    let ints = &lazy.nbt_tags()[2];
+    assert!(ints.has_lazy_value());
+    assert_eq!(ints.value(), &NbtTagType::Lazy(NbtTagId::IntArray));
+    assert!(!lazy.nbt_tags()[3].has_lazy_value());
+    assert_eq!(lazy.nbt_tags()[3].value(), &NbtTagType::Byte(1));
+    for (lazy_tag, eager_tag) in lazy.nbt_tags().iter().zip(eager.nbt_tags()) {
+        assert_eq!(lazy_tag.position(), eager_tag.position());
     }
 
-    // Additional tests can be added here to cover more scenarios, such as:
-    // - Parsing ByteArray, IntArray, LongArray
-    // - Parsing nested compounds
-    // - Handling maximum depth
-    // - Error scenarios for incomplete data, etc.
+    assert_eq!(
+        lazy.tag_value(1).unwrap().as_ref(),
+        &NbtTagType::String("Steve".to_string())
+    );
+    assert_eq!(
+        lazy.tag_value(2).unwrap().as_ref(),
+        &NbtTagType::IntArray(vec![7, -1])
+    );
+
+    // skipped payloads are copied as they are, or decoded to change the encoding
+    assert_eq!(lazy.to_bytes().unwrap(), buffer);
+    let little_endian = ParseOptions::bedrock();
+    assert_eq!(
+        lazy.to_bytes_with_options(&little_endian).unwrap(),
+        eager.to_bytes_with_options(&little_endian).unwrap()
+    );
+
+    // a lazy value can be replaced, but Lazy is not a value which can be set
+    let mut edited = lazy.clone();
+    assert!(matches!(
+        edited.set_value(2, NbtTagType::Lazy(NbtTagId::IntArray)),
+        Err(NbtEditError::LazyValue)
+    ));
+    edited
+        .patch_value(2, NbtTagType::IntArray(vec![8, -1]))
+        .unwrap();
+    assert_eq!(
+        edited.tag_value(2).unwrap().as_ref(),
+        &NbtTagType::IntArray(vec![8, -1])
+    );
+
+    lazy.decode_values().unwrap();
+    assert_eq!(lazy.nbt_tags(), eager.nbt_tags());
 }
@@ -0,0 +1,906 @@
+use serde::{Deserialize, Serialize};
+
+use crate::nbt::write;
+use crate::nbt::{
+    NbtData, NbtEditError, NbtEncoding, NbtReadError, NbtTagId, NbtTagType, NbtWriteError,
+    ParseOptions, RootMode,
+};
+
+/// Owned NBT value, with the children of compounds and lists inside their parent.
+///
+/// Easier to build and walk than the flat NbtTag tree of [`NbtData`], but without byte positions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NbtValue {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// A string which was not valid MUTF-8 (UTF-8 for Bedrock), kept byte by byte like
+    /// [`NbtTag::has_invalid_value`](crate::nbt::NbtTag::has_invalid_value): every char is one of the original bytes
+    RawString(String),
+    List(NbtList),
+    Compound(NbtCompound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl NbtValue {
+    pub fn tag_id(&self) -> NbtTagId {
+        match self {
+            NbtValue::Byte(_) => NbtTagId::Byte,
+            NbtValue::Short(_) => NbtTagId::Short,
+            NbtValue::Int(_) => NbtTagId::Int,
+            NbtValue::Long(_) => NbtTagId::Long,
+            NbtValue::Float(_) => NbtTagId::Float,
+            NbtValue::Double(_) => NbtTagId::Double,
+            NbtValue::ByteArray(_) => NbtTagId::ByteArray,
+            NbtValue::String(_) | NbtValue::RawString(_) => NbtTagId::String,
+            NbtValue::List(_) => NbtTagId::List,
+            NbtValue::Compound(_) => NbtTagId::Compound,
+            NbtValue::IntArray(_) => NbtTagId::IntArray,
+            NbtValue::LongArray(_) => NbtTagId::LongArray,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&NbtCompound> {
+        match self {
+            NbtValue::Compound(compound) => Some(compound),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&NbtList> {
+        match self {
+            NbtValue::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    // payload of the value, like write::nbt_tag but with the children of compounds and lists
+    fn write_payload(
+        &self,
+        buffer: &mut Vec<u8>,
+        encoding: NbtEncoding,
+    ) -> Result<(), NbtWriteError> {
+        match self {
+            NbtValue::Byte(value) => write::nbt_tag(buffer, &NbtTagType::Byte(*value), encoding),
+            NbtValue::Short(value) => write::nbt_tag(buffer, &NbtTagType::Short(*value), encoding),
+            NbtValue::Int(value) => write::nbt_tag(buffer, &NbtTagType::Int(*value), encoding),
+            NbtValue::Long(value) => write::nbt_tag(buffer, &NbtTagType::Long(*value), encoding),
+            NbtValue::Float(value) => write::nbt_tag(buffer, &NbtTagType::Float(*value), encoding),
+            NbtValue::Double(value) => {
+                write::nbt_tag(buffer, &NbtTagType::Double(*value), encoding)
+            }
+            NbtValue::ByteArray(values) => {
+                write::nbt_tag(buffer, &NbtTagType::ByteArray(values.clone()), encoding)
+            }
+            NbtValue::String(value) => write::nbt_tag_string(buffer, value, encoding),
+            NbtValue::RawString(value) => write::nbt_tag_string_raw(buffer, value, encoding),
+            NbtValue::List(list) => list.write_payload(buffer, encoding),
+            NbtValue::Compound(compound) => compound.write_payload(buffer, encoding),
+            NbtValue::IntArray(values) => {
+                write::nbt_tag(buffer, &NbtTagType::IntArray(values.clone()), encoding)
+            }
+            NbtValue::LongArray(values) => {
+                write::nbt_tag(buffer, &NbtTagType::LongArray(values.clone()), encoding)
+            }
+        }
+    }
+}
+
+impl From<i8> for NbtValue {
+    fn from(value: i8) -> NbtValue {
+        NbtValue::Byte(value)
+    }
+}
+
+impl From<bool> for NbtValue {
+    fn from(value: bool) -> NbtValue {
+        NbtValue::Byte(value as i8)
+    }
+}
+
+impl From<i16> for NbtValue {
+    fn from(value: i16) -> NbtValue {
+        NbtValue::Short(value)
+    }
+}
+
+impl From<i32> for NbtValue {
+    fn from(value: i32) -> NbtValue {
+        NbtValue::Int(value)
+    }
+}
+
+impl From<i64> for NbtValue {
+    fn from(value: i64) -> NbtValue {
+        NbtValue::Long(value)
+    }
+}
+
+impl From<f32> for NbtValue {
+    fn from(value: f32) -> NbtValue {
+        NbtValue::Float(value)
+    }
+}
+
+impl From<f64> for NbtValue {
+    fn from(value: f64) -> NbtValue {
+        NbtValue::Double(value)
+    }
+}
+
+impl From<String> for NbtValue {
+    fn from(value: String) -> NbtValue {
+        NbtValue::String(value)
+    }
+}
+
+impl From<&str> for NbtValue {
+    fn from(value: &str) -> NbtValue {
+        NbtValue::String(value.to_string())
+    }
+}
+
+impl From<Vec<i8>> for NbtValue {
+    fn from(values: Vec<i8>) -> NbtValue {
+        NbtValue::ByteArray(values)
+    }
+}
+
+impl From<Vec<i32>> for NbtValue {
+    fn from(values: Vec<i32>) -> NbtValue {
+        NbtValue::IntArray(values)
+    }
+}
+
+impl From<Vec<i64>> for NbtValue {
+    fn from(values: Vec<i64>) -> NbtValue {
+        NbtValue::LongArray(values)
+    }
+}
+
+impl From<NbtList> for NbtValue {
+    fn from(list: NbtList) -> NbtValue {
+        NbtValue::List(list)
+    }
+}
+
+impl From<NbtCompound> for NbtValue {
+    fn from(compound: NbtCompound) -> NbtValue {
+        NbtValue::Compound(compound)
+    }
+}
+
+/// Rust types which can be the elements of a [`NbtList`]: `Vec<T>` converts into a list of T,
+/// so a list can only be built from elements of a single type
+pub trait NbtListElement: Sized {
+    fn into_list(values: Vec<Self>) -> NbtList;
+}
+
+impl<T: NbtListElement> From<Vec<T>> for NbtList {
+    fn from(values: Vec<T>) -> NbtList {
+        T::into_list(values)
+    }
+}
+
+macro_rules! impl_nbt_list_element {
+    ($($element:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl NbtListElement for $element {
+                fn into_list(values: Vec<$element>) -> NbtList {
+                    NbtList::$variant(values)
+                }
+            }
+        )*
+    };
+}
+
+impl_nbt_list_element! {
+    i8 => Byte,
+    i16 => Short,
+    i32 => Int,
+    i64 => Long,
+    f32 => Float,
+    f64 => Double,
+    Vec<i8> => ByteArray,
+    String => String,
+    NbtList => List,
+    NbtCompound => Compound,
+    Vec<i32> => IntArray,
+    Vec<i64> => LongArray,
+}
+
+impl NbtListElement for &str {
+    fn into_list(values: Vec<&str>) -> NbtList {
+        NbtList::String(values.into_iter().map(str::to_string).collect())
+    }
+}
+
+/// Elements of a NBT List, all of the same type.
+///
+/// A list with no element type (an empty list of End tags) is [`NbtList::Empty`], it takes the type
+/// of its first element
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum NbtList {
+    #[default]
+    Empty,
+    Byte(Vec<i8>),
+    Short(Vec<i16>),
+    Int(Vec<i32>),
+    Long(Vec<i64>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    ByteArray(Vec<Vec<i8>>),
+    String(Vec<String>),
+    List(Vec<NbtList>),
+    Compound(Vec<NbtCompound>),
+    IntArray(Vec<Vec<i32>>),
+    LongArray(Vec<Vec<i64>>),
+}
+
+impl NbtList {
+    /// Empty list of elements of type `tag_id`
+    pub fn new(tag_id: NbtTagId) -> NbtList {
+        match tag_id {
+            NbtTagId::End => NbtList::Empty,
+            NbtTagId::Byte => NbtList::Byte(Vec::new()),
+            NbtTagId::Short => NbtList::Short(Vec::new()),
+            NbtTagId::Int => NbtList::Int(Vec::new()),
+            NbtTagId::Long => NbtList::Long(Vec::new()),
+            NbtTagId::Float => NbtList::Float(Vec::new()),
+            NbtTagId::Double => NbtList::Double(Vec::new()),
+            NbtTagId::ByteArray => NbtList::ByteArray(Vec::new()),
+            NbtTagId::String => NbtList::String(Vec::new()),
+            NbtTagId::List => NbtList::List(Vec::new()),
+            NbtTagId::Compound => NbtList::Compound(Vec::new()),
+            NbtTagId::IntArray => NbtList::IntArray(Vec::new()),
+            NbtTagId::LongArray => NbtList::LongArray(Vec::new()),
+        }
+    }
+
+    /// Type of the elements, End for [`NbtList::Empty`]
+    pub fn element_id(&self) -> NbtTagId {
+        match self {
+            NbtList::Empty => NbtTagId::End,
+            NbtList::Byte(_) => NbtTagId::Byte,
+            NbtList::Short(_) => NbtTagId::Short,
+            NbtList::Int(_) => NbtTagId::Int,
+            NbtList::Long(_) => NbtTagId::Long,
+            NbtList::Float(_) => NbtTagId::Float,
+            NbtList::Double(_) => NbtTagId::Double,
+            NbtList::ByteArray(_) => NbtTagId::ByteArray,
+            NbtList::String(_) => NbtTagId::String,
+            NbtList::List(_) => NbtTagId::List,
+            NbtList::Compound(_) => NbtTagId::Compound,
+            NbtList::IntArray(_) => NbtTagId::IntArray,
+            NbtList::LongArray(_) => NbtTagId::LongArray,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            NbtList::Empty => 0,
+            NbtList::Byte(values) => values.len(),
+            NbtList::Short(values) => values.len(),
+            NbtList::Int(values) => values.len(),
+            NbtList::Long(values) => values.len(),
+            NbtList::Float(values) => values.len(),
+            NbtList::Double(values) => values.len(),
+            NbtList::ByteArray(values) => values.len(),
+            NbtList::String(values) => values.len(),
+            NbtList::List(values) => values.len(),
+            NbtList::Compound(values) => values.len(),
+            NbtList::IntArray(values) => values.len(),
+            NbtList::LongArray(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append `value` to the list, it must have the type of the other elements
+    pub fn push(&mut self, value: NbtValue) -> Result<(), NbtEditError> {
+        if let NbtList::Empty = self {
+            *self = NbtList::new(value.tag_id());
+        }
+
+        match (self, value) {
+            (NbtList::Byte(values), NbtValue::Byte(value)) => values.push(value),
+            (NbtList::Short(values), NbtValue::Short(value)) => values.push(value),
+            (NbtList::Int(values), NbtValue::Int(value)) => values.push(value),
+            (NbtList::Long(values), NbtValue::Long(value)) => values.push(value),
+            (NbtList::Float(values), NbtValue::Float(value)) => values.push(value),
+            (NbtList::Double(values), NbtValue::Double(value)) => values.push(value),
+            (NbtList::ByteArray(values), NbtValue::ByteArray(value)) => values.push(value),
+            (NbtList::String(values), NbtValue::String(value)) => values.push(value),
+            (NbtList::List(values), NbtValue::List(value)) => values.push(value),
+            (NbtList::Compound(values), NbtValue::Compound(value)) => values.push(value),
+            (NbtList::IntArray(values), NbtValue::IntArray(value)) => values.push(value),
+            (NbtList::LongArray(values), NbtValue::LongArray(value)) => values.push(value),
+            (list, value) => {
+                return Err(NbtEditError::ListTypeMismatch {
+                    expected: list.element_id(),
+                    found: value.tag_id(),
+                })
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Element at `index`, as an owned NbtValue
+    pub fn get(&self, index: usize) -> Option<NbtValue> {
+        match self {
+            NbtList::Empty => None,
+            NbtList::Byte(values) => values.get(index).copied().map(NbtValue::Byte),
+            NbtList::Short(values) => values.get(index).copied().map(NbtValue::Short),
+            NbtList::Int(values) => values.get(index).copied().map(NbtValue::Int),
+            NbtList::Long(values) => values.get(index).copied().map(NbtValue::Long),
+            NbtList::Float(values) => values.get(index).copied().map(NbtValue::Float),
+            NbtList::Double(values) => values.get(index).copied().map(NbtValue::Double),
+            NbtList::ByteArray(values) => values.get(index).cloned().map(NbtValue::ByteArray),
+            NbtList::String(values) => values.get(index).cloned().map(NbtValue::String),
+            NbtList::List(values) => values.get(index).cloned().map(NbtValue::List),
+            NbtList::Compound(values) => values.get(index).cloned().map(NbtValue::Compound),
+            NbtList::IntArray(values) => values.get(index).cloned().map(NbtValue::IntArray),
+            NbtList::LongArray(values) => values.get(index).cloned().map(NbtValue::LongArray),
+        }
+    }
+
+    fn write_payload(
+        &self,
+        buffer: &mut Vec<u8>,
+        encoding: NbtEncoding,
+    ) -> Result<(), NbtWriteError> {
+        let len = i32::try_from(self.len()).map_err(|_| NbtWriteError::ArrayTooLong(self.len()))?;
+        write::nbt_tag(
+            buffer,
+            &NbtTagType::List((self.element_id(), len)),
+            encoding,
+        )?;
+
+        // the elements have neither id nor name, only their payload
+        match self {
+            NbtList::Empty => {}
+            NbtList::Byte(values) => {
+                for value in values {
+                    write::nbt_tag(buffer, &NbtTagType::Byte(*value), encoding)?;
+                }
+            }
+            NbtList::Short(values) => {
+                for value in values {
+                    write::nbt_tag(buffer, &NbtTagType::Short(*value), encoding)?;
+                }
+            }
+            NbtList::Int(values) => {
+                for value in values {
+                    write::nbt_tag(buffer, &NbtTagType::Int(*value), encoding)?;
+                }
+            }
+            NbtList::Long(values) => {
+                for value in values {
+                    write::nbt_tag(buffer, &NbtTagType::Long(*value), encoding)?;
+                }
+            }
+            NbtList::Float(values) => {
+                for value in values {
+                    write::nbt_tag(buffer, &NbtTagType::Float(*value), encoding)?;
+                }
+            }
+            NbtList::Double(values) => {
+                for value in values {
+                    write::nbt_tag(buffer, &NbtTagType::Double(*value), encoding)?;
+                }
+            }
+            NbtList::ByteArray(values) => {
+                for value in values {
+                    write::nbt_tag(buffer, &NbtTagType::ByteArray(value.clone()), encoding)?;
+                }
+            }
+            NbtList::String(values) => {
+                for value in values {
+                    write::nbt_tag_string(buffer, value, encoding)?;
+                }
+            }
+            NbtList::List(values) => {
+                for value in values {
+                    value.write_payload(buffer, encoding)?;
+                }
+            }
+            NbtList::Compound(values) => {
+                for value in values {
+                    value.write_payload(buffer, encoding)?;
+                }
+            }
+            NbtList::IntArray(values) => {
+                for value in values {
+                    write::nbt_tag(buffer, &NbtTagType::IntArray(value.clone()), encoding)?;
+                }
+            }
+            NbtList::LongArray(values) => {
+                for value in values {
+                    write::nbt_tag(buffer, &NbtTagType::LongArray(value.clone()), encoding)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Named children of a NBT Compound, in the order they were inserted
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NbtCompound {
+    entries: Vec<(String, NbtValue)>,
+}
+
+impl NbtCompound {
+    pub fn new() -> NbtCompound {
+        NbtCompound::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NbtValue> {
+        self.entries
+            .iter()
+            .find(|(entry_name, _)| entry_name == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut NbtValue> {
+        self.entries
+            .iter_mut()
+            .find(|(entry_name, _)| entry_name == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Set the child `name` to `value` and return the previous value.
+    /// An existing child keeps its place, a new one is added at the end
+    pub fn insert(&mut self, name: String, value: NbtValue) -> Option<NbtValue> {
+        match self.get_mut(&name) {
+            Some(existing) => Some(std::mem::replace(existing, value)),
+            None => {
+                self.entries.push((name, value));
+                None
+            }
+        }
+    }
+
+    // NBT compounds can have several children with the same name, NbtData::to_value keeps them all
+    fn push(&mut self, name: String, value: NbtValue) {
+        self.entries.push((name, value));
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<NbtValue> {
+        let position = self
+            .entries
+            .iter()
+            .position(|(entry_name, _)| entry_name == name)?;
+        Some(self.entries.remove(position).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &NbtValue)> {
+        self.entries.iter().map(|(name, value)| (name, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(name, _)| name)
+    }
+
+    fn write_payload(
+        &self,
+        buffer: &mut Vec<u8>,
+        encoding: NbtEncoding,
+    ) -> Result<(), NbtWriteError> {
+        for (name, value) in self.entries.iter() {
+            write::nbt_tag_id(buffer, &value.tag_id())?;
+            write::nbt_tag_string(buffer, name, encoding)?;
+            value.write_payload(buffer, encoding)?;
+        }
+        write::nbt_tag_id(buffer, &NbtTagId::End)
+    }
+
+    // every child written as a root tag, with the encoding and root mode of `options`
+    pub(crate) fn write_roots(&self, options: &ParseOptions) -> Result<Vec<u8>, NbtWriteError> {
+        let encoding = options.encoding();
+        let mut buffer = Vec::new();
+        for (name, value) in self.entries.iter() {
+            write::nbt_tag_id(&mut buffer, &value.tag_id())?;
+            if options.root_mode() != RootMode::Nameless {
+                write::nbt_tag_string(&mut buffer, name, encoding)?;
+            }
+            value.write_payload(&mut buffer, encoding)?;
+        }
+        Ok(buffer)
+    }
+}
+
+impl FromIterator<(String, NbtValue)> for NbtCompound {
+    fn from_iter<I: IntoIterator<Item = (String, NbtValue)>>(iter: I) -> NbtCompound {
+        let mut compound = NbtCompound::new();
+        for (name, value) in iter {
+            compound.insert(name, value);
+        }
+        compound
+    }
+}
+
+impl IntoIterator for NbtCompound {
+    type Item = (String, NbtValue);
+    type IntoIter = std::vec::IntoIter<(String, NbtValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl NbtData {
+    /// The NbtTag tree as owned values: a compound with one child per root, named as the root.
+    ///
+    /// Values skipped by [`ParseOptions::lazy_values`] are decoded, and children with the same name are
+    /// all kept, in order. The NBT which NbtValue cannot hold is an error rather than being dropped:
+    /// a Bedrock header, a trailer, invalid MUTF-8 names or list elements, negative list lengths
+    /// and empty NBT made of a single End tag
+    pub fn to_value(&self) -> Result<NbtCompound, NbtReadError> {
+        if self.bedrock_header().is_some() {
+            return Err(NbtReadError::UnrepresentableValue(
+                "Bedrock header".to_string(),
+            ));
+        }
+        if self.trailer().is_some() {
+            return Err(NbtReadError::UnrepresentableValue("trailer".to_string()));
+        }
+
+        let mut roots = NbtCompound::new();
+        for root in self.roots() {
+            roots.push(self.name_at(root)?, self.value_at(root)?);
+        }
+        Ok(roots)
+    }
+
+    /// Build a NbtData from owned values, e.g. the result of [`NbtData::to_value`]:
+    /// every child of `roots` is written as a root tag with its name
+    pub fn from_value(roots: &NbtCompound) -> Result<NbtData, NbtWriteError> {
+        NbtData::from_value_with_options(roots, &ParseOptions::default())
+    }
+
+    /// Build a NbtData from owned values, written with the encoding and root mode of `options`.
+    ///
+    /// The values are written to binary NBT and parsed again, so that the NbtTags get their byte ranges
+    pub fn from_value_with_options(
+        roots: &NbtCompound,
+        options: &ParseOptions,
+    ) -> Result<NbtData, NbtWriteError> {
+        let buffer = roots.write_roots(options)?;
+
+        let mut options = options.clone();
+        if roots.len() > 1 {
+            options.set_multiple_roots(true);
+        }
+        NbtData::from_buf_with_options(buffer, &options)
+            .map_err(|e| NbtWriteError::InvalidNbtTree(e.to_string()))
+    }
+
+    fn value_at(&self, index: usize) -> Result<NbtValue, NbtReadError> {
+        // a root is listed in its own children
+        let children = self.tags[index]
+            .position()
+            .children
+            .iter()
+            .copied()
+            .filter(|&child| child != index);
+
+        let nbt_tag = &self.tags[index];
+        let value = match self.tag_value(index)?.into_owned() {
+            NbtTagType::End(_) => {
+                return Err(NbtReadError::UnrepresentableValue("End tag".to_string()))
+            }
+            NbtTagType::Lazy(_) => return Err(NbtReadError::InvalidContent),
+            NbtTagType::Byte(value) => NbtValue::Byte(value),
+            NbtTagType::Short(value) => NbtValue::Short(value),
+            NbtTagType::Int(value) => NbtValue::Int(value),
+            NbtTagType::Long(value) => NbtValue::Long(value),
+            NbtTagType::Float(value) => NbtValue::Float(value),
+            NbtTagType::Double(value) => NbtValue::Double(value),
+            NbtTagType::ByteArray(values) => NbtValue::ByteArray(values),
+            NbtTagType::String(value) if nbt_tag.has_invalid_value() => NbtValue::RawString(value),
+            NbtTagType::String(value) => NbtValue::String(value),
+            NbtTagType::IntArray(values) => NbtValue::IntArray(values),
+            NbtTagType::LongArray(values) => NbtValue::LongArray(values),
+            NbtTagType::List((_, len)) if len < 0 => {
+                return Err(NbtReadError::UnrepresentableValue(
+                    "negative list length".to_string(),
+                ))
+            }
+            NbtTagType::List((tag_id, _)) => {
+                let mut list = NbtList::new(tag_id);
+                for child in children {
+                    // NbtList::String only holds valid strings
+                    let value = match self.value_at(child)? {
+                        NbtValue::RawString(_) => {
+                            return Err(NbtReadError::UnrepresentableValue(
+                                "invalid MUTF-8 list element".to_string(),
+                            ))
+                        }
+                        value => value,
+                    };
+                    list.push(value).map_err(|_| NbtReadError::InvalidContent)?;
+                }
+                NbtValue::List(list)
+            }
+            NbtTagType::Compound(_) => {
+                let mut compound = NbtCompound::new();
+                for child in children {
+                    if let NbtTagType::End(_) = self.tags[child].value() {
+                        continue;
+                    }
+                    compound.push(self.name_at(child)?, self.value_at(child)?);
+                }
+                NbtValue::Compound(compound)
+            }
+        };
+
+        Ok(value)
+    }
+
+    // NbtCompound keys are written back as valid MUTF-8, the original bytes of invalid names would be lost
+    fn name_at(&self, index: usize) -> Result<String, NbtReadError> {
+        let nbt_tag = &self.tags[index];
+        if nbt_tag.has_invalid_name() {
+            return Err(NbtReadError::UnrepresentableValue(
+                "invalid MUTF-8 name".to_string(),
+            ));
+        }
+        Ok(nbt_tag.name().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::test_utils::{list_header, named_tag_header};
+
+    // Root { Name: "Steve", Scores: [1, 2], Items: [{ Count: 1b }], Empty: [] }
+    fn sample() -> Vec<u8> {
+        let mut buffer = Vec::new();
+        named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+        named_tag_header(&mut buffer, NbtTagId::String, "Name");
+        buffer.extend(&5u16.to_be_bytes());
+        buffer.extend(b"Steve");
+        named_tag_header(&mut buffer, NbtTagId::List, "Scores");
+        list_header(&mut buffer, NbtTagId::Int, 2);
+        buffer.extend(&1i32.to_be_bytes());
+        buffer.extend(&2i32.to_be_bytes());
+        named_tag_header(&mut buffer, NbtTagId::List, "Items");
+        list_header(&mut buffer, NbtTagId::Compound, 1);
+        named_tag_header(&mut buffer, NbtTagId::Byte, "Count");
+        buffer.push(1);
+        buffer.push(NbtTagId::End.into_u8());
+        named_tag_header(&mut buffer, NbtTagId::List, "Empty");
+        list_header(&mut buffer, NbtTagId::End, 0);
+        buffer.push(NbtTagId::End.into_u8());
+        buffer
+    }
+
+    #[test]
+    fn test_nbt_data_to_value() {
+        let nbt_data = NbtData::from_buf(sample()).unwrap();
+        let roots = nbt_data.to_value().unwrap();
+
+        assert_eq!(roots.len(), 1);
+        let root = roots.get("Root").unwrap().as_compound().unwrap();
+        assert_eq!(
+            root.keys().collect::<Vec<_>>(),
+            vec!["Name", "Scores", "Items", "Empty"]
+        );
+        assert_eq!(
+            root.get("Name"),
+            Some(&NbtValue::String("Steve".to_string()))
+        );
+        assert_eq!(
+            root.get("Scores"),
+            Some(&NbtValue::List(NbtList::Int(vec![1, 2])))
+        );
+        let items = root.get("Items").unwrap().as_list().unwrap();
+        let mut item = NbtCompound::new();
+        item.insert("Count".to_string(), NbtValue::Byte(1));
+        assert_eq!(items.get(0), Some(NbtValue::Compound(item)));
+        assert_eq!(root.get("Empty"), Some(&NbtValue::List(NbtList::Empty)));
+    }
+
+    #[test]
+    fn test_nbt_value_round_trip() {
+        let nbt_data = NbtData::from_buf(sample()).unwrap();
+        let roots = nbt_data.to_value().unwrap();
+
+        let rebuilt = NbtData::from_value(&roots).unwrap();
+        assert_eq!(rebuilt.raw_bytes(), &sample());
+        assert_eq!(rebuilt.nbt_tags(), nbt_data.nbt_tags());
+    }
+
+    #[test]
+    fn test_nbt_value_round_trip_options() {
+        let nbt_data = NbtData::from_buf(sample()).unwrap();
+        let roots = nbt_data.to_value().unwrap();
+
+        for options in [
+            ParseOptions::bedrock(),
+            ParseOptions::bedrock_network(),
+            ParseOptions::java_network(),
+        ] {
+            let rebuilt = NbtData::from_value_with_options(&roots, &options).unwrap();
+            let mut expected = roots.clone();
+            if options.root_mode() == RootMode::Nameless {
+                let root = expected.remove("Root").unwrap();
+                expected.insert(String::new(), root);
+            }
+            assert_eq!(rebuilt.to_value().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_nbt_value_multiple_roots() {
+        let mut roots = NbtCompound::new();
+        roots.insert("First".to_string(), NbtValue::Compound(NbtCompound::new()));
+        roots.insert("Second".to_string(), NbtValue::Compound(NbtCompound::new()));
+
+        let nbt_data = NbtData::from_value(&roots).unwrap();
+        assert_eq!(nbt_data.roots(), vec![0, 2]);
+        assert_eq!(nbt_data.to_value().unwrap(), roots);
+    }
+
+    #[test]
+    fn test_nbt_value_keeps_duplicate_names() {
+        let mut buffer = Vec::new();
+        named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+        named_tag_header(&mut buffer, NbtTagId::Int, "A");
+        buffer.extend(&1i32.to_be_bytes());
+        named_tag_header(&mut buffer, NbtTagId::Int, "A");
+        buffer.extend(&2i32.to_be_bytes());
+        buffer.push(NbtTagId::End.into_u8());
+        let mut two_roots = buffer.clone();
+        two_roots.extend(&buffer);
+
+        let mut options = ParseOptions::default();
+        options.set_multiple_roots(true);
+        let nbt_data = NbtData::from_buf_with_options(two_roots.clone(), &options).unwrap();
+        let roots = nbt_data.to_value().unwrap();
+
+        assert_eq!(roots.len(), 2);
+        let root = roots.get("Root").unwrap().as_compound().unwrap();
+        assert_eq!(
+            root.iter().collect::<Vec<_>>(),
+            vec![
+                (&"A".to_string(), &NbtValue::Int(1)),
+                (&"A".to_string(), &NbtValue::Int(2))
+            ]
+        );
+        assert_eq!(NbtData::from_value(&roots).unwrap().raw_bytes(), &two_roots);
+    }
+
+    #[test]
+    fn test_nbt_value_raw_string() {
+        let mut buffer = Vec::new();
+        named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+        named_tag_header(&mut buffer, NbtTagId::String, "Raw");
+        buffer.extend(&2u16.to_be_bytes());
+        buffer.extend([0xFF, b'A']);
+        buffer.push(NbtTagId::End.into_u8());
+
+        let roots = NbtData::from_buf(buffer.clone())
+            .unwrap()
+            .to_value()
+            .unwrap();
+        let root = roots.get("Root").unwrap().as_compound().unwrap();
+        assert_eq!(
+            root.get("Raw"),
+            Some(&NbtValue::RawString("\u{FF}A".to_string()))
+        );
+        assert_eq!(NbtData::from_value(&roots).unwrap().raw_bytes(), &buffer);
+    }
+
+    #[test]
+    fn test_nbt_value_unrepresentable() {
+        // bytes after the root
+        let mut buffer = sample();
+        buffer.extend([1, 2, 3]);
+        let nbt_data = NbtData::from_buf(buffer).unwrap();
+        assert!(matches!(
+            nbt_data.to_value(),
+            Err(NbtReadError::UnrepresentableValue(_))
+        ));
+
+        // a Bedrock level.dat header before the root
+        let roots = NbtData::from_buf(sample()).unwrap().to_value().unwrap();
+        let options = ParseOptions::bedrock();
+        let root = NbtData::from_value_with_options(&roots, &options).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend(&10i32.to_le_bytes());
+        buffer.extend(&(root.raw_bytes().len() as i32).to_le_bytes());
+        buffer.extend(root.raw_bytes());
+        let nbt_data = NbtData::from_buf_with_options(buffer, &options).unwrap();
+        assert!(nbt_data.bedrock_header().is_some());
+        assert!(matches!(
+            nbt_data.to_value(),
+            Err(NbtReadError::UnrepresentableValue(_))
+        ));
+
+        // a negative list length is read as an empty list, but written back as 0
+        let mut buffer = Vec::new();
+        named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+        named_tag_header(&mut buffer, NbtTagId::List, "Negative");
+        buffer.push(NbtTagId::Int.into_u8());
+        buffer.extend(&(-1i32).to_be_bytes());
+        buffer.push(NbtTagId::End.into_u8());
+        let nbt_data = NbtData::from_buf(buffer).unwrap();
+        assert!(matches!(
+            nbt_data.to_value(),
+            Err(NbtReadError::UnrepresentableValue(_))
+        ));
+
+        // a name which is not valid MUTF-8
+        let mut buffer = Vec::new();
+        named_tag_header(&mut buffer, NbtTagId::Compound, "Root");
+        buffer.push(NbtTagId::Byte.into_u8());
+        buffer.extend(&1u16.to_be_bytes());
+        buffer.push(0xFF);
+        buffer.push(1);
+        buffer.push(NbtTagId::End.into_u8());
+        let nbt_data = NbtData::from_buf(buffer).unwrap();
+        assert!(matches!(
+            nbt_data.to_value(),
+            Err(NbtReadError::UnrepresentableValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_nbt_list_push() {
+        let mut list = NbtList::default();
+        list.push(NbtValue::Int(1)).unwrap();
+        list.push(NbtValue::Int(2)).unwrap();
+        assert_eq!(list, NbtList::Int(vec![1, 2]));
+        assert!(matches!(
+            list.push(NbtValue::Byte(3)),
+            Err(NbtEditError::ListTypeMismatch {
+                expected: NbtTagId::Int,
+                found: NbtTagId::Byte
+            })
+        ));
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn test_nbt_compound_insert_keeps_order() {
+        let mut compound: NbtCompound = [
+            ("b".to_string(), NbtValue::Int(1)),
+            ("a".to_string(), NbtValue::Int(2)),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            compound.insert("b".to_string(), NbtValue::Int(3)),
+            Some(NbtValue::Int(1))
+        );
+        assert_eq!(compound.keys().collect::<Vec<_>>(), vec!["b", "a"]);
+        assert_eq!(compound.remove("b"), Some(NbtValue::Int(3)));
+        assert_eq!(compound.keys().collect::<Vec<_>>(), vec!["a"]);
+    }
+}
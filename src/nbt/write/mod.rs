@@ -0,0 +1,391 @@
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+use std::io;
+use std::io::Write;
+
+use crate::nbt;
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_u8(byte);
+        }
+        writer.write_u8(byte | 0x80)?;
+    }
+}
+
+fn write_i16<W: Write>(writer: &mut W, value: i16, encoding: nbt::NbtEncoding) -> io::Result<()> {
+    match encoding {
+        nbt::NbtEncoding::BigEndian => writer.write_i16::<BigEndian>(value),
+        nbt::NbtEncoding::LittleEndian | nbt::NbtEncoding::NetworkLittleEndian => {
+            writer.write_i16::<LittleEndian>(value)
+        }
+    }
+}
+
+fn write_i32<W: Write>(writer: &mut W, value: i32, encoding: nbt::NbtEncoding) -> io::Result<()> {
+    match encoding {
+        nbt::NbtEncoding::BigEndian => writer.write_i32::<BigEndian>(value),
+        nbt::NbtEncoding::LittleEndian => writer.write_i32::<LittleEndian>(value),
+        nbt::NbtEncoding::NetworkLittleEndian => {
+            write_varint(writer, u64::from(((value << 1) ^ (value >> 31)) as u32))
+        }
+    }
+}
+
+fn write_i64<W: Write>(writer: &mut W, value: i64, encoding: nbt::NbtEncoding) -> io::Result<()> {
+    match encoding {
+        nbt::NbtEncoding::BigEndian => writer.write_i64::<BigEndian>(value),
+        nbt::NbtEncoding::LittleEndian => writer.write_i64::<LittleEndian>(value),
+        nbt::NbtEncoding::NetworkLittleEndian => {
+            write_varint(writer, ((value << 1) ^ (value >> 63)) as u64)
+        }
+    }
+}
+
+fn write_f32<W: Write>(writer: &mut W, value: f32, encoding: nbt::NbtEncoding) -> io::Result<()> {
+    match encoding {
+        nbt::NbtEncoding::BigEndian => writer.write_f32::<BigEndian>(value),
+        nbt::NbtEncoding::LittleEndian | nbt::NbtEncoding::NetworkLittleEndian => {
+            writer.write_f32::<LittleEndian>(value)
+        }
+    }
+}
+
+fn write_f64<W: Write>(writer: &mut W, value: f64, encoding: nbt::NbtEncoding) -> io::Result<()> {
+    match encoding {
+        nbt::NbtEncoding::BigEndian => writer.write_f64::<BigEndian>(value),
+        nbt::NbtEncoding::LittleEndian | nbt::NbtEncoding::NetworkLittleEndian => {
+            writer.write_f64::<LittleEndian>(value)
+        }
+    }
+}
+
+pub fn nbt_tag_id<W: Write>(
+    writer: &mut W,
+    tag_id: &nbt::NbtTagId,
+) -> Result<(), nbt::NbtWriteError> {
+    writer.write_u8(tag_id.into_u8())?;
+    Ok(())
+}
+
+pub fn nbt_tag_string<W: Write>(
+    writer: &mut W,
+    value: &str,
+    encoding: nbt::NbtEncoding,
+) -> Result<(), nbt::NbtWriteError> {
+    raw_string(writer, &nbt::mutf8::encode_with(value, encoding), encoding)
+}
+
+/// Write a string that could not be decoded when parsed, every char is written back as the original byte
+pub fn nbt_tag_string_raw<W: Write>(
+    writer: &mut W,
+    value: &str,
+    encoding: nbt::NbtEncoding,
+) -> Result<(), nbt::NbtWriteError> {
+    match nbt::mutf8::encode_raw(value) {
+        Some(raw) => raw_string(writer, &raw, encoding),
+        None => Err(nbt::NbtWriteError::UnencodableString(value.to_string())),
+    }
+}
+
+fn raw_string<W: Write>(
+    writer: &mut W,
+    raw: &[u8],
+    encoding: nbt::NbtEncoding,
+) -> Result<(), nbt::NbtWriteError> {
+    // the length is unsigned, like in Java's DataOutput::writeUTF
+    let len = match u16::try_from(raw.len()) {
+        Ok(len) => len,
+        Err(_) => return Err(nbt::NbtWriteError::StringTooLong(raw.len())),
+    };
+
+    match encoding {
+        nbt::NbtEncoding::BigEndian => writer.write_u16::<BigEndian>(len)?,
+        nbt::NbtEncoding::LittleEndian => writer.write_u16::<LittleEndian>(len)?,
+        nbt::NbtEncoding::NetworkLittleEndian => write_varint(writer, u64::from(len))?,
+    }
+    writer.write_all(raw)?;
+    Ok(())
+}
+
+fn array_len<W: Write>(
+    writer: &mut W,
+    len: usize,
+    encoding: nbt::NbtEncoding,
+) -> Result<(), nbt::NbtWriteError> {
+    let len = match i32::try_from(len) {
+        Ok(len) => len,
+        Err(_) => return Err(nbt::NbtWriteError::ArrayTooLong(len)),
+    };
+    write_i32(writer, len, encoding)?;
+    Ok(())
+}
+
+/// Write the payload of a NbtTag, i.e. everything after the tag id and the name.
+/// For lists only the header (element tag id and length) is written, for compounds nothing:
+/// their content is made of the following NbtTags in the tree
+pub fn nbt_tag<W: Write>(
+    writer: &mut W,
+    tag_value: &nbt::NbtTagType,
+    encoding: nbt::NbtEncoding,
+) -> Result<(), nbt::NbtWriteError> {
+    match tag_value {
+        nbt::NbtTagType::End(_) => {}
+        nbt::NbtTagType::Byte(x) => writer.write_i8(*x)?,
+        nbt::NbtTagType::Short(x) => write_i16(writer, *x, encoding)?,
+        nbt::NbtTagType::Int(x) => write_i32(writer, *x, encoding)?,
+        nbt::NbtTagType::Long(x) => write_i64(writer, *x, encoding)?,
+        nbt::NbtTagType::Float(x) => write_f32(writer, *x, encoding)?,
+        nbt::NbtTagType::Double(x) => write_f64(writer, *x, encoding)?,
+        nbt::NbtTagType::ByteArray(values) => {
+            array_len(writer, values.len(), encoding)?;
+            for x in values {
+                writer.write_i8(*x)?;
+            }
+        }
+        nbt::NbtTagType::String(value) => nbt_tag_string(writer, value, encoding)?,
+        nbt::NbtTagType::List((list_elem_tag_id, len)) => {
+            nbt_tag_id(writer, list_elem_tag_id)?;
+            write_i32(writer, *len, encoding)?;
+        }
+        nbt::NbtTagType::Compound(_) => {}
+        nbt::NbtTagType::IntArray(values) => {
+            array_len(writer, values.len(), encoding)?;
+            for x in values {
+                write_i32(writer, *x, encoding)?;
+            }
+        }
+        nbt::NbtTagType::LongArray(values) => {
+            array_len(writer, values.len(), encoding)?;
+            for x in values {
+                write_i64(writer, *x, encoding)?;
+            }
+        }
+        // only NbtData knows the raw bytes of a value which was not decoded
+        nbt::NbtTagType::Lazy(tag_id) => {
+            return Err(nbt::NbtWriteError::InvalidNbtTree(format!(
+                "{:?} value was not decoded",
+                tag_id
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::fsm;
+    use std::io::Cursor;
+
+    fn write_and_parse(tag_value: nbt::NbtTagType) -> nbt::NbtTagType {
+        let mut buffer = Vec::new();
+        nbt_tag(&mut buffer, &tag_value, nbt::NbtEncoding::BigEndian).unwrap();
+
+        let mut cursor = Cursor::new(buffer);
+        let parsed = fsm::parse::nbt_tag(
+            &mut cursor,
+            &tag_value.into_id(),
+            &nbt::ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(cursor.position() as usize, cursor.get_ref().len());
+        parsed
+    }
+
+    #[test]
+    fn test_nbt_tag_id() {
+        let mut buffer = Vec::new();
+        nbt_tag_id(&mut buffer, &nbt::NbtTagId::LongArray).unwrap();
+        assert_eq!(buffer, vec![12u8]);
+    }
+
+    #[test]
+    fn test_nbt_tag_string() {
+        let mut buffer = Vec::new();
+        nbt_tag_string(&mut buffer, "Test", nbt::NbtEncoding::BigEndian).unwrap();
+        assert_eq!(buffer, vec![0, 4, b'T', b'e', b's', b't']);
+    }
+
+    #[test]
+    fn test_nbt_tag_little_endian() {
+        let mut buffer = Vec::new();
+        nbt_tag_string(&mut buffer, "Test", nbt::NbtEncoding::LittleEndian).unwrap();
+        nbt_tag(
+            &mut buffer,
+            &nbt::NbtTagType::Int(0x01020304),
+            nbt::NbtEncoding::LittleEndian,
+        )
+        .unwrap();
+        assert_eq!(buffer, vec![4, 0, b'T', b'e', b's', b't', 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_nbt_tag_network_varints() {
+        let mut buffer = Vec::new();
+        nbt_tag_string(&mut buffer, "Test", nbt::NbtEncoding::NetworkLittleEndian).unwrap();
+        nbt_tag(
+            &mut buffer,
+            &nbt::NbtTagType::Int(-65),
+            nbt::NbtEncoding::NetworkLittleEndian,
+        )
+        .unwrap();
+        nbt_tag(
+            &mut buffer,
+            &nbt::NbtTagType::Long(1),
+            nbt::NbtEncoding::NetworkLittleEndian,
+        )
+        .unwrap();
+        // -65 zig-zag encodes to 129, which needs 2 VarInt bytes
+        assert_eq!(buffer, vec![4, b'T', b'e', b's', b't', 0x81, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn test_nbt_tag_network_round_trip() {
+        for tag_value in [
+            nbt::NbtTagType::Int(i32::MIN),
+            nbt::NbtTagType::Int(i32::MAX),
+            nbt::NbtTagType::Long(i64::MIN),
+            nbt::NbtTagType::Long(i64::MAX),
+            nbt::NbtTagType::Short(-300),
+            nbt::NbtTagType::Double(0.125),
+            nbt::NbtTagType::String("a".repeat(200)),
+            nbt::NbtTagType::IntArray(vec![0, -1, 1 << 20]),
+            nbt::NbtTagType::LongArray(vec![0, -1, 1 << 40]),
+        ] {
+            let mut buffer = Vec::new();
+            nbt_tag(
+                &mut buffer,
+                &tag_value,
+                nbt::NbtEncoding::NetworkLittleEndian,
+            )
+            .unwrap();
+
+            let mut cursor = Cursor::new(buffer);
+            let parsed = fsm::parse::nbt_tag(
+                &mut cursor,
+                &tag_value.into_id(),
+                &nbt::ParseOptions::bedrock_network(),
+            )
+            .unwrap();
+            assert_eq!(cursor.position() as usize, cursor.get_ref().len());
+            assert_eq!(parsed, tag_value);
+        }
+    }
+
+    #[test]
+    fn test_nbt_tag_string_mutf8() {
+        let mut buffer = Vec::new();
+        nbt_tag_string(&mut buffer, "\0", nbt::NbtEncoding::BigEndian).unwrap();
+        assert_eq!(buffer, vec![0, 2, 0xC0, 0x80]);
+
+        let mut buffer = Vec::new();
+        nbt_tag_string(&mut buffer, "\0", nbt::NbtEncoding::LittleEndian).unwrap();
+        assert_eq!(buffer, vec![1, 0, 0x00]);
+    }
+
+    #[test]
+    fn test_nbt_tag_string_raw() {
+        let mut buffer = Vec::new();
+        nbt_tag_string_raw(&mut buffer, "\u{FF}", nbt::NbtEncoding::BigEndian).unwrap();
+        assert_eq!(buffer, vec![0, 1, 0xFF]);
+
+        let result = nbt_tag_string_raw(&mut buffer, "\u{100}", nbt::NbtEncoding::BigEndian);
+        assert!(matches!(
+            result,
+            Err(nbt::NbtWriteError::UnencodableString(_))
+        ));
+    }
+
+    #[test]
+    fn test_nbt_tag_string_too_long() {
+        let mut buffer = Vec::new();
+        let value = "a".repeat(u16::MAX as usize + 1);
+        let result = nbt_tag_string(&mut buffer, &value, nbt::NbtEncoding::BigEndian);
+        assert!(matches!(result, Err(nbt::NbtWriteError::StringTooLong(_))));
+    }
+
+    #[test]
+    fn test_nbt_tag_string_unsigned_length() {
+        // lengths above i16::MAX are valid, the prefix is an unsigned 16 bit integer
+        let value = "a".repeat(40000);
+        for encoding in [
+            nbt::NbtEncoding::BigEndian,
+            nbt::NbtEncoding::LittleEndian,
+            nbt::NbtEncoding::NetworkLittleEndian,
+        ] {
+            let mut buffer = Vec::new();
+            nbt_tag_string(&mut buffer, &value, encoding).unwrap();
+
+            let mut options = nbt::ParseOptions::default();
+            options.set_encoding(encoding);
+            let mut cursor = Cursor::new(buffer);
+            let parsed = fsm::parse::nbt_tag_string(&mut cursor, &options).unwrap();
+            assert_eq!(parsed, value);
+        }
+
+        let mut buffer = Vec::new();
+        nbt_tag_string(&mut buffer, &value, nbt::NbtEncoding::BigEndian).unwrap();
+        assert_eq!(&buffer[..2], &40000u16.to_be_bytes());
+    }
+
+    #[test]
+    fn test_nbt_tag_scalars_round_trip() {
+        for tag_value in [
+            nbt::NbtTagType::Byte(-12),
+            nbt::NbtTagType::Short(1234),
+            nbt::NbtTagType::Int(-123456),
+            nbt::NbtTagType::Long(1234567890123),
+            nbt::NbtTagType::Float(0.5),
+            nbt::NbtTagType::Double(-0.25),
+            nbt::NbtTagType::String("Hello World".to_string()),
+        ] {
+            assert_eq!(write_and_parse(tag_value.clone()), tag_value);
+        }
+    }
+
+    #[test]
+    fn test_nbt_tag_arrays_round_trip() {
+        for tag_value in [
+            nbt::NbtTagType::ByteArray(vec![1, -2, 3]),
+            nbt::NbtTagType::IntArray(vec![1, -2, 3]),
+            nbt::NbtTagType::LongArray(vec![1, -2, 3]),
+            nbt::NbtTagType::ByteArray(vec![]),
+        ] {
+            assert_eq!(write_and_parse(tag_value.clone()), tag_value);
+        }
+    }
+
+    #[test]
+    fn test_nbt_tag_list_header() {
+        let mut buffer = Vec::new();
+        nbt_tag(
+            &mut buffer,
+            &nbt::NbtTagType::List((nbt::NbtTagId::Int, 3)),
+            nbt::NbtEncoding::BigEndian,
+        )
+        .unwrap();
+        assert_eq!(buffer, vec![3, 0, 0, 0, 3]);
+    }
+
+    #[test]
+    fn test_nbt_tag_compound_and_end_have_no_payload() {
+        let mut buffer = Vec::new();
+        nbt_tag(
+            &mut buffer,
+            &nbt::NbtTagType::Compound("".to_string()),
+            nbt::NbtEncoding::BigEndian,
+        )
+        .unwrap();
+        nbt_tag(
+            &mut buffer,
+            &nbt::NbtTagType::End(None),
+            nbt::NbtEncoding::BigEndian,
+        )
+        .unwrap();
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,840 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::{nbt, Compression, NbtFile};
+
+pub const SECTOR_SIZE: usize = 4096;
+pub const HEADER_SIZE: usize = 2 * SECTOR_SIZE;
+pub const CHUNKS_PER_REGION_SIDE: usize = 32;
+pub const CHUNKS_PER_REGION: usize = CHUNKS_PER_REGION_SIDE * CHUNKS_PER_REGION_SIDE;
+
+const CHUNK_LENGTH_SIZE: usize = 4;
+const CHUNK_COMPRESSION_SIZE: usize = 1;
+const EXTERNAL_CHUNK_FLAG: u8 = 0x80;
+const MAX_CHUNK_SECTORS: usize = u8::MAX as usize;
+
+#[derive(Error, Debug)]
+pub enum RegionError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error), // Automatically convert `io::Error` to `RegionError`
+
+    #[error("NBT read error: {0}")]
+    NbtRead(#[from] nbt::NbtReadError), // Automatically convert `NbtReadError` to `RegionError`
+
+    #[error("Region file is smaller than its 8 KiB header")]
+    InvalidHeader, // if the file cannot contain the location and timestamp tables
+
+    #[error("Chunk [{0}, {1}] is outside of the region")]
+    ChunkOutOfBounds(usize, usize), // if x or z are not in 0..32
+
+    #[error("Chunk [{0}, {1}] points outside of the region file")]
+    InvalidChunkPosition(usize, usize), // if the sectors or the length of a chunk exceed the file
+
+    #[error("Unsupported chunk compression type: {0}")]
+    UnsupportedCompression(u8), // e.g. LZ4 or custom compressions
+
+    #[error("External chunk file not found: {0}")]
+    MissingExternalChunk(String), // if a chunk is stored in a c.X.Z.mcc file which cannot be read
+
+    #[error("NBT write error: {0}")]
+    NbtWrite(#[from] nbt::NbtWriteError), // Automatically convert `NbtWriteError` to `RegionError`
+
+    #[error("Region file has no file path, use save_as")]
+    MissingFilePath, // if a region file built from a buffer is saved without a path
+}
+
+/// Location, timestamp and payload header of a chunk inside a region file
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
+pub struct RegionChunk {
+    x: usize,
+    z: usize,
+    sector_offset: u32,
+    sector_count: u8,
+    timestamp: u32,
+    length: u32,
+    compression_type: u8,
+}
+
+impl RegionChunk {
+    /// Local x coordinate of the chunk in the region (0..32)
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    /// Local z coordinate of the chunk in the region (0..32)
+    pub fn z(&self) -> usize {
+        self.z
+    }
+
+    /// Offset of the chunk from the start of the file, in 4 KiB sectors
+    pub fn sector_offset(&self) -> u32 {
+        self.sector_offset
+    }
+
+    /// Number of 4 KiB sectors allocated to the chunk
+    pub fn sector_count(&self) -> u8 {
+        self.sector_count
+    }
+
+    /// Last modification time of the chunk, in seconds since the epoch
+    pub fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    /// Length of the chunk payload in bytes, including the compression type byte.
+    /// It is 0 for a chunk whose sectors are outside of the region file
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Raw compression type byte, including the external chunk flag
+    pub fn compression_type(&self) -> u8 {
+        self.compression_type
+    }
+
+    pub fn compression(&self) -> Option<Compression> {
+        compression_from_type(self.compression_type & !EXTERNAL_CHUNK_FLAG)
+    }
+
+    /// The chunk payload is stored in a separate `c.X.Z.mcc` file next to the region file
+    pub fn is_external(&self) -> bool {
+        self.compression_type & EXTERNAL_CHUNK_FLAG != 0
+    }
+
+    /// Byte offset of the chunk from the start of the region file
+    pub fn byte_offset(&self) -> usize {
+        self.sector_offset as usize * SECTOR_SIZE
+    }
+
+    pub fn is_present(&self) -> bool {
+        self.sector_offset != 0 && self.sector_count != 0
+    }
+}
+
+pub(crate) fn compression_from_type(compression_type: u8) -> Option<Compression> {
+    match compression_type {
+        1 => Some(Compression::Gzip),
+        2 => Some(Compression::Zlib),
+        3 => Some(Compression::Uncompressed),
+        _ => None,
+    }
+}
+
+pub(crate) fn compression_into_type(compression: Compression) -> u8 {
+    match compression {
+        Compression::Gzip => 1,
+        Compression::Zlib => 2,
+        Compression::Uncompressed => 3,
+    }
+}
+
+/// Payload of a chunk too big for the region file, waiting to be written to its `c.X.Z.mcc` file.
+/// A `None` payload means that the external file is no longer used and must be removed
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+struct ExternalChunk {
+    x: usize,
+    z: usize,
+    payload: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RegionFile {
+    file_path: String,
+    region_x: Option<i32>,
+    region_z: Option<i32>,
+    chunks: Vec<RegionChunk>,
+    raw_bytes: Vec<u8>,
+    pending_external_chunks: Vec<ExternalChunk>,
+}
+
+impl RegionFile {
+    pub fn open<P: AsRef<Path>>(file_path: P) -> Result<RegionFile, RegionError> {
+        let file_buffer = fs::read(file_path.as_ref())?;
+        let mut region_file = RegionFile::from_buf(file_buffer)?;
+
+        region_file.file_path = file_path.as_ref().to_string_lossy().into_owned();
+        if let Some((region_x, region_z)) = region_coordinates(file_path.as_ref()) {
+            region_file.region_x = Some(region_x);
+            region_file.region_z = Some(region_z);
+        }
+
+        Ok(region_file)
+    }
+
+    pub fn from_buf(file_buffer: Vec<u8>) -> Result<RegionFile, RegionError> {
+        if file_buffer.len() < HEADER_SIZE {
+            return Err(RegionError::InvalidHeader);
+        }
+
+        let mut chunks = Vec::with_capacity(CHUNKS_PER_REGION);
+        let mut locations = Cursor::new(&file_buffer[..SECTOR_SIZE]);
+        let mut timestamps = Cursor::new(&file_buffer[SECTOR_SIZE..HEADER_SIZE]);
+
+        for index in 0..CHUNKS_PER_REGION {
+            let location = locations.read_u32::<BigEndian>()?;
+            let mut chunk = RegionChunk {
+                x: index % CHUNKS_PER_REGION_SIDE,
+                z: index / CHUNKS_PER_REGION_SIDE,
+                sector_offset: location >> 8,
+                sector_count: (location & 0xFF) as u8,
+                timestamp: timestamps.read_u32::<BigEndian>()?,
+                length: 0,
+                compression_type: 0,
+            };
+
+            if chunk.is_present() {
+                // a chunk pointing outside of the file keeps a length of 0: only reading it fails,
+                // the other chunks of a corrupted region stay readable
+                let start = chunk.byte_offset();
+                let end = start + CHUNK_LENGTH_SIZE + CHUNK_COMPRESSION_SIZE;
+                if let Some(chunk_header) = file_buffer.get(start..end) {
+                    let mut chunk_header = Cursor::new(chunk_header);
+                    chunk.length = chunk_header.read_u32::<BigEndian>()?;
+                    chunk.compression_type = chunk_header.read_u8()?;
+                }
+            }
+
+            chunks.push(chunk);
+        }
+
+        Ok(RegionFile {
+            file_path: String::new(),
+            region_x: None,
+            region_z: None,
+            chunks,
+            raw_bytes: file_buffer,
+            pending_external_chunks: Vec::new(),
+        })
+    }
+
+    pub fn file_path(&self) -> &str {
+        &self.file_path
+    }
+
+    /// Region coordinates parsed from a `r.X.Z.mca` file name
+    pub fn region_coordinates(&self) -> Option<(i32, i32)> {
+        Some((self.region_x?, self.region_z?))
+    }
+
+    pub fn as_raw_bytes(&self) -> &Vec<u8> {
+        &self.raw_bytes
+    }
+
+    /// All the 1024 chunk entries of the header, including the ones which are not generated
+    pub fn chunks(&self) -> &Vec<RegionChunk> {
+        &self.chunks
+    }
+
+    pub fn present_chunks(&self) -> impl Iterator<Item = &RegionChunk> {
+        self.chunks.iter().filter(|chunk| chunk.is_present())
+    }
+
+    pub fn chunk(&self, x: usize, z: usize) -> Result<&RegionChunk, RegionError> {
+        Ok(&self.chunks[chunk_index(x, z)?])
+    }
+
+    /// Compressed payload of a chunk, as stored in the region file or in its external `.mcc` file
+    pub fn chunk_payload(&self, x: usize, z: usize) -> Result<Option<Vec<u8>>, RegionError> {
+        let chunk = self.chunk(x, z)?;
+        if !chunk.is_present() {
+            return Ok(None);
+        }
+
+        if chunk.is_external() {
+            if let Some(external_chunk) = self.pending_external_chunk(x, z) {
+                return Ok(external_chunk.payload.clone());
+            }
+
+            let mcc_path = self.external_chunk_path(x, z)?;
+            return match fs::read(&mcc_path) {
+                Ok(payload) => Ok(Some(payload)),
+                Err(_) => Err(RegionError::MissingExternalChunk(
+                    mcc_path.to_string_lossy().into_owned(),
+                )),
+            };
+        }
+
+        let start = chunk.byte_offset() + CHUNK_LENGTH_SIZE + CHUNK_COMPRESSION_SIZE;
+        let end = chunk.byte_offset() + CHUNK_LENGTH_SIZE + chunk.length as usize;
+        let allocated_end = chunk.byte_offset() + chunk.sector_count as usize * SECTOR_SIZE;
+        if chunk.length == 0 || end > allocated_end {
+            return Err(RegionError::InvalidChunkPosition(x, z));
+        }
+
+        match self.raw_bytes.get(start..end) {
+            Some(payload) => Ok(Some(payload.to_vec())),
+            None => Err(RegionError::InvalidChunkPosition(x, z)),
+        }
+    }
+
+    /// Decompress and parse a chunk. The byte positions of the NbtTags are relative to the
+    /// decompressed chunk payload, so they can be used with the hex dump like for any NbtFile
+    pub fn read_chunk(&self, x: usize, z: usize) -> Result<Option<nbt::NbtData>, RegionError> {
+        let chunk = *self.chunk(x, z)?;
+        let payload = match self.chunk_payload(x, z)? {
+            Some(payload) => payload,
+            None => return Ok(None),
+        };
+
+        let compression = match chunk.compression() {
+            Some(compression) => compression,
+            None => return Err(RegionError::UnsupportedCompression(chunk.compression_type)),
+        };
+
+        let nbtdata = nbt::NbtData::from_buf(compression.decompress(&payload)?)?;
+        Ok(Some(nbtdata))
+    }
+
+    /// Same as [`RegionFile::read_chunk`], wrapped in a NbtFile to get the formatted output and the hex dump
+    pub fn read_chunk_file(&self, x: usize, z: usize) -> Result<Option<NbtFile>, RegionError> {
+        let chunk = *self.chunk(x, z)?;
+        let nbtdata = match self.read_chunk(x, z)? {
+            Some(nbtdata) => nbtdata,
+            None => return Ok(None),
+        };
+
+        let file_path = format!("{}[{}, {}]", self.file_path, x, z);
+        // read_chunk already checked that the compression is supported
+        let compression = chunk.compression().unwrap_or_default();
+        Ok(Some(NbtFile::from_nbtdata(file_path, compression, nbtdata)))
+    }
+
+    /// Serialize, compress and store a chunk, updating the location and timestamp tables.
+    ///
+    /// The chunk keeps its sectors if it still fits in them, otherwise it is moved to the first free
+    /// sectors big enough for it, or appended at the end of the file. The bytes of the other chunks are
+    /// never touched. Chunks bigger than 255 sectors are stored in an external `c.X.Z.mcc` file.
+    /// Changes are only written to disk by [`RegionFile::save`]
+    pub fn write_chunk(
+        &mut self,
+        x: usize,
+        z: usize,
+        nbtdata: &nbt::NbtData,
+        compression: Compression,
+    ) -> Result<(), RegionError> {
+        let index = chunk_index(x, z)?;
+        let compressed_data = compression.compress(&nbtdata.to_bytes()?)?;
+        let mut compression_type = compression_into_type(compression);
+
+        let mut sector_count = sectors_for(compressed_data.len());
+        let in_region_data = if sector_count > MAX_CHUNK_SECTORS {
+            // only the header stays in the region file, the payload goes to c.X.Z.mcc
+            compression_type |= EXTERNAL_CHUNK_FLAG;
+            sector_count = 1;
+            self.set_pending_external_chunk(x, z, Some(compressed_data));
+            Vec::new()
+        } else {
+            if self.chunks[index].is_external() {
+                self.set_pending_external_chunk(x, z, None);
+            }
+            compressed_data
+        };
+
+        let sector_offset = self.allocate_sectors(index, sector_count);
+        let start = sector_offset * SECTOR_SIZE;
+        let end = start + sector_count * SECTOR_SIZE;
+        if self.raw_bytes.len() < end {
+            self.raw_bytes.resize(end, 0);
+        }
+
+        let length = (in_region_data.len() + CHUNK_COMPRESSION_SIZE) as u32;
+        let mut sectors = Vec::with_capacity(sector_count * SECTOR_SIZE);
+        sectors.write_u32::<BigEndian>(length)?;
+        sectors.write_u8(compression_type)?;
+        sectors.extend_from_slice(&in_region_data);
+        sectors.resize(sector_count * SECTOR_SIZE, 0);
+        self.raw_bytes[start..end].copy_from_slice(&sectors);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as u32)
+            .unwrap_or_default();
+
+        let chunk = &mut self.chunks[index];
+        chunk.sector_offset = sector_offset as u32;
+        chunk.sector_count = sector_count as u8;
+        chunk.timestamp = timestamp;
+        chunk.length = length;
+        chunk.compression_type = compression_type;
+
+        let location = (chunk.sector_offset << 8) | chunk.sector_count as u32;
+        self.raw_bytes[index * 4..index * 4 + 4].copy_from_slice(&location.to_be_bytes());
+        self.raw_bytes[SECTOR_SIZE + index * 4..SECTOR_SIZE + index * 4 + 4]
+            .copy_from_slice(&timestamp.to_be_bytes());
+
+        Ok(())
+    }
+
+    /// Write the region file, and the external chunks, back to [`RegionFile::file_path`]
+    pub fn save(&mut self) -> Result<(), RegionError> {
+        if self.file_path.is_empty() {
+            return Err(RegionError::MissingFilePath);
+        }
+
+        // resolve all the paths first, so that nothing is written if one of them is unknown
+        let mut external_chunks = Vec::with_capacity(self.pending_external_chunks.len());
+        for external_chunk in &self.pending_external_chunks {
+            let mcc_path = self.external_chunk_path(external_chunk.x, external_chunk.z)?;
+            external_chunks.push((mcc_path, &external_chunk.payload));
+        }
+
+        for (mcc_path, payload) in external_chunks {
+            match payload {
+                Some(payload) => fs::write(&mcc_path, payload)?,
+                None => {
+                    if mcc_path.exists() {
+                        fs::remove_file(&mcc_path)?
+                    }
+                }
+            }
+        }
+        fs::write(&self.file_path, &self.raw_bytes)?;
+        self.pending_external_chunks.clear();
+
+        Ok(())
+    }
+
+    pub fn save_as<P: AsRef<Path>>(&mut self, file_path: P) -> Result<(), RegionError> {
+        self.file_path = file_path.as_ref().to_string_lossy().into_owned();
+        if let Some((region_x, region_z)) = region_coordinates(file_path.as_ref()) {
+            self.region_x = Some(region_x);
+            self.region_z = Some(region_z);
+        }
+        self.save()
+    }
+
+    fn pending_external_chunk(&self, x: usize, z: usize) -> Option<&ExternalChunk> {
+        self.pending_external_chunks
+            .iter()
+            .find(|external_chunk| external_chunk.x == x && external_chunk.z == z)
+    }
+
+    fn set_pending_external_chunk(&mut self, x: usize, z: usize, payload: Option<Vec<u8>>) {
+        self.pending_external_chunks
+            .retain(|external_chunk| external_chunk.x != x || external_chunk.z != z);
+        self.pending_external_chunks
+            .push(ExternalChunk { x, z, payload });
+    }
+
+    /// First sector of a free run of `sector_count` sectors for the chunk at `index`
+    fn allocate_sectors(&self, index: usize, sector_count: usize) -> usize {
+        let current = self.chunks[index];
+        // a chunk pointing outside of the file is moved, its sectors do not exist
+        if current.is_present()
+            && current.byte_offset() < self.raw_bytes.len()
+            && sector_count <= current.sector_count as usize
+        {
+            return current.sector_offset as usize;
+        }
+
+        // sectors 0 and 1 are the location and timestamp tables
+        let total_sectors = self
+            .raw_bytes
+            .len()
+            .div_ceil(SECTOR_SIZE)
+            .max(HEADER_SIZE / SECTOR_SIZE);
+        let mut used_sectors = vec![false; total_sectors];
+        used_sectors[..HEADER_SIZE / SECTOR_SIZE].fill(true);
+
+        for (chunk_index, chunk) in self.chunks.iter().enumerate() {
+            if chunk_index == index || !chunk.is_present() {
+                continue;
+            }
+            let start = (chunk.sector_offset as usize).min(total_sectors);
+            let end = (start + chunk.sector_count as usize).min(total_sectors);
+            used_sectors[start..end].fill(true);
+        }
+
+        // a free run can also continue past the end of the file
+        let mut run_start = 0;
+        let mut run_len = 0;
+        for (sector, used) in used_sectors.iter().enumerate() {
+            if *used {
+                run_len = 0;
+                continue;
+            }
+            if run_len == 0 {
+                run_start = sector;
+            }
+            run_len += 1;
+            if run_len == sector_count {
+                return run_start;
+            }
+        }
+
+        if run_len > 0 {
+            run_start
+        } else {
+            total_sectors
+        }
+    }
+
+    fn external_chunk_path(&self, x: usize, z: usize) -> Result<PathBuf, RegionError> {
+        let (region_x, region_z) = match self.region_coordinates() {
+            Some(coordinates) => coordinates,
+            None => {
+                return Err(RegionError::MissingExternalChunk(format!(
+                    "region coordinates of {:?} are unknown",
+                    self.file_path
+                )))
+            }
+        };
+
+        let chunk_x = region_x * CHUNKS_PER_REGION_SIDE as i32 + x as i32;
+        let chunk_z = region_z * CHUNKS_PER_REGION_SIDE as i32 + z as i32;
+        let mcc_name = format!("c.{}.{}.mcc", chunk_x, chunk_z);
+
+        Ok(match Path::new(&self.file_path).parent() {
+            Some(directory) => directory.join(mcc_name),
+            None => PathBuf::from(mcc_name),
+        })
+    }
+}
+
+fn sectors_for(len: usize) -> usize {
+    (len + CHUNK_LENGTH_SIZE + CHUNK_COMPRESSION_SIZE).div_ceil(SECTOR_SIZE)
+}
+
+pub(crate) fn chunk_index(x: usize, z: usize) -> Result<usize, RegionError> {
+    if x >= CHUNKS_PER_REGION_SIDE || z >= CHUNKS_PER_REGION_SIDE {
+        return Err(RegionError::ChunkOutOfBounds(x, z));
+    }
+    Ok(x + z * CHUNKS_PER_REGION_SIDE)
+}
+
+/// Parse the region coordinates from a `r.X.Z.mca` (or `.mcr`) file name
+fn region_coordinates(file_path: &Path) -> Option<(i32, i32)> {
+    let file_name = file_path.file_name()?.to_str()?;
+    let mut parts = file_name.split('.');
+
+    if parts.next()? != "r" {
+        return None;
+    }
+    let region_x = parts.next()?.parse().ok()?;
+    let region_z = parts.next()?.parse().ok()?;
+
+    match parts.next()? {
+        "mca" | "mcr" => Some((region_x, region_z)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_nbt() -> Vec<u8> {
+        // root compound "a" with an Int "b" = 7
+        vec![
+            0x0A, 0x00, 0x01, b'a', 0x03, 0x00, 0x01, b'b', 0x00, 0x00, 0x00, 0x07, 0x00,
+        ]
+    }
+
+    /// Build a region file with a single chunk at (x, z) stored in sector 2
+    fn region_with_chunk(x: usize, z: usize, compression_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buffer = vec![0u8; HEADER_SIZE + SECTOR_SIZE];
+        let index = x + z * CHUNKS_PER_REGION_SIDE;
+
+        buffer[index * 4..index * 4 + 4].copy_from_slice(&((2u32 << 8) | 1).to_be_bytes());
+        buffer[SECTOR_SIZE + index * 4..SECTOR_SIZE + index * 4 + 4]
+            .copy_from_slice(&1234u32.to_be_bytes());
+
+        let length = (payload.len() + 1) as u32;
+        buffer[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&length.to_be_bytes());
+        buffer[HEADER_SIZE + 4] = compression_type;
+        buffer[HEADER_SIZE + 5..HEADER_SIZE + 5 + payload.len()].copy_from_slice(payload);
+        buffer
+    }
+
+    #[test]
+    fn test_region_file_header_too_small() {
+        let result = RegionFile::from_buf(vec![0u8; HEADER_SIZE - 1]);
+        assert!(matches!(result, Err(RegionError::InvalidHeader)));
+    }
+
+    #[test]
+    fn test_region_file_empty() {
+        let region_file = RegionFile::from_buf(vec![0u8; HEADER_SIZE]).unwrap();
+        assert_eq!(region_file.chunks().len(), CHUNKS_PER_REGION);
+        assert_eq!(region_file.present_chunks().count(), 0);
+        assert!(region_file.read_chunk(0, 0).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_region_file_chunk_header() {
+        let payload = Compression::Zlib.compress(&raw_nbt()).unwrap();
+        let region_file = RegionFile::from_buf(region_with_chunk(3, 5, 2, &payload)).unwrap();
+
+        let chunk = region_file.chunk(3, 5).unwrap();
+        assert!(chunk.is_present());
+        assert!(!chunk.is_external());
+        assert_eq!(chunk.sector_offset(), 2);
+        assert_eq!(chunk.sector_count(), 1);
+        assert_eq!(chunk.timestamp(), 1234);
+        assert_eq!(chunk.length() as usize, payload.len() + 1);
+        assert_eq!(chunk.compression(), Some(Compression::Zlib));
+        assert_eq!(region_file.present_chunks().count(), 1);
+    }
+
+    #[test]
+    fn test_region_file_read_chunk() {
+        for (compression_type, compression) in [
+            (1, Compression::Gzip),
+            (2, Compression::Zlib),
+            (3, Compression::Uncompressed),
+        ] {
+            let payload = compression.compress(&raw_nbt()).unwrap();
+            let region_file =
+                RegionFile::from_buf(region_with_chunk(0, 0, compression_type, &payload)).unwrap();
+
+            let nbtdata = region_file.read_chunk(0, 0).unwrap().unwrap();
+            assert_eq!(nbtdata.raw_bytes(), &raw_nbt());
+            assert_eq!(nbtdata.nbt_tags()[1].name(), "b");
+            assert_eq!(nbtdata.nbt_tags()[1].value(), &nbt::NbtTagType::Int(7));
+            // positions are relative to the decompressed chunk payload
+            assert_eq!(nbtdata.nbt_tags()[1].position().byte_start_all(), 4);
+        }
+    }
+
+    #[test]
+    fn test_region_file_unsupported_compression() {
+        let region_file = RegionFile::from_buf(region_with_chunk(0, 0, 4, &raw_nbt())).unwrap();
+        let result = region_file.read_chunk(0, 0);
+        assert!(matches!(
+            result,
+            Err(RegionError::UnsupportedCompression(4))
+        ));
+    }
+
+    #[test]
+    fn test_region_file_chunk_out_of_bounds() {
+        let region_file = RegionFile::from_buf(vec![0u8; HEADER_SIZE]).unwrap();
+        assert!(matches!(
+            region_file.chunk(32, 0),
+            Err(RegionError::ChunkOutOfBounds(32, 0))
+        ));
+    }
+
+    #[test]
+    fn test_region_file_chunk_outside_of_file() {
+        let payload = Compression::Zlib.compress(&raw_nbt()).unwrap();
+        let mut buffer = region_with_chunk(1, 0, 2, &payload);
+        buffer[0..4].copy_from_slice(&((5u32 << 8) | 1).to_be_bytes());
+        let mut region_file = RegionFile::from_buf(buffer).unwrap();
+
+        // only the broken chunk cannot be read
+        assert_eq!(region_file.chunk(0, 0).unwrap().length(), 0);
+        assert!(matches!(
+            region_file.read_chunk(0, 0),
+            Err(RegionError::InvalidChunkPosition(0, 0))
+        ));
+        assert_eq!(
+            region_file.read_chunk(1, 0).unwrap().unwrap().raw_bytes(),
+            &raw_nbt()
+        );
+
+        // writing it again moves it inside of the file
+        let nbtdata = nbt::NbtData::from_buf(raw_nbt()).unwrap();
+        region_file
+            .write_chunk(0, 0, &nbtdata, Compression::Zlib)
+            .unwrap();
+        assert_eq!(region_file.chunk(0, 0).unwrap().sector_offset(), 3);
+        assert_eq!(
+            region_file.as_raw_bytes().len(),
+            HEADER_SIZE + 2 * SECTOR_SIZE
+        );
+        assert_eq!(
+            region_file.read_chunk(0, 0).unwrap().unwrap().raw_bytes(),
+            &raw_nbt()
+        );
+    }
+
+    /// Empty directory for the files of a test, unique to the test run
+    fn test_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("nbtsniper_{}_{}", name, std::process::id()));
+        if directory.exists() {
+            fs::remove_dir_all(&directory).unwrap();
+        }
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn test_region_file_external_chunk() {
+        let directory = test_directory("region_external_chunk");
+
+        // chunk (1, 2) of region (-1, 0) is chunk (-31, 2) in the world
+        let payload = Compression::Zlib.compress(&raw_nbt()).unwrap();
+        fs::write(directory.join("c.-31.2.mcc"), &payload).unwrap();
+        let region_path = directory.join("r.-1.0.mca");
+        fs::write(
+            &region_path,
+            region_with_chunk(1, 2, 2 | EXTERNAL_CHUNK_FLAG, &[]),
+        )
+        .unwrap();
+
+        let region_file = RegionFile::open(&region_path).unwrap();
+        assert_eq!(region_file.region_coordinates(), Some((-1, 0)));
+        assert!(region_file.chunk(1, 2).unwrap().is_external());
+
+        let nbtdata = region_file.read_chunk(1, 2).unwrap().unwrap();
+        assert_eq!(nbtdata.raw_bytes(), &raw_nbt());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    /// Root compound "a" with `lists` lists of `len` Long
+    fn nbtdata_with_longs(lists: usize, len: i32) -> nbt::NbtData {
+        let mut buffer = vec![0x0A, 0x00, 0x01, b'a'];
+        for ii in 0..lists {
+            buffer.extend([0x09, 0x00, 0x01, b'0' + ii as u8, 0x04]);
+            buffer.extend(len.to_be_bytes());
+            for value in 0..len {
+                buffer.extend((value as i64).to_be_bytes());
+            }
+        }
+        buffer.push(0x00);
+        nbt::NbtData::from_buf(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_region_file_write_chunk_in_empty_region() {
+        let mut region_file = RegionFile::from_buf(vec![0u8; HEADER_SIZE]).unwrap();
+        let nbtdata = nbt::NbtData::from_buf(raw_nbt()).unwrap();
+        region_file
+            .write_chunk(4, 7, &nbtdata, Compression::Zlib)
+            .unwrap();
+
+        let chunk = region_file.chunk(4, 7).unwrap();
+        assert_eq!(chunk.sector_offset(), 2);
+        assert_eq!(chunk.sector_count(), 1);
+        assert_eq!(chunk.compression(), Some(Compression::Zlib));
+        assert!(chunk.timestamp() > 0);
+        assert_eq!(region_file.as_raw_bytes().len(), HEADER_SIZE + SECTOR_SIZE);
+
+        // the header written in the raw bytes is the same we keep in memory
+        let reparsed = RegionFile::from_buf(region_file.as_raw_bytes().clone()).unwrap();
+        assert_eq!(reparsed.chunk(4, 7).unwrap(), chunk);
+        assert_eq!(
+            reparsed.read_chunk(4, 7).unwrap().unwrap().raw_bytes(),
+            &raw_nbt()
+        );
+    }
+
+    #[test]
+    fn test_region_file_write_chunk_grow_and_shrink() {
+        let mut region_file = RegionFile::from_buf(vec![0u8; HEADER_SIZE]).unwrap();
+        let small = nbt::NbtData::from_buf(raw_nbt()).unwrap();
+        // 2 lists of 700 Long take 3 sectors uncompressed
+        let big = nbtdata_with_longs(2, 700);
+
+        region_file
+            .write_chunk(0, 0, &small, Compression::Uncompressed)
+            .unwrap();
+        region_file
+            .write_chunk(1, 0, &small, Compression::Uncompressed)
+            .unwrap();
+        let untouched_chunk = *region_file.chunk(1, 0).unwrap();
+        let untouched_bytes = region_file.as_raw_bytes()[3 * SECTOR_SIZE..4 * SECTOR_SIZE].to_vec();
+
+        // (0, 0) does not fit anymore in sector 2, so it is moved after (1, 0)
+        region_file
+            .write_chunk(0, 0, &big, Compression::Uncompressed)
+            .unwrap();
+        let chunk = *region_file.chunk(0, 0).unwrap();
+        assert_eq!(chunk.sector_offset(), 4);
+        assert_eq!(chunk.sector_count(), 3);
+        assert_eq!(region_file.chunk(1, 0).unwrap(), &untouched_chunk);
+        assert_eq!(
+            &region_file.as_raw_bytes()[3 * SECTOR_SIZE..4 * SECTOR_SIZE],
+            &untouched_bytes[..]
+        );
+        assert_eq!(
+            region_file.read_chunk(0, 0).unwrap().unwrap().raw_bytes(),
+            big.raw_bytes()
+        );
+
+        // a new chunk reuses the sector freed by (0, 0)
+        region_file
+            .write_chunk(2, 0, &small, Compression::Uncompressed)
+            .unwrap();
+        assert_eq!(region_file.chunk(2, 0).unwrap().sector_offset(), 2);
+
+        // shrinking keeps the same offset
+        region_file
+            .write_chunk(0, 0, &small, Compression::Uncompressed)
+            .unwrap();
+        let chunk = *region_file.chunk(0, 0).unwrap();
+        assert_eq!(chunk.sector_offset(), 4);
+        assert_eq!(chunk.sector_count(), 1);
+        assert_eq!(
+            region_file.read_chunk(0, 0).unwrap().unwrap().raw_bytes(),
+            &raw_nbt()
+        );
+    }
+
+    #[test]
+    fn test_region_file_write_external_chunk() {
+        let directory = test_directory("region_write_external_chunk");
+        let region_path = directory.join("r.1.0.mca");
+        let mcc_path = directory.join("c.33.0.mcc");
+
+        // 5 lists of 30000 Long are more than 255 sectors uncompressed
+        let big = nbtdata_with_longs(5, 30000);
+        let mut region_file = RegionFile::from_buf(vec![0u8; HEADER_SIZE]).unwrap();
+        region_file
+            .write_chunk(1, 0, &big, Compression::Uncompressed)
+            .unwrap();
+
+        let chunk = *region_file.chunk(1, 0).unwrap();
+        assert!(chunk.is_external());
+        assert_eq!(chunk.sector_count(), 1);
+        assert_eq!(chunk.length(), 1);
+        region_file.save_as(&region_path).unwrap();
+        assert!(mcc_path.exists());
+
+        let reopened = RegionFile::open(&region_path).unwrap();
+        assert_eq!(
+            reopened.read_chunk(1, 0).unwrap().unwrap().raw_bytes(),
+            big.raw_bytes()
+        );
+
+        // once the chunk fits again in the region, the external file is removed
+        let mut reopened = reopened;
+        let small = nbt::NbtData::from_buf(raw_nbt()).unwrap();
+        reopened
+            .write_chunk(1, 0, &small, Compression::Gzip)
+            .unwrap();
+        reopened.save().unwrap();
+        assert!(!mcc_path.exists());
+        assert!(!reopened.chunk(1, 0).unwrap().is_external());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_region_file_save_without_path() {
+        let mut region_file = RegionFile::from_buf(vec![0u8; HEADER_SIZE]).unwrap();
+        assert!(matches!(
+            region_file.save(),
+            Err(RegionError::MissingFilePath)
+        ));
+    }
+
+    #[test]
+    fn test_region_coordinates() {
+        assert_eq!(
+            region_coordinates(Path::new("world/region/r.-1.2.mca")),
+            Some((-1, 2))
+        );
+        assert_eq!(region_coordinates(Path::new("r.0.0.mcr")), Some((0, 0)));
+        assert_eq!(region_coordinates(Path::new("level.dat")), None);
+    }
+}
@@ -1,6 +1,4 @@
-use std::any::Any;
-
-use nbtsniper::{nbt, NbtFile};
+use nbtsniper::{nbt, Compression, NbtFile, NbtFileError};
 
 #[test]
 fn bigtest() {
@@ -8,24 +6,25 @@ fn bigtest() {
 
     println!("-------------------------");
     println!("Debug Output");
-    print!("{:?}\n", mc_bin);
+    println!("{:?}", mc_bin);
 
-    println!("");
+    println!();
     println!("-------------------------");
     println!("Printing JSON to file");
-    mc_bin.to_json("tests/files/output/out_bigtest.json");
+    let out_path = std::env::temp_dir().join("out_bigtest.json");
+    mc_bin.to_json(out_path.to_str().unwrap()).unwrap();
 
     /* println!("");
     println!("-------------------------");
     println!("Raw data using dump_hex() method");
     print!("{}\n", mc_bin.hex_dump()); */
 
-    println!("");
+    println!();
     println!("-------------------------");
     println!("Iterate NbtTags");
     mc_bin.nbt_tags().iter().for_each(|x| println!("{:?}", x));
 
-    println!("");
+    println!();
     println!("-------------------------");
     println!("Print Hashmap of NbtTags");
     println!("{:?}\n", mc_bin.nbt_hashmap());
@@ -34,7 +33,7 @@ fn bigtest() {
         println!("doubleTest: {:?}", mc_bin.nbt_tags()[nbt_index]);
     }
 
-    println!("");
+    println!();
     println!("-------------------------");
     println!("Raw data using as_bytes() method");
     println!("{:?}\n", mc_bin.as_raw_bytes());
@@ -237,3 +236,320 @@ fn test_bigtest_start_end_bytes_are_continuous() {
         assert_eq!(curr_pos + 1, next_pos);
     }
 }
+
+#[test]
+fn test_bigtest_compression_is_detected() {
+    let gzip_bin = NbtFile::read("tests/files/bigtest.nbt".to_string());
+    let zlib_bin = NbtFile::read("tests/files/bigtest_zlib.nbt".to_string());
+    let raw_bin = NbtFile::read("tests/files/bigtest_uncompressed.nbt".to_string());
+
+    assert_eq!(gzip_bin.compression(), Compression::Gzip);
+    assert_eq!(zlib_bin.compression(), Compression::Zlib);
+    assert_eq!(raw_bin.compression(), Compression::Uncompressed);
+
+    assert_eq!(gzip_bin.as_raw_bytes(), zlib_bin.as_raw_bytes());
+    assert_eq!(gzip_bin.as_raw_bytes(), raw_bin.as_raw_bytes());
+    assert_eq!(gzip_bin.nbt_tags(), raw_bin.nbt_tags());
+}
+
+#[test]
+fn test_bigtest_compression_override() {
+    let raw_bin = NbtFile::read_with_compression(
+        "tests/files/bigtest_uncompressed.nbt".to_string(),
+        Compression::Uncompressed,
+    );
+    let gzip_bin = NbtFile::read("tests/files/bigtest.nbt".to_string());
+
+    assert_eq!(raw_bin.compression(), Compression::Uncompressed);
+    assert_eq!(gzip_bin.nbt_tags(), raw_bin.nbt_tags());
+}
+
+#[test]
+fn test_try_read_missing_file() {
+    let result = NbtFile::try_read("tests/files/does_not_exist.nbt".to_string());
+    assert!(matches!(result, Err(NbtFileError::Io(_))));
+}
+
+#[test]
+fn test_try_read_truncated_file() {
+    let mut buffer = std::fs::read("tests/files/bigtest_uncompressed.nbt").unwrap();
+    buffer.truncate(buffer.len() / 2);
+    let path = std::env::temp_dir().join("truncated_bigtest.nbt");
+    std::fs::write(&path, buffer).unwrap();
+
+    let result = NbtFile::open(&path);
+    assert!(matches!(result, Err(NbtFileError::NbtRead(_))));
+}
+
+#[test]
+fn test_open_bigtest() {
+    let mc_bin = NbtFile::open("tests/files/bigtest.nbt").unwrap();
+    assert_eq!(mc_bin.file_path(), "tests/files/bigtest.nbt");
+    assert_eq!(mc_bin.nbt_tags()[0].name(), "Level");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_open_non_utf8_path() {
+    use std::os::unix::ffi::OsStrExt;
+
+    // 0xFF is never valid UTF-8
+    let mut file_name = format!("bigtest_{}_", std::process::id()).into_bytes();
+    file_name.extend(b"\xff.nbt");
+    let path = std::env::temp_dir().join(std::ffi::OsStr::from_bytes(&file_name));
+    std::fs::copy("tests/files/bigtest.nbt", &path).unwrap();
+
+    let mc_bin = NbtFile::open(&path).unwrap();
+    assert_eq!(mc_bin.nbt_tags()[0].name(), "Level");
+    assert!(mc_bin.file_path().ends_with("_\u{FFFD}.nbt"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_bigtest_write_round_trip_is_byte_identical() {
+    for file_path in ["tests/files/bigtest.nbt", "tests/files/inputs/bigtest.nbt"] {
+        let mc_bin = NbtFile::read(file_path.to_string());
+        let out_path = std::env::temp_dir().join("round_trip_bigtest.nbt");
+        mc_bin
+            .write(out_path.to_str().unwrap(), Compression::Uncompressed)
+            .unwrap();
+
+        let written = std::fs::read(&out_path).unwrap();
+        assert_eq!(&written, mc_bin.as_raw_bytes());
+    }
+}
+
+#[test]
+fn test_bigtest_write_with_compression() {
+    let mc_bin = NbtFile::read("tests/files/bigtest.nbt".to_string());
+
+    for compression in [Compression::Gzip, Compression::Zlib] {
+        let out_path =
+            std::env::temp_dir().join(format!("compressed_bigtest_{:?}.nbt", compression));
+        mc_bin
+            .write(out_path.to_str().unwrap(), compression)
+            .unwrap();
+
+        let written_bin = NbtFile::open(&out_path).unwrap();
+        assert_eq!(written_bin.compression(), compression);
+        assert_eq!(written_bin.as_raw_bytes(), mc_bin.as_raw_bytes());
+        assert_eq!(written_bin.nbt_tags(), mc_bin.nbt_tags());
+    }
+}
+
+#[test]
+fn test_read_non_compound_root() {
+    // root IntArray named "Colors" with 2 elements
+    let mut buffer = vec![11u8, 0, 6];
+    buffer.extend("Colors".as_bytes());
+    buffer.extend(&2i32.to_be_bytes());
+    buffer.extend(&7i32.to_be_bytes());
+    buffer.extend(&(-1i32).to_be_bytes());
+    let path = std::env::temp_dir().join("int_array_root.nbt");
+    std::fs::write(&path, &buffer).unwrap();
+    let path = path.to_str().unwrap().to_string();
+
+    assert!(matches!(
+        NbtFile::try_read(path.clone()),
+        Err(NbtFileError::NbtRead(_))
+    ));
+
+    let mut options = nbt::ParseOptions::default();
+    options.set_any_root_tag(true);
+    let mc_bin = NbtFile::try_read_with_options(path, &options).unwrap();
+    assert_eq!(mc_bin.nbt_tags().len(), 1);
+    assert_eq!(
+        mc_bin.nbt_tags()[0].value(),
+        &nbt::NbtTagType::IntArray(vec![7, -1])
+    );
+
+    let debug_output = format!("{:?}", mc_bin);
+    assert!(debug_output.starts_with("Colors[IntArray]"));
+
+    let out_path = std::env::temp_dir().join("int_array_root.json");
+    mc_bin.to_json(out_path.to_str().unwrap()).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&out_path).unwrap()).unwrap();
+    assert_eq!(json[0]["value"]["IntArray"], serde_json::json!([7, -1]));
+}
+
+#[test]
+fn test_from_reader_bigtest() {
+    let mc_bin = NbtFile::read("tests/files/bigtest.nbt".to_string());
+
+    // compressed bytes, e.g. piped through stdin
+    let compressed = std::fs::read("tests/files/bigtest.nbt").unwrap();
+    let reader_bin =
+        NbtFile::from_reader(compressed.as_slice(), &nbt::ParseOptions::default()).unwrap();
+    assert_eq!(reader_bin.file_path(), "");
+    assert_eq!(reader_bin.compression(), Compression::Gzip);
+    assert_eq!(reader_bin.nbt_tags(), mc_bin.nbt_tags());
+
+    let nbt_data = nbt::NbtData::from_slice(mc_bin.as_raw_bytes()).unwrap();
+    assert_eq!(nbt_data.nbt_tags(), mc_bin.nbt_tags());
+}
+
+#[test]
+fn test_lazy_values_bigtest() {
+    let mut options = nbt::ParseOptions::default();
+    options.set_lazy_values(true);
+    let lazy_bin =
+        NbtFile::try_read_with_options("tests/files/bigtest.nbt".to_string(), &options).unwrap();
+    let mc_bin = NbtFile::read("tests/files/bigtest.nbt".to_string());
+
+    // the Debug output decodes the skipped values
+    assert_eq!(format!("{:?}", lazy_bin), format!("{:?}", mc_bin));
+    assert!(lazy_bin
+        .nbt_tags()
+        .iter()
+        .any(|nbt_tag| nbt_tag.has_lazy_value()));
+}
+
+#[test]
+fn test_edit_bigtest() {
+    let mut mc_bin = NbtFile::read("tests/files/bigtest.nbt".to_string());
+    let nbt_data = mc_bin.nbt_data_as_mut();
+
+    let int_test = nbt_data.tags_map()["intTest"];
+    nbt_data
+        .set_value(int_test, nbt::NbtTagType::Int(42))
+        .unwrap();
+    let nested = nbt_data.tags_map()["nested compound test"];
+    nbt_data.remove_subtree(nested).unwrap();
+    nbt_data
+        .rename(nbt_data.tags_map()["byteTest"], "renamedByte".to_string())
+        .unwrap();
+
+    let out_path = std::env::temp_dir().join("edited_bigtest.nbt");
+    mc_bin
+        .write(out_path.to_str().unwrap(), Compression::Gzip)
+        .unwrap();
+
+    let edited_bin = NbtFile::open(&out_path).unwrap();
+    let tags_map = edited_bin.nbt_hashmap();
+    assert_eq!(
+        edited_bin.nbt_tags()[tags_map["intTest"]].value(),
+        &nbt::NbtTagType::Int(42)
+    );
+    assert!(!tags_map.contains_key("nested compound test"));
+    assert!(!tags_map.contains_key("egg"));
+    assert!(tags_map.contains_key("renamedByte"));
+    assert_eq!(edited_bin.nbt_tags().len(), mc_bin.nbt_tags().len());
+}
+
+#[test]
+fn test_patch_value_bigtest() {
+    let mut mc_bin = NbtFile::read("tests/files/bigtest.nbt".to_string());
+    let nbt_data = mc_bin.nbt_data_as_mut();
+
+    // 2147483647 -> 2147483646 only changes the last byte of the Int
+    let int_test = nbt_data.tags_map()["intTest"];
+    let byte_end = nbt_data.nbt_tags()[int_test]
+        .position()
+        .byte_end_value()
+        .unwrap();
+    let changed = nbt_data
+        .patch_value(int_test, nbt::NbtTagType::Int(2147483646))
+        .unwrap();
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0].byte_start(), byte_end);
+    assert_eq!(changed[0].byte_end(), byte_end);
+    assert_eq!(&nbt_data.to_bytes().unwrap(), nbt_data.raw_bytes());
+
+    let debug_output = format!("{:?}", mc_bin);
+    assert!(debug_output.contains("intTest[Int]: 2147483646"));
+    assert!(debug_output.contains("7F FF  FF FE*"));
+    assert!(debug_output.contains(&format!("Patched Bytes[{}:{}]", byte_end, byte_end)));
+}
+
+#[test]
+fn test_nbt_value_bigtest() {
+    let mc_bin = NbtFile::read("tests/files/bigtest.nbt".to_string());
+    let roots = mc_bin.nbt_data().to_value().unwrap();
+
+    let level = roots.get("Level").unwrap().as_compound().unwrap();
+    assert_eq!(level.get("intTest"), Some(&nbt::NbtValue::Int(2147483647)));
+    assert_eq!(
+        level.get("listTest (long)"),
+        Some(&nbt::NbtValue::List(nbt::NbtList::Long(vec![
+            11, 12, 13, 14, 15
+        ])))
+    );
+
+    // back to the flat tree, byte for byte
+    let nbt_data = nbt::NbtData::from_value(&roots).unwrap();
+    assert_eq!(nbt_data.raw_bytes(), mc_bin.as_raw_bytes());
+    assert_eq!(nbt_data.nbt_tags(), mc_bin.nbt_tags());
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct BigtestFood {
+    name: String,
+    value: f32,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct BigtestNested {
+    ham: BigtestFood,
+    egg: BigtestFood,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct BigtestListEntry {
+    name: String,
+    #[serde(rename = "created-on")]
+    created_on: i64,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BigtestLevel {
+    long_test: i64,
+    short_test: i16,
+    string_test: String,
+    float_test: f32,
+    int_test: i32,
+    #[serde(rename = "nested compound test")]
+    nested_compound_test: BigtestNested,
+    #[serde(rename = "listTest (long)")]
+    list_test_long: Vec<i64>,
+    #[serde(rename = "listTest (compound)")]
+    list_test_compound: Vec<BigtestListEntry>,
+    byte_test: i8,
+    #[serde(
+        rename = "byteArrayTest (the first 1000 values of (n*n*255+n*7)%100, starting with n=0 (0, 62, 34, 16, 8, ...))",
+        with = "nbt::byte_array"
+    )]
+    byte_array_test: Vec<u8>,
+    double_test: f64,
+}
+
+#[test]
+fn test_serde_bigtest() {
+    let mc_bin = NbtFile::read("tests/files/bigtest.nbt".to_string());
+    let level: BigtestLevel = nbtsniper::from_bytes(mc_bin.as_raw_bytes()).unwrap();
+
+    assert_eq!(level.int_test, 2147483647);
+    assert_eq!(level.nested_compound_test.egg.name, "Eggbert");
+    assert_eq!(level.list_test_long, vec![11, 12, 13, 14, 15]);
+    assert_eq!(level.list_test_compound[1].created_on, 1264099775885);
+    assert_eq!(level.byte_array_test.len(), 1000);
+    assert_eq!(level.byte_array_test[1], 62);
+
+    // the fields not in the struct, e.g. ListCompoundListTest, are skipped
+    let bytes = nbtsniper::to_bytes(&level).unwrap();
+    let nbt_data = nbt::NbtData::from_buf(bytes.clone()).unwrap();
+    let roots = nbt_data.to_value().unwrap();
+    let written = roots.get("").unwrap().as_compound().unwrap();
+    assert!(matches!(
+        written.get(
+            "byteArrayTest (the first 1000 values of (n*n*255+n*7)%100, starting with n=0 (0, 62, 34, 16, 8, ...))"
+        ),
+        Some(nbt::NbtValue::ByteArray(_))
+    ));
+    assert_eq!(
+        nbtsniper::from_bytes::<BigtestLevel>(&bytes).unwrap(),
+        level
+    );
+}